@@ -0,0 +1,482 @@
+//! `#[derive(Uniforms)]` and `#[derive(Vertex)]`, re-exported by miniquad with the "derive" feature.
+//!
+//! The input is parsed by hand and the output generated as a string, pulling syn and quote
+//! would cost more compile time than all of miniquad.
+//!
+//! The generated code refers to `miniquad::`, the crate should be in scope under that name.
+
+use proc_macro::{Delimiter, Spacing, TokenStream, TokenTree};
+
+/// `impl miniquad::Uniforms` for a `#[repr(C)]` struct, one `UniformDesc` per field.
+///
+/// The `UniformType` is guessed from the field type: `f32`, `[f32; 2..4]`, `i32`/`u32` and
+/// their arrays, `[f32; 16]`/`[[f32; 4]; 4]` as `Mat4` and the glam vectors and matrices.
+/// Arrays of any of those become uniform arrays. `#[uniform(Float4)]` overrides the guess,
+/// `#[uniform(name = "Model")]` the name.
+///
+/// `#[uniforms(std140)]` additionally checks at compile time that every field is aligned
+/// the way std140 wants it.
+#[proc_macro_derive(Uniforms, attributes(uniform, uniforms))]
+pub fn derive_uniforms(input: TokenStream) -> TokenStream {
+    result(parse_struct(input).and_then(|input| uniforms_impl(&input)))
+}
+
+/// `impl miniquad::Vertex` for a `#[repr(C)]` struct, one `VertexAttribute` per field.
+///
+/// The `VertexFormat` is guessed from the field type: `f32`, `u8`, `i8`, `u16`, `i16`, `u32`
+/// and their arrays up to 4, `[f32; 16]`/`[[f32; 4]; 4]` as `Mat4` and the glam vectors and
+/// matrices. `#[vertex(Byte4Norm)]` overrides the guess, `#[vertex(name = "in_pos")]` the name.
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    result(parse_struct(input).and_then(|input| vertex_impl(&input)))
+}
+
+fn result(code: Result<String, String>) -> TokenStream {
+    let code = code.unwrap_or_else(|error| format!("compile_error!({error:?});"));
+    code.parse().unwrap()
+}
+
+/// `#[name(...)]`, `content` is what is inside the parenthesis.
+struct Attribute {
+    name: String,
+    content: Vec<TokenTree>,
+}
+
+struct Field {
+    name: String,
+    ty: String,
+    attributes: Vec<Attribute>,
+}
+
+struct Struct {
+    name: String,
+    attributes: Vec<Attribute>,
+    fields: Vec<Field>,
+}
+
+fn parse_attributes(tokens: &[TokenTree], i: &mut usize) -> Vec<Attribute> {
+    let mut attributes = vec![];
+    while let (Some(TokenTree::Punct(punct)), Some(TokenTree::Group(group))) =
+        (tokens.get(*i), tokens.get(*i + 1))
+    {
+        if punct.as_char() != '#' || group.delimiter() != Delimiter::Bracket {
+            break;
+        }
+        let attribute: Vec<TokenTree> = group.stream().into_iter().collect();
+        if let Some(TokenTree::Ident(name)) = attribute.first() {
+            let content = match attribute.get(1) {
+                Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+                    group.stream().into_iter().collect()
+                }
+                _ => vec![],
+            };
+            attributes.push(Attribute {
+                name: name.to_string(),
+                content,
+            });
+        }
+        *i += 2;
+    }
+    attributes
+}
+
+fn skip_visibility(tokens: &[TokenTree], i: &mut usize) {
+    if matches!(tokens.get(*i), Some(TokenTree::Ident(ident)) if ident.to_string() == "pub") {
+        *i += 1;
+        if matches!(tokens.get(*i), Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis)
+        {
+            *i += 1;
+        }
+    }
+}
+
+/// Split on the commas outside of generic arguments, `[T; N]` and `(A, B)` are groups already.
+fn split_commas(tokens: Vec<TokenTree>) -> Vec<Vec<TokenTree>> {
+    let mut items = vec![vec![]];
+    let mut depth = 0;
+    let mut arrow = false;
+    for token in tokens {
+        if let TokenTree::Punct(punct) = &token {
+            match punct.as_char() {
+                ',' if depth == 0 => {
+                    items.push(vec![]);
+                    continue;
+                }
+                '<' => depth += 1,
+                // `->` of fn pointer types does not close anything
+                '>' if !arrow => depth -= 1,
+                _ => {}
+            }
+            arrow = punct.as_char() == '-' && punct.spacing() == Spacing::Joint;
+        } else {
+            arrow = false;
+        }
+        items.last_mut().unwrap().push(token);
+    }
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+fn parse_struct(input: TokenStream) -> Result<Struct, String> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let mut i = 0;
+    let attributes = parse_attributes(&tokens, &mut i);
+    skip_visibility(&tokens, &mut i);
+    match tokens.get(i) {
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "struct" => {}
+        _ => return Err("only structs can be derived".to_string()),
+    }
+    let name = match tokens.get(i + 1) {
+        Some(TokenTree::Ident(ident)) => ident.to_string(),
+        _ => return Err("struct name expected".to_string()),
+    };
+    let body = match tokens.get(i + 2) {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => group.stream(),
+        Some(TokenTree::Punct(punct)) if punct.as_char() == '<' => {
+            return Err(format!("{name}: generic structs are not supported"))
+        }
+        _ => {
+            return Err(format!(
+                "{name}: only structs with named fields are supported"
+            ))
+        }
+    };
+
+    if !attributes.iter().any(|attribute| {
+        attribute.name == "repr"
+            && attribute
+                .content
+                .iter()
+                .any(|token| token.to_string() == "C")
+    }) {
+        return Err(format!(
+            "{name} should be #[repr(C)], miniquad relies on the field order"
+        ));
+    }
+
+    let mut fields = vec![];
+    for field in split_commas(body.into_iter().collect()) {
+        let mut i = 0;
+        let attributes = parse_attributes(&field, &mut i);
+        skip_visibility(&field, &mut i);
+        let field_name = match (field.get(i), field.get(i + 1)) {
+            (Some(TokenTree::Ident(ident)), Some(TokenTree::Punct(colon)))
+                if colon.as_char() == ':' =>
+            {
+                ident.to_string().trim_start_matches("r#").to_string()
+            }
+            _ => return Err(format!("{name}: field name expected")),
+        };
+        let ty: TokenStream = field[i + 2..].iter().cloned().collect();
+        fields.push(Field {
+            name: field_name,
+            ty: ty.to_string(),
+            attributes,
+        });
+    }
+    if fields.is_empty() {
+        return Err(format!("{name} has no fields"));
+    }
+
+    Ok(Struct {
+        name,
+        attributes,
+        fields,
+    })
+}
+
+/// `#[attribute(Format, name = "name")]` options of a field, in any order.
+fn field_options(
+    struct_name: &str,
+    field: &Field,
+    attribute: &str,
+) -> Result<(Option<String>, String), String> {
+    let mut format = None;
+    let mut name = field.name.clone();
+    for options in field.attributes.iter().filter(|a| a.name == attribute) {
+        for option in split_commas(options.content.clone()) {
+            match &option[..] {
+                [TokenTree::Ident(ident)] => format = Some(ident.to_string()),
+                [TokenTree::Ident(key), TokenTree::Punct(eq), TokenTree::Literal(value)]
+                    if key.to_string() == "name" && eq.as_char() == '=' =>
+                {
+                    let value = value.to_string();
+                    name = value
+                        .strip_prefix('"')
+                        .and_then(|value| value.strip_suffix('"'))
+                        .ok_or_else(|| {
+                            format!("{struct_name}::{}: name should be a string", field.name)
+                        })?
+                        .to_string();
+                }
+                _ => {
+                    return Err(format!(
+                        "{struct_name}::{}: unexpected #[{attribute}(...)] option",
+                        field.name
+                    ))
+                }
+            }
+        }
+    }
+    Ok((format, name))
+}
+
+/// `[T; N]` into `(T, N)`, `None` for anything else.
+fn array(ty: &str) -> Option<(&str, usize)> {
+    let inner = ty.strip_prefix('[')?.strip_suffix(']')?;
+    let separator = inner.rfind(';')?;
+    let count = inner[separator + 1..].trim().parse().ok()?;
+    Some((inner[..separator].trim(), count))
+}
+
+/// The last path segment, `glam::Vec4` into `Vec4`.
+fn type_name(ty: &str) -> &str {
+    ty.rsplit("::").next().unwrap().trim()
+}
+
+fn guess_uniform_type(ty: &str) -> Option<&'static str> {
+    let guess = match array(ty) {
+        Some((element, count)) => match (type_name(element), count) {
+            ("f32", 1) => "Float1",
+            ("f32", 2) => "Float2",
+            ("f32", 3) => "Float3",
+            ("f32", 4) => "Float4",
+            ("f32", 16) => "Mat4",
+            ("i32" | "u32", 1) => "Int1",
+            ("i32" | "u32", 2) => "Int2",
+            ("i32" | "u32", 3) => "Int3",
+            ("i32" | "u32", 4) => "Int4",
+            _ if count == 4 && guess_uniform_type(element) == Some("Float4") => "Mat4",
+            // an array of uniforms, the count comes from the size
+            _ => return guess_uniform_type(element),
+        },
+        None => match type_name(ty) {
+            "f32" => "Float1",
+            "Vec2" => "Float2",
+            "Vec3" => "Float3",
+            "Vec4" | "Quat" => "Float4",
+            "i32" | "u32" => "Int1",
+            "IVec2" | "UVec2" => "Int2",
+            "IVec3" | "UVec3" => "Int3",
+            "IVec4" | "UVec4" => "Int4",
+            "Mat4" => "Mat4",
+            _ => return None,
+        },
+    };
+    Some(guess)
+}
+
+fn guess_vertex_format(ty: &str) -> Option<&'static str> {
+    let (element, count) = array(ty).unwrap_or((ty, 1));
+    let guess = match (type_name(element), count) {
+        ("f32", 1) => "Float1",
+        ("f32", 2) | ("Vec2", 1) => "Float2",
+        ("f32", 3) | ("Vec3", 1) => "Float3",
+        ("f32", 4) | ("Vec4", 1) => "Float4",
+        ("f32", 16) | ("Mat4", 1) => "Mat4",
+        ("u8", 1) => "Byte1",
+        ("u8", 2) => "Byte2",
+        ("u8", 3) => "Byte3",
+        ("u8", 4) => "Byte4",
+        ("i8", 1) => "SByte1",
+        ("i8", 2) => "SByte2",
+        ("i8", 3) => "SByte3",
+        ("i8", 4) => "SByte4",
+        ("u16", 1) => "Short1",
+        ("u16", 2) => "Short2",
+        ("u16", 3) => "Short3",
+        ("u16", 4) => "Short4",
+        ("i16", 1) => "SShort1",
+        ("i16", 2) => "SShort2",
+        ("i16", 3) => "SShort3",
+        ("i16", 4) => "SShort4",
+        ("u32", 1) => "Int1",
+        ("u32", 2) => "Int2",
+        ("u32", 3) => "Int3",
+        ("u32", 4) => "Int4",
+        _ if count == 4 && guess_vertex_format(element) == Some("Float4") => "Mat4",
+        _ => return None,
+    };
+    Some(guess)
+}
+
+fn uniforms_impl(input: &Struct) -> Result<String, String> {
+    let name = &input.name;
+    let mut std140 = false;
+    for attribute in input.attributes.iter().filter(|a| a.name == "uniforms") {
+        for option in &attribute.content {
+            match option {
+                TokenTree::Ident(ident) if ident.to_string() == "std140" => std140 = true,
+                TokenTree::Punct(punct) if punct.as_char() == ',' => {}
+                _ => return Err(format!("{name}: unexpected #[uniforms(...)] option")),
+            }
+        }
+    }
+
+    let mut descs = String::new();
+    let mut checks = String::new();
+    for field in &input.fields {
+        let (uniform_type, uniform_name) = field_options(name, field, "uniform")?;
+        let uniform_type = match uniform_type {
+            Some(uniform_type) => uniform_type,
+            None => guess_uniform_type(&field.ty)
+                .ok_or_else(|| {
+                    format!(
+                        "{name}::{}: no UniformType for `{}`, specify it with #[uniform(Float4)] or similar",
+                        field.name, field.ty
+                    )
+                })?
+                .to_string(),
+        };
+        let ty = &field.ty;
+        descs += &format!(
+            "miniquad::UniformDesc::new({uniform_name:?}, miniquad::UniformType::{uniform_type})
+                .array(::core::mem::size_of::<{ty}>() / miniquad::UniformType::{uniform_type}.size()),"
+        );
+        checks += &format!(
+            "let size = ::core::mem::size_of::<{ty}>();
+            let uniform_size = miniquad::UniformType::{uniform_type}.size();
+            assert!(size > 0 && size % uniform_size == 0, {:?});",
+            format!(
+                "{name}::{}: the field size is not a multiple of UniformType::{uniform_type}",
+                field.name
+            )
+        );
+        if std140 {
+            let alignment = match &uniform_type[..] {
+                "Float1" | "Int1" => 4,
+                "Float2" | "Int2" => 8,
+                _ => 16,
+            };
+            checks += &format!(
+                "let array = size > uniform_size;
+                assert!(!array || uniform_size % 16 == 0, {:?});
+                let alignment = if array {{ 16 }} else {{ {alignment} }};
+                assert!(offset % alignment == 0, {:?});",
+                format!(
+                    "{name}::{}: std140 array elements are 16 bytes apart, use 4 component elements",
+                    field.name
+                ),
+                format!(
+                    "{name}::{}: misaligned for std140, add padding before the field",
+                    field.name
+                ),
+            );
+        }
+        checks += "offset += size;";
+    }
+
+    Ok(format!(
+        "impl miniquad::Uniforms for {name} {{
+            fn uniform_descs() -> ::std::vec::Vec<miniquad::UniformDesc> {{
+                ::std::vec![{descs}]
+            }}
+        }}
+        #[allow(clippy::all)]
+        const _: () = {{
+            let mut offset = 0;
+            {checks}
+            assert!(offset == ::core::mem::size_of::<{name}>(), {:?});
+        }};",
+        format!("{name} has padding, miniquad reads uniforms tightly packed")
+    ))
+}
+
+fn vertex_impl(input: &Struct) -> Result<String, String> {
+    let name = &input.name;
+    let mut attributes = String::new();
+    let mut checks = String::new();
+    for field in &input.fields {
+        let (format, attribute_name) = field_options(name, field, "vertex")?;
+        let format = match format {
+            Some(format) => format,
+            None => guess_vertex_format(&field.ty)
+                .ok_or_else(|| {
+                    format!(
+                        "{name}::{}: no VertexFormat for `{}`, specify it with #[vertex(Byte4Norm)] or similar",
+                        field.name, field.ty
+                    )
+                })?
+                .to_string(),
+        };
+        let ty = &field.ty;
+        attributes += &format!(
+            "miniquad::VertexAttribute::with_buffer({attribute_name:?}, miniquad::VertexFormat::{format}, buffer_index),"
+        );
+        checks += &format!(
+            "let size = ::core::mem::size_of::<{ty}>();
+            assert!(size == miniquad::VertexFormat::{format}.size_bytes() as usize, {:?});
+            offset += size;",
+            format!(
+                "{name}::{}: the field size does not match VertexFormat::{format}",
+                field.name
+            )
+        );
+    }
+
+    Ok(format!(
+        "impl miniquad::Vertex for {name} {{
+            fn attributes(buffer_index: usize) -> ::std::vec::Vec<miniquad::VertexAttribute> {{
+                ::std::vec![{attributes}]
+            }}
+        }}
+        #[allow(clippy::all)]
+        const _: () = {{
+            let mut offset = 0;
+            {checks}
+            assert!(offset == ::core::mem::size_of::<{name}>(), {:?});
+        }};",
+        format!("{name} has padding, miniquad expects tightly packed vertex attributes")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn array_types() {
+        assert_eq!(array("[f32 ; 4]"), Some(("f32", 4)));
+        assert_eq!(array("[[f32 ; 4] ; 4]"), Some(("[f32 ; 4]", 4)));
+        assert_eq!(array("[glam :: Vec4 ; 8]"), Some(("glam :: Vec4", 8)));
+        assert_eq!(array("f32"), None);
+        assert_eq!(array("[f32 ; N]"), None);
+        assert_eq!(array("(f32 , f32)"), None);
+    }
+
+    #[test]
+    fn uniform_types() {
+        assert_eq!(guess_uniform_type("f32"), Some("Float1"));
+        assert_eq!(guess_uniform_type("[f32 ; 3]"), Some("Float3"));
+        assert_eq!(guess_uniform_type("[u32 ; 2]"), Some("Int2"));
+        assert_eq!(guess_uniform_type("glam :: Vec4"), Some("Float4"));
+        assert_eq!(guess_uniform_type("glam :: IVec3"), Some("Int3"));
+        assert_eq!(guess_uniform_type("[f32 ; 16]"), Some("Mat4"));
+        assert_eq!(guess_uniform_type("[[f32 ; 4] ; 4]"), Some("Mat4"));
+        assert_eq!(guess_uniform_type("glam :: Mat4"), Some("Mat4"));
+        // arrays of uniforms keep the element type
+        assert_eq!(guess_uniform_type("[[f32 ; 4] ; 8]"), Some("Float4"));
+        assert_eq!(guess_uniform_type("[glam :: Mat4 ; 2]"), Some("Mat4"));
+        assert_eq!(guess_uniform_type("[f32 ; 5]"), Some("Float1"));
+        assert_eq!(guess_uniform_type("u8"), None);
+        assert_eq!(guess_uniform_type("[u8 ; 4]"), None);
+    }
+
+    #[test]
+    fn vertex_formats() {
+        assert_eq!(guess_vertex_format("f32"), Some("Float1"));
+        assert_eq!(guess_vertex_format("[f32 ; 2]"), Some("Float2"));
+        assert_eq!(guess_vertex_format("glam :: Vec3"), Some("Float3"));
+        assert_eq!(guess_vertex_format("[u8 ; 4]"), Some("Byte4"));
+        assert_eq!(guess_vertex_format("[i8 ; 3]"), Some("SByte3"));
+        assert_eq!(guess_vertex_format("u16"), Some("Short1"));
+        assert_eq!(guess_vertex_format("[i16 ; 2]"), Some("SShort2"));
+        assert_eq!(guess_vertex_format("[u32 ; 4]"), Some("Int4"));
+        assert_eq!(guess_vertex_format("[[f32 ; 4] ; 4]"), Some("Mat4"));
+        assert_eq!(guess_vertex_format("glam :: Mat4"), Some("Mat4"));
+        // no arrays of attributes
+        assert_eq!(guess_vertex_format("[f32 ; 5]"), None);
+        assert_eq!(guess_vertex_format("[[f32 ; 2] ; 2]"), None);
+        assert_eq!(guess_vertex_format("i32"), None);
+    }
+}
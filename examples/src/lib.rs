@@ -198,10 +198,11 @@ mod shader {
 
     pub fn meta() -> ShaderMeta {
         ShaderMeta {
-            images: vec!["tex".to_string()],
+            images: vec![ImageDesc::new("tex", ImageType::Texture2D)],
             uniforms: UniformBlockLayout {
                 uniforms: vec![UniformDesc::new("offset", UniformType::Float2)],
             },
+            uniform_blocks: vec![],
         }
     }
 
@@ -24,6 +24,20 @@ var high_dpi = false;
 // if false, requestAnimationFrame will be called at the end of each frame
 var blocking_event_loop = false;
 
+// extensions have to be acquired again after webglcontextrestored
+var acquire_webgl_extensions = function () { };
+
+function acquireDrawBuffersIndexed(ctx) {
+    // WebGL 2 only extension
+    var ext = ctx.getExtension('OES_draw_buffers_indexed');
+    if (ext) {
+        ctx['enablei'] = function (target, index) { ext['enableiOES'](target, index); };
+        ctx['disablei'] = function (target, index) { ext['disableiOES'](target, index); };
+        ctx['blendFuncSeparatei'] = function (buf, srcRGB, dstRGB, srcAlpha, dstAlpha) { ext['blendFuncSeparateiOES'](buf, srcRGB, dstRGB, srcAlpha, dstAlpha); };
+        ctx['blendEquationSeparatei'] = function (buf, modeRGB, modeAlpha) { ext['blendEquationSeparateiOES'](buf, modeRGB, modeAlpha); };
+    }
+}
+
 function init_webgl(version) {
     if (version == 1) {
         gl = canvas.getContext("webgl");
@@ -71,24 +85,34 @@ function init_webgl(version) {
             }
         }
 
-        try {
-            gl.getExtension("EXT_shader_texture_lod");
-            gl.getExtension("OES_standard_derivatives");
-        } catch (e) {
-            console.warn(e);
-        }
+        acquire_webgl_extensions = function () {
+            try {
+                gl.getExtension("EXT_shader_texture_lod");
+                gl.getExtension("OES_standard_derivatives");
+                gl.getExtension("EXT_blend_minmax");
+            } catch (e) {
+                console.warn(e);
+            }
 
-        acquireVertexArrayObjectExtension(gl);
-        acquireInstancedArraysExtension(gl);
-        acquireDisjointTimerQueryExtension(gl);
-        acquireDrawBuffers(gl);
+            acquireVertexArrayObjectExtension(gl);
+            acquireInstancedArraysExtension(gl);
+            acquireDisjointTimerQueryExtension(gl);
+            acquireDrawBuffers(gl);
 
-        // https://developer.mozilla.org/en-US/docs/Web/API/WEBGL_depth_texture
-        if (gl.getExtension('WEBGL_depth_texture') == null) {
-            alert("Cant initialize WEBGL_depth_texture extension");
-        }
+            // https://developer.mozilla.org/en-US/docs/Web/API/WEBGL_depth_texture
+            if (gl.getExtension('WEBGL_depth_texture') == null) {
+                alert("Cant initialize WEBGL_depth_texture extension");
+            }
+        };
+        acquire_webgl_extensions();
     } else {
         gl = canvas.getContext("webgl2");
+        if (gl != null) {
+            acquire_webgl_extensions = function () {
+                acquireDrawBuffersIndexed(gl);
+            };
+            acquire_webgl_extensions();
+        }
     }
     if (gl === null) {
         alert("Unable to initialize WebGL. Your browser or machine may not support it.");
@@ -692,7 +716,12 @@ var importObject = {
                 pixels ? getArray(pixels, Uint8Array, texture_size(format, width, height)) : null);
         },
         glReadPixels: function (x, y, width, height, format, type, pixels) {
-            var pixelData = getArray(pixels, Uint8Array, texture_size(format, width, height));
+            var pixelData;
+            if (type == 0x140B) { // GL_HALF_FLOAT, webgl wants an Uint16Array here
+                pixelData = getArray(pixels, Uint16Array, width * height * 4);
+            } else {
+                pixelData = getArray(pixels, Uint8Array, texture_size(format, width, height));
+            }
             gl.readPixels(x, y, width, height, format, type, pixelData);
         },
         glTexParameteri: function (target, pname, param) {
@@ -824,6 +853,18 @@ var importObject = {
         glBindVertexArray: function (vao) {
             gl.bindVertexArray(GL.vaos[vao]);
         },
+        glDeleteVertexArrays: function (n, arrays) {
+            for (var i = 0; i < n; i++) {
+                var id = getArray(arrays + i * 4, Uint32Array, 1)[0];
+                var vao = GL.vaos[id];
+
+                if (!vao) continue;
+
+                gl.deleteVertexArray(vao);
+                vao.name = 0;
+                GL.vaos[id] = null;
+            }
+        },
         glBindFramebuffer: function (target, framebuffer) {
             GL.validateGLObjectID(GL.framebuffers, framebuffer, 'glBindFramebuffer', 'framebuffer');
 
@@ -847,6 +888,37 @@ var importObject = {
         glBufferSubData: function (target, offset, size, data) {
             gl.bufferSubData(target, offset, data ? getArray(data, Uint8Array, size) : size);
         },
+        glGetBufferSubData: function (target, offset, size, data) {
+            gl.getBufferSubData(target, offset, getArray(data, Uint8Array, size));
+        },
+        glBindBufferBase: function (target, index, buffer) {
+            GL.validateGLObjectID(GL.buffers, buffer, 'glBindBufferBase', 'buffer');
+            gl.bindBufferBase(target, index, GL.buffers[buffer]);
+        },
+        glTransformFeedbackVaryings: function (program, count, varyings, bufferMode) {
+            GL.validateGLObjectID(GL.programs, program, 'glTransformFeedbackVaryings', 'program');
+            var names = [];
+            for (var i = 0; i < count; ++i) {
+                names.push(UTF8ToString(getArray(varyings + i * 4, Uint32Array, 1)[0]));
+            }
+            gl.transformFeedbackVaryings(GL.programs[program], names, bufferMode);
+        },
+        glBeginTransformFeedback: function (primitiveMode) {
+            gl.beginTransformFeedback(primitiveMode);
+        },
+        glEndTransformFeedback: function () {
+            gl.endTransformFeedback();
+        },
+        glBindTransformFeedback: function (target, id) {
+            // only the default transform feedback object is ever bound
+            assert(id == 0, "glBindTransformFeedback: non-default objects are unimplemented");
+            gl.bindTransformFeedback(target, null);
+        },
+        glBindSampler: function (unit, sampler) {
+            // only ever used to unbind, miniquad has no sampler objects
+            assert(sampler == 0, "glBindSampler: sampler objects are unimplemented");
+            gl.bindSampler(unit, null);
+        },
         glEnable: function (cap) {
             gl.enable(cap);
         },
@@ -859,6 +931,9 @@ var importObject = {
         glDepthFunc: function (func) {
             gl.depthFunc(func);
         },
+        glDepthMask: function (flag) {
+            gl.depthMask(flag);
+        },
         glBlendFuncSeparate: function (sfactorRGB, dfactorRGB, sfactorAlpha, dfactorAlpha) {
             gl.blendFuncSeparate(sfactorRGB, dfactorRGB, sfactorAlpha, dfactorAlpha);
         },
@@ -938,6 +1013,9 @@ var importObject = {
         glStencilFuncSeparate: function (face, func, ref_, mask) {
             gl.stencilFuncSeparate(face, func, ref_, mask);
         },
+        glStencilMask: function (mask) {
+            gl.stencilMask(mask);
+        },
         glStencilMaskSeparate: function (face, mask) {
             gl.stencilMaskSeparate(face, mask);
         },
@@ -1002,7 +1080,10 @@ var importObject = {
         glGetString: function (id) {
             // getParameter returns "any": it could be GLenum, String or whatever,
             // depending on the id.
-            var parameter = gl.getParameter(id).toString();
+            // WebGL has no GL_EXTENSIONS parameter, listed the GLES2 way instead
+            var parameter = id == 0x1F03 /* GL_EXTENSIONS */
+                ? gl.getSupportedExtensions().join(' ')
+                : gl.getParameter(id).toString();
             var len = parameter.length + 1;
             var msg = wasm_exports.allocate_vec_u8(len);
             var array = new Uint8Array(wasm_memory.buffer, msg, len);
@@ -1170,6 +1251,30 @@ var importObject = {
                                dstX0, dstY0, dstX1, dstY1,
                                mask, filter);
         },
+        glBlendColor: function (r, g, b, a) {
+            gl.blendColor(r, g, b, a);
+        },
+        glEnablei: function (target, index) {
+            gl.enablei(target, index);
+        },
+        glDisablei: function (target, index) {
+            gl.disablei(target, index);
+        },
+        glBlendFuncSeparatei: function (buf, srcRGB, dstRGB, srcAlpha, dstAlpha) {
+            gl.blendFuncSeparatei(buf, srcRGB, dstRGB, srcAlpha, dstAlpha);
+        },
+        glBlendEquationSeparatei: function (buf, modeRGB, modeAlpha) {
+            gl.blendEquationSeparatei(buf, modeRGB, modeAlpha);
+        },
+        glLineWidth: function (width) {
+            gl.lineWidth(width);
+        },
+        glPolygonMode: function (face, mode) {
+            // not available in webgl, never called with Features::polygon_mode false
+        },
+        glDrawElementsInstancedBaseInstance: function (mode, count, type, indices, primcount, baseinstance) {
+            // not available in webgl, never called with Features::base_instance false
+        },
 
         setup_canvas_size: function (high_dpi) {
             window.high_dpi = high_dpi;
@@ -1377,6 +1482,15 @@ var importObject = {
             window.addEventListener("focus", checkFocus);
             window.addEventListener("blur", checkFocus);
 
+            canvas.addEventListener("webglcontextlost", function (event) {
+                // without preventDefault the context will never be restored
+                event.preventDefault();
+            }, false);
+            canvas.addEventListener("webglcontextrestored", function () {
+                acquire_webgl_extensions();
+                wasm_exports.context_restored();
+            }, false);
+
             window.blocking_event_loop = blocking;
             window.requestAnimationFrame(animation);
         },
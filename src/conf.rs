@@ -41,6 +41,8 @@
 //! [`dpi_scale`]: super::window::dpi_scale
 //! [`screen_size`]: super::window::screen_size
 
+use crate::event::{KeyCode, KeyMods};
+
 /// Specifies how to load an OpenGL context on X11 in Linux.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub enum LinuxX11Gl {
@@ -117,6 +119,44 @@ pub enum WaylandDecorations {
     ServerOnly,
 }
 
+/// Which OpenGL API and profile to request at context creation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GlProfile {
+    /// OpenGL ES on EGL and OHOS, desktop OpenGL on GLX. This is the default choice.
+    #[default]
+    PlatformDefault,
+    /// OpenGL ES. On GLX requires `GLX_EXT_create_context_es2_profile`.
+    Es,
+    /// Desktop OpenGL core profile, 3.2 unless `GlConfig::version` says otherwise.
+    /// Not available on Android and OHOS.
+    Core,
+    /// Desktop OpenGL compatibility profile. Not available on Android and OHOS.
+    Compatibility,
+}
+
+/// OpenGL context creation options, used by EGL (Linux, Android, OHOS) and GLX.
+///
+/// `debug`, `robustness` and `no_error` are dropped if the driver refuses them,
+/// what the context actually got is reported in `ContextInfo::gl_context`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct GlConfig {
+    /// Requested `(major, minor)` version. `None` keeps the defaults,
+    /// GLES 2.0 on EGL and GL 2.1 on GLX.
+    pub version: Option<(u8, u8)>,
+    pub profile: GlProfile,
+    /// Request a debug context.
+    pub debug: bool,
+    /// Request robust buffer access, with a context loss on GPU resets.
+    pub robustness: bool,
+    /// Request a `KHR_no_error` context, where GL errors are undefined behavior.
+    /// Ignored together with `debug` or `robustness`.
+    pub no_error: bool,
+    /// Depth bits of the default framebuffer. `None` keeps the defaults, 16 on EGL and 24 on GLX.
+    pub depth_bits: Option<u8>,
+    /// Stencil bits of the default framebuffer. `None` keeps the defaults, 0 on EGL and 8 on GLX.
+    pub stencil_bits: Option<u8>,
+}
+
 /// Platform-specific settings.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Platform {
@@ -173,6 +213,31 @@ pub struct Platform {
     /// Whether to automatically setup the panic hook for Android.
     /// Set this to false if your app does its own panic_hook setup to avoid conflicts.
     pub android_panic_hook: bool,
+
+    /// If `true`, `GlContext` keeps CPU-side copies of shader sources, texture and
+    /// buffer data and recreates all the resources, under the same handles, after
+    /// the GL context was lost and restored.
+    /// Only useful on Android, OHOS and WebGL, where the context may be lost.
+    ///
+    /// Content rendered into render target textures is not preserved.
+    /// See also [`EventHandler::context_restored_event`].
+    ///
+    /// [`EventHandler::context_restored_event`]: crate::EventHandler::context_restored_event
+    pub context_loss_recovery: bool,
+
+    /// OpenGL version, profile, flags and default framebuffer depth/stencil.
+    /// Ignored on the Web, Windows and Apple platforms.
+    pub gl_config: GlConfig,
+
+    /// Show the debug overlay from the start: FPS, frame time graph, draw calls and
+    /// GPU memory, drawn on top of the default framebuffer in `commit_frame`.
+    /// See also `window::set_debug_overlay`.
+    pub debug_overlay: bool,
+
+    /// Key combination showing and hiding the debug overlay, for example
+    /// `Some((KeyCode::F3, KeyMods::default()))`.
+    /// The key events of the combination are not passed to the `EventHandler`.
+    pub debug_overlay_toggle: Option<(KeyCode, KeyMods)>,
 }
 
 impl Default for Platform {
@@ -188,6 +253,10 @@ impl Default for Platform {
             wayland_decorations: WaylandDecorations::default(),
             linux_wm_class: "miniquad-application",
             android_panic_hook: true,
+            context_loss_recovery: false,
+            gl_config: GlConfig::default(),
+            debug_overlay: false,
+            debug_overlay_toggle: None,
         }
     }
 }
@@ -218,6 +287,12 @@ pub struct Conf {
     /// Defaults to `1`.
     pub sample_count: i32,
 
+    /// Request a default framebuffer that converts the linear shader output to sRGB.
+    /// Supported with EGL (`EGL_KHR_gl_colorspace`), GLX (`GLX_ARB_framebuffer_sRGB`)
+    /// and Metal, check `ContextInfo::srgb_framebuffer` for the outcome.
+    /// Defaults to `false`.
+    pub srgb_framebuffer: bool,
+
     /// If `true`, the user can resize the window.
     pub window_resizable: bool,
 
@@ -273,6 +348,7 @@ impl Default for Conf {
             high_dpi: false,
             fullscreen: false,
             sample_count: 1,
+            srgb_framebuffer: false,
             window_resizable: true,
             icon: Some(Icon::miniquad_logo()),
             platform: Default::default(),
@@ -290,6 +366,7 @@ impl Default for Conf {
             high_dpi: true,
             fullscreen: true, //
             sample_count: 1,
+            srgb_framebuffer: false,
             window_resizable: false, //
             icon: Some(Icon::miniquad_logo()),
             platform: Default::default(),
@@ -144,7 +144,7 @@ pub enum KeyCode {
     Unknown = 0x01ff,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
 pub struct KeyMods {
     pub shift: bool,
     pub ctrl: bool,
@@ -231,4 +231,14 @@ pub trait EventHandler {
     /// `ctx.dropped_file_path()`, and for wasm targets the file bytes
     /// can be requested with `ctx.dropped_file_bytes()`.
     fn files_dropped_event(&mut self) {}
+
+    /// Rendering context was lost and a new one was created.
+    /// Right now is only implemented on Android, OHOS and wasm.
+    ///
+    /// With `conf::Platform::context_loss_recovery` all the resources created by
+    /// `GlContext` are restored automatically, on the next `begin_pass`. Shaders
+    /// failing to compile again are logged, and pipelines using them draw nothing.
+    /// Without it all the GPU resource handles are invalid and should be recreated
+    /// with a new rendering backend.
+    fn context_restored_event(&mut self) {}
 }
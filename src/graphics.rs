@@ -6,18 +6,26 @@ use std::{error::Error, fmt::Display};
 
 //pub use texture::{FilterMode, TextureAccess, TextureFormat, TextureParams, TextureWrap};
 
+pub mod batch;
+mod commands;
 mod gl;
+pub(crate) mod overlay;
+pub mod resources;
 
+pub use commands::CommandBuffer;
 pub use gl::raw_gl;
 
 #[cfg(target_vendor = "apple")]
 mod metal;
 
-pub use gl::GlContext;
+pub use gl::{GlContext, LoadId, Loaded, Loader};
 
 #[cfg(target_vendor = "apple")]
 pub use metal::MetalContext;
 
+#[cfg(feature = "derive")]
+pub use miniquad_derive::{Uniforms, Vertex};
+
 #[derive(Clone, Copy, Debug)]
 pub enum UniformType {
     /// One 32-bit wide float (equivalent to `f32`)
@@ -42,7 +50,7 @@ pub enum UniformType {
 
 impl UniformType {
     /// Byte size for a given UniformType
-    pub fn size(&self) -> usize {
+    pub const fn size(&self) -> usize {
         match self {
             UniformType::Float1 => 4,
             UniformType::Float2 => 8,
@@ -64,11 +72,41 @@ pub struct UniformDesc {
     pub array_count: usize,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct UniformBlockLayout {
     pub uniforms: Vec<UniformDesc>,
 }
 
+/// A `#[repr(C)]` struct given to `apply_uniforms`, its fields tightly packed
+/// in the order of `uniform_descs`.
+///
+/// With the "derive" feature, `#[derive(Uniforms)]` implements it and checks
+/// the struct layout at compile time.
+///
+/// ```ignore
+/// #[derive(Uniforms)]
+/// #[repr(C)]
+/// struct Globals {
+///     mvp: glam::Mat4,
+///     #[uniform(name = "lightColors")]
+///     light_colors: [[f32; 4]; 8],
+/// }
+///
+/// let meta = ShaderMeta {
+///     uniforms: Globals::uniform_layout(),
+///     ..Default::default()
+/// };
+/// ```
+pub trait Uniforms {
+    fn uniform_descs() -> Vec<UniformDesc>;
+
+    fn uniform_layout() -> UniformBlockLayout {
+        UniformBlockLayout {
+            uniforms: Self::uniform_descs(),
+        }
+    }
+}
+
 impl UniformDesc {
     pub fn new(name: &str, uniform_type: UniformType) -> UniformDesc {
         UniformDesc {
@@ -86,12 +124,107 @@ impl UniformDesc {
     }
 }
 
-#[derive(Clone)]
+/// Uniforms applied independently from the rest with `apply_uniform_block`,
+/// like per-frame, per-material or per-object data.
+///
+/// On GL uniform names are shared by all the blocks of a program.
+/// On metal `ShaderMeta::uniform_blocks[i]` is bound at `[[buffer(UNIFORM_BLOCKS_BUFFER_INDEX + i)]]`,
+/// `ShaderMeta::uniforms` at `[[buffer(0)]]`.
+#[derive(Debug, Clone)]
+pub struct UniformBlockDesc {
+    /// The only stage using the block, `None` if both stages do.
+    pub stage: Option<ShaderType>,
+    pub layout: UniformBlockLayout,
+}
+
+impl UniformBlockDesc {
+    pub fn new(stage: Option<ShaderType>, uniforms: Vec<UniformDesc>) -> UniformBlockDesc {
+        UniformBlockDesc {
+            stage,
+            layout: UniformBlockLayout { uniforms },
+        }
+    }
+}
+
+/// Sampler type of a shader image, the kind of texture it can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageType {
+    /// `sampler2D`, `texture2d<float>` on metal.
+    Texture2D,
+    /// `samplerCube`, `texturecube<float>` on metal.
+    CubeMap,
+    /// `sampler2DShadow`, `depth2d<float>` on metal. Takes a 2D depth texture,
+    /// sampled with a `LessOrEqual` comparison against the reference value.
+    /// Requires `features.shadow_samplers`.
+    Shadow,
+}
+
+impl ImageType {
+    /// Can a texture created with `params` be bound to an image of this type.
+    pub fn accepts(&self, params: &TextureParams) -> bool {
+        let depth = matches!(params.format, TextureFormat::Depth | TextureFormat::Depth32);
+        match self {
+            ImageType::Texture2D => params.kind == TextureKind::Texture2D,
+            ImageType::CubeMap => params.kind == TextureKind::CubeMap,
+            ImageType::Shadow => params.kind == TextureKind::Texture2D && depth,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageDesc {
+    pub name: String,
+    pub image_type: ImageType,
+}
+
+impl ImageDesc {
+    pub fn new(name: &str, image_type: ImageType) -> ImageDesc {
+        ImageDesc {
+            name: name.to_string(),
+            image_type,
+        }
+    }
+}
+
+/// `ImageType::Texture2D` image, what every image was before `ImageType`:
+/// `images: vec!["tex".into()]`.
+impl From<&str> for ImageDesc {
+    fn from(name: &str) -> ImageDesc {
+        ImageDesc::new(name, ImageType::Texture2D)
+    }
+}
+
+impl From<String> for ImageDesc {
+    fn from(name: String) -> ImageDesc {
+        ImageDesc {
+            name,
+            image_type: ImageType::Texture2D,
+        }
+    }
+}
+
+/// Literals written against 0.4.8 no longer compile: `uniform_blocks` is new and
+/// `images` holds `ImageDesc`s. Add `..Default::default()`, or `uniform_blocks: vec![]`,
+/// and convert image names with `.into()`, see CHANGELOG.md.
+#[derive(Clone, Default)]
 pub struct ShaderMeta {
+    /// Uniforms of both stages, applied with `apply_uniforms`.
     pub uniforms: UniformBlockLayout,
-    pub images: Vec<String>,
+    /// Additional blocks, applied with `apply_uniform_block`.
+    pub uniform_blocks: Vec<UniformBlockDesc>,
+    /// `Bindings::images` are matched with these by position,
+    /// `Bindings::named_images` orders them by name.
+    pub images: Vec<ImageDesc>,
 }
 
+impl ShaderMeta {
+    /// Position of the `name` image, its index in `Bindings::images`.
+    pub fn image_index(&self, name: &str) -> Option<usize> {
+        self.images.iter().position(|image| image.name == name)
+    }
+}
+
+#[allow(non_camel_case_types)]
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum VertexFormat {
     /// One 32-bit wide float (equivalent to `f32`)
@@ -126,6 +259,68 @@ pub enum VertexFormat {
     Int3,
     /// Four unsigned 32-bit integers (equivalent to `[u32; 4]`)
     Int4,
+    /// One unsigned 8-bit integer (equivalent to `u8`), normalized to `[0, 1]`
+    Byte1Norm,
+    /// Two unsigned 8-bit integers (equivalent to `[u8; 2]`), normalized to `[0, 1]`
+    Byte2Norm,
+    /// Three unsigned 8-bit integers (equivalent to `[u8; 3]`), normalized to `[0, 1]`
+    Byte3Norm,
+    /// Four unsigned 8-bit integers (equivalent to `[u8; 4]`), normalized to `[0, 1]`
+    Byte4Norm,
+    /// One signed 8-bit integer (equivalent to `i8`)
+    SByte1,
+    /// Two signed 8-bit integers (equivalent to `[i8; 2]`)
+    SByte2,
+    /// Three signed 8-bit integers (equivalent to `[i8; 3]`)
+    SByte3,
+    /// Four signed 8-bit integers (equivalent to `[i8; 4]`)
+    SByte4,
+    /// One signed 8-bit integer (equivalent to `i8`), normalized to `[-1, 1]`
+    SByte1Norm,
+    /// Two signed 8-bit integers (equivalent to `[i8; 2]`), normalized to `[-1, 1]`
+    SByte2Norm,
+    /// Three signed 8-bit integers (equivalent to `[i8; 3]`), normalized to `[-1, 1]`
+    SByte3Norm,
+    /// Four signed 8-bit integers (equivalent to `[i8; 4]`), normalized to `[-1, 1]`
+    SByte4Norm,
+    /// One unsigned 16-bit integer (equivalent to `u16`), normalized to `[0, 1]`
+    Short1Norm,
+    /// Two unsigned 16-bit integers (equivalent to `[u16; 2]`), normalized to `[0, 1]`
+    Short2Norm,
+    /// Three unsigned 16-bit integers (equivalent to `[u16; 3]`), normalized to `[0, 1]`
+    Short3Norm,
+    /// Four unsigned 16-bit integers (equivalent to `[u16; 4]`), normalized to `[0, 1]`
+    Short4Norm,
+    /// One signed 16-bit integer (equivalent to `i16`)
+    SShort1,
+    /// Two signed 16-bit integers (equivalent to `[i16; 2]`)
+    SShort2,
+    /// Three signed 16-bit integers (equivalent to `[i16; 3]`)
+    SShort3,
+    /// Four signed 16-bit integers (equivalent to `[i16; 4]`)
+    SShort4,
+    /// One signed 16-bit integer (equivalent to `i16`), normalized to `[-1, 1]`
+    SShort1Norm,
+    /// Two signed 16-bit integers (equivalent to `[i16; 2]`), normalized to `[-1, 1]`
+    SShort2Norm,
+    /// Three signed 16-bit integers (equivalent to `[i16; 3]`), normalized to `[-1, 1]`
+    SShort3Norm,
+    /// Four signed 16-bit integers (equivalent to `[i16; 4]`), normalized to `[-1, 1]`
+    SShort4Norm,
+    /// One 16-bit wide float, received as `float` in the shader, not available on GLES2/WebGL1
+    Half1,
+    /// Two 16-bit wide floats, received as `vec2` in the shader, not available on GLES2/WebGL1
+    Half2,
+    /// Three 16-bit wide floats, received as `vec3` in the shader, not available on GLES2/WebGL1
+    Half3,
+    /// Four 16-bit wide floats, received as `vec4` in the shader, not available on GLES2/WebGL1
+    Half4,
+    /// Four components packed into a `u32`: 10 bits for x, y and z and 2 bits for w,
+    /// with x in the least significant bits. Normalized to `[0, 1]`
+    UInt2_10_10_10_Rev,
+    /// Four components packed into a `u32`: signed 10 bits for x, y and z and 2 bits for w,
+    /// with x in the least significant bits. Normalized to `[-1, 1]`
+    Int2_10_10_10_Rev,
     /// Four by four matrix of 32-bit floats
     Mat4,
 }
@@ -152,12 +347,42 @@ impl VertexFormat {
             VertexFormat::Int2 => 2,
             VertexFormat::Int3 => 3,
             VertexFormat::Int4 => 4,
+            VertexFormat::Byte1Norm => 1,
+            VertexFormat::Byte2Norm => 2,
+            VertexFormat::Byte3Norm => 3,
+            VertexFormat::Byte4Norm => 4,
+            VertexFormat::SByte1 => 1,
+            VertexFormat::SByte2 => 2,
+            VertexFormat::SByte3 => 3,
+            VertexFormat::SByte4 => 4,
+            VertexFormat::SByte1Norm => 1,
+            VertexFormat::SByte2Norm => 2,
+            VertexFormat::SByte3Norm => 3,
+            VertexFormat::SByte4Norm => 4,
+            VertexFormat::Short1Norm => 1,
+            VertexFormat::Short2Norm => 2,
+            VertexFormat::Short3Norm => 3,
+            VertexFormat::Short4Norm => 4,
+            VertexFormat::SShort1 => 1,
+            VertexFormat::SShort2 => 2,
+            VertexFormat::SShort3 => 3,
+            VertexFormat::SShort4 => 4,
+            VertexFormat::SShort1Norm => 1,
+            VertexFormat::SShort2Norm => 2,
+            VertexFormat::SShort3Norm => 3,
+            VertexFormat::SShort4Norm => 4,
+            VertexFormat::Half1 => 1,
+            VertexFormat::Half2 => 2,
+            VertexFormat::Half3 => 3,
+            VertexFormat::Half4 => 4,
+            VertexFormat::UInt2_10_10_10_Rev => 4,
+            VertexFormat::Int2_10_10_10_Rev => 4,
             VertexFormat::Mat4 => 16,
         }
     }
 
     /// Size in bytes
-    pub fn size_bytes(&self) -> i32 {
+    pub const fn size_bytes(&self) -> i32 {
         match self {
             VertexFormat::Float1 => 1 * 4,
             VertexFormat::Float2 => 2 * 4,
@@ -175,10 +400,65 @@ impl VertexFormat {
             VertexFormat::Int2 => 2 * 4,
             VertexFormat::Int3 => 3 * 4,
             VertexFormat::Int4 => 4 * 4,
+            VertexFormat::Byte1Norm => 1,
+            VertexFormat::Byte2Norm => 2,
+            VertexFormat::Byte3Norm => 3,
+            VertexFormat::Byte4Norm => 4,
+            VertexFormat::SByte1 => 1,
+            VertexFormat::SByte2 => 2,
+            VertexFormat::SByte3 => 3,
+            VertexFormat::SByte4 => 4,
+            VertexFormat::SByte1Norm => 1,
+            VertexFormat::SByte2Norm => 2,
+            VertexFormat::SByte3Norm => 3,
+            VertexFormat::SByte4Norm => 4,
+            VertexFormat::Short1Norm => 1 * 2,
+            VertexFormat::Short2Norm => 2 * 2,
+            VertexFormat::Short3Norm => 3 * 2,
+            VertexFormat::Short4Norm => 4 * 2,
+            VertexFormat::SShort1 => 1 * 2,
+            VertexFormat::SShort2 => 2 * 2,
+            VertexFormat::SShort3 => 3 * 2,
+            VertexFormat::SShort4 => 4 * 2,
+            VertexFormat::SShort1Norm => 1 * 2,
+            VertexFormat::SShort2Norm => 2 * 2,
+            VertexFormat::SShort3Norm => 3 * 2,
+            VertexFormat::SShort4Norm => 4 * 2,
+            VertexFormat::Half1 => 1 * 2,
+            VertexFormat::Half2 => 2 * 2,
+            VertexFormat::Half3 => 3 * 2,
+            VertexFormat::Half4 => 4 * 2,
+            VertexFormat::UInt2_10_10_10_Rev => 4,
+            VertexFormat::Int2_10_10_10_Rev => 4,
             VertexFormat::Mat4 => 16 * 4,
         }
     }
 
+    /// Integer formats converted to floats in `[0, 1]` or `[-1, 1]` before reaching the shader.
+    pub fn normalized(&self) -> bool {
+        matches!(
+            self,
+            VertexFormat::Byte1Norm
+                | VertexFormat::Byte2Norm
+                | VertexFormat::Byte3Norm
+                | VertexFormat::Byte4Norm
+                | VertexFormat::SByte1Norm
+                | VertexFormat::SByte2Norm
+                | VertexFormat::SByte3Norm
+                | VertexFormat::SByte4Norm
+                | VertexFormat::Short1Norm
+                | VertexFormat::Short2Norm
+                | VertexFormat::Short3Norm
+                | VertexFormat::Short4Norm
+                | VertexFormat::SShort1Norm
+                | VertexFormat::SShort2Norm
+                | VertexFormat::SShort3Norm
+                | VertexFormat::SShort4Norm
+                | VertexFormat::UInt2_10_10_10_Rev
+                | VertexFormat::Int2_10_10_10_Rev
+        )
+    }
+
     fn type_(&self) -> GLuint {
         match self {
             VertexFormat::Float1 => GL_FLOAT,
@@ -197,6 +477,36 @@ impl VertexFormat {
             VertexFormat::Int2 => GL_UNSIGNED_INT,
             VertexFormat::Int3 => GL_UNSIGNED_INT,
             VertexFormat::Int4 => GL_UNSIGNED_INT,
+            VertexFormat::Byte1Norm => GL_UNSIGNED_BYTE,
+            VertexFormat::Byte2Norm => GL_UNSIGNED_BYTE,
+            VertexFormat::Byte3Norm => GL_UNSIGNED_BYTE,
+            VertexFormat::Byte4Norm => GL_UNSIGNED_BYTE,
+            VertexFormat::SByte1 => GL_BYTE,
+            VertexFormat::SByte2 => GL_BYTE,
+            VertexFormat::SByte3 => GL_BYTE,
+            VertexFormat::SByte4 => GL_BYTE,
+            VertexFormat::SByte1Norm => GL_BYTE,
+            VertexFormat::SByte2Norm => GL_BYTE,
+            VertexFormat::SByte3Norm => GL_BYTE,
+            VertexFormat::SByte4Norm => GL_BYTE,
+            VertexFormat::Short1Norm => GL_UNSIGNED_SHORT,
+            VertexFormat::Short2Norm => GL_UNSIGNED_SHORT,
+            VertexFormat::Short3Norm => GL_UNSIGNED_SHORT,
+            VertexFormat::Short4Norm => GL_UNSIGNED_SHORT,
+            VertexFormat::SShort1 => GL_SHORT,
+            VertexFormat::SShort2 => GL_SHORT,
+            VertexFormat::SShort3 => GL_SHORT,
+            VertexFormat::SShort4 => GL_SHORT,
+            VertexFormat::SShort1Norm => GL_SHORT,
+            VertexFormat::SShort2Norm => GL_SHORT,
+            VertexFormat::SShort3Norm => GL_SHORT,
+            VertexFormat::SShort4Norm => GL_SHORT,
+            VertexFormat::Half1 => GL_HALF_FLOAT,
+            VertexFormat::Half2 => GL_HALF_FLOAT,
+            VertexFormat::Half3 => GL_HALF_FLOAT,
+            VertexFormat::Half4 => GL_HALF_FLOAT,
+            VertexFormat::UInt2_10_10_10_Rev => GL_UNSIGNED_INT_2_10_10_10_REV,
+            VertexFormat::Int2_10_10_10_Rev => GL_INT_2_10_10_10_REV,
             VertexFormat::Mat4 => GL_FLOAT,
         }
     }
@@ -213,6 +523,9 @@ pub enum VertexStep {
 pub struct BufferLayout {
     pub stride: i32,
     pub step_func: VertexStep,
+    /// With `VertexStep::PerInstance`, how many instances are drawn with the same
+    /// element before advancing to the next one. Must be at least 1.
+    /// Ignored with `VertexStep::PerVertex`.
     pub step_rate: i32,
 }
 
@@ -231,7 +544,8 @@ pub struct VertexAttribute {
     pub name: &'static str,
     pub format: VertexFormat,
     pub buffer_index: usize,
-    /// This flag affects integer VertexFormats, Byte*, Short*, Int*
+    /// This flag affects integer VertexFormats, Byte*, Short*, Int*, SByte*, SShort*
+    /// Normalized formats, *Norm and the packed ones, are always received as floats
     /// Taking Byte4 as an example:
     /// On Metal, it might be received as either `float4` or `uint4`
     /// On OpenGl and `gl_pass_as_float = true` shaders should receive it as `vec4`
@@ -261,13 +575,48 @@ impl VertexAttribute {
     }
 }
 
+/// A `#[repr(C)]` vertex, its fields tightly packed in the order of `attributes`.
+///
+/// With the "derive" feature, `#[derive(Vertex)]` implements it and checks
+/// the struct layout at compile time.
+///
+/// ```ignore
+/// #[derive(Vertex)]
+/// #[repr(C)]
+/// struct QuadVertex {
+///     #[vertex(name = "in_pos")]
+///     pos: [f32; 2],
+///     #[vertex(Byte4Norm, name = "in_color")]
+///     color: [u8; 4],
+/// }
+///
+/// let pipeline = ctx.new_pipeline(
+///     &[QuadVertex::buffer_layout()],
+///     &QuadVertex::attributes(0),
+///     shader,
+///     PipelineParams::default(),
+/// );
+/// ```
+pub trait Vertex: Sized {
+    /// Attributes of the fields, read from the `buffer_index` buffer of the pipeline.
+    fn attributes(buffer_index: usize) -> Vec<VertexAttribute>;
+
+    /// Per-vertex `BufferLayout` with the struct size as the stride.
+    fn buffer_layout() -> BufferLayout {
+        BufferLayout {
+            stride: std::mem::size_of::<Self>() as i32,
+            ..BufferLayout::default()
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct PipelineLayout {
     pub buffers: &'static [BufferLayout],
     pub attributes: &'static [VertexAttribute],
 }
 
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub enum ShaderType {
     Vertex,
     Fragment,
@@ -314,6 +663,86 @@ impl Display for ShaderError {
 
 impl Error for ShaderError {}
 
+#[derive(Clone, Debug)]
+pub enum BindingError {
+    /// No image with this name in `ShaderMeta::images`.
+    UnknownImage(String),
+    /// The shader image has no texture in the bindings.
+    MissingImage(String),
+    /// The texture bound to the image can't be sampled with its `ImageType`.
+    IncompatibleTexture {
+        image: String,
+        image_type: ImageType,
+        kind: TextureKind,
+        format: TextureFormat,
+    },
+    /// The image's `ImageType` needs a feature the context doesn't have,
+    /// like `ImageType::Shadow` without `features.shadow_samplers`.
+    UnsupportedImageType {
+        image: String,
+        image_type: ImageType,
+    },
+}
+
+impl Display for BindingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownImage(name) => write!(f, "No image named \"{name}\" in the shader"),
+            Self::MissingImage(name) => write!(f, "No texture bound to the \"{name}\" image"),
+            Self::IncompatibleTexture {
+                image,
+                image_type,
+                kind,
+                format,
+            } => write!(
+                f,
+                "{kind:?} {format:?} texture bound to the \"{image}\" image of type {image_type:?}"
+            ),
+            Self::UnsupportedImageType { image, image_type } => write!(
+                f,
+                "The \"{image}\" image is of type {image_type:?}, not supported by the GPU"
+            ),
+        }
+    }
+}
+
+impl Error for BindingError {}
+
+/// Check `textures`, in `Bindings::images` order, against the shader `images`.
+/// Raw textures are not checked, miniquad does not know their params.
+pub(crate) fn validate_images(
+    images: &[ImageDesc],
+    textures: &[TextureId],
+    features: &Features,
+    texture_params: impl Fn(TextureId) -> TextureParams,
+) -> Result<(), BindingError> {
+    for (n, image) in images.iter().enumerate() {
+        if image.image_type == ImageType::Shadow && !features.shadow_samplers {
+            return Err(BindingError::UnsupportedImageType {
+                image: image.name.clone(),
+                image_type: image.image_type,
+            });
+        }
+        let texture = match textures.get(n) {
+            Some(texture) => *texture,
+            None => return Err(BindingError::MissingImage(image.name.clone())),
+        };
+        if let TextureIdInner::Raw(_) = texture.0 {
+            continue;
+        }
+        let params = texture_params(texture);
+        if !image.image_type.accepts(&params) {
+            return Err(BindingError::IncompatibleTexture {
+                image: image.name.clone(),
+                image_type: image.image_type,
+                kind: params.kind,
+                format: params.format,
+            });
+        }
+    }
+    Ok(())
+}
+
 /// List of all the possible formats of input data when uploading to texture.
 /// The list is built by intersection of texture formats supported by 3.3 core profile and webgl1.
 #[repr(u8)]
@@ -325,6 +754,12 @@ pub enum TextureFormat {
     Depth,
     Depth32,
     Alpha,
+    /// sRGB encoded RGB8, decoded to linear when sampled.
+    /// Requires GL 2.1, GLES 3 or WebGL 2, not available on metal.
+    SRGB8,
+    /// sRGB encoded RGBA8 with linear alpha, decoded when sampled and encoded
+    /// when rendered to. Requires GL 2.1, GLES 3 or WebGL 2.
+    SRGBA8,
 }
 impl TextureFormat {
     /// Returns the size in bytes of texture with `dimensions`.
@@ -337,7 +772,87 @@ impl TextureFormat {
             TextureFormat::Depth => 2 * square,
             TextureFormat::Depth32 => 4 * square,
             TextureFormat::Alpha => 1 * square,
+            TextureFormat::SRGB8 => 3 * square,
+            TextureFormat::SRGBA8 => 4 * square,
+        }
+    }
+}
+
+/// Layout of the source data of `texture_update_region`.
+/// The default is the layout `texture_update_part` expects.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextureUpload {
+    /// Distance between the starts of two source rows in pixels, 0 for rows of exactly
+    /// `width` pixels. Allows uploading a part of a larger image without copying it first.
+    pub row_length: u32,
+    /// Source pixels are in BGRA order, like most video decoders output.
+    /// `TextureFormat::RGBA8` only.
+    pub bgra: bool,
+    /// Multiply the color channels by alpha while uploading.
+    /// `TextureFormat::RGBA8` only.
+    pub premultiply_alpha: bool,
+    /// Source rows go in the opposite direction, the first one lands at `y_offset + height - 1`.
+    pub flip_y: bool,
+}
+
+impl TextureUpload {
+    /// Number of source bytes a `width`x`height` upload reads.
+    pub fn source_size(&self, format: TextureFormat, width: u32, height: u32) -> usize {
+        if width == 0 || height == 0 {
+            return 0;
+        }
+        let pixel_size = format.size(1, 1) as usize;
+        let stride = self.row_length.max(width) as usize;
+        (stride * (height as usize - 1) + width as usize) * pixel_size
+    }
+
+    /// Panics if the options don't apply to `format`.
+    pub(crate) fn check_format(&self, format: TextureFormat) {
+        assert!(
+            format == TextureFormat::RGBA8 || !(self.bgra || self.premultiply_alpha),
+            "BGRA and premultiplied uploads are only supported for RGBA8 textures"
+        );
+    }
+
+    /// Copy a `width`x`height` part of `source` into tightly packed rows, in the texture's
+    /// channel order, the way `texture_update_part` takes it.
+    pub(crate) fn repack(
+        &self,
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+        source: &[u8],
+    ) -> Vec<u8> {
+        self.check_format(format);
+        assert!(source.len() >= self.source_size(format, width, height));
+
+        let pixel_size = format.size(1, 1) as usize;
+        let row_size = width as usize * pixel_size;
+        let stride = self.row_length.max(width) as usize * pixel_size;
+        let mut packed = Vec::with_capacity(row_size * height as usize);
+        for y in 0..height as usize {
+            let row = if self.flip_y {
+                height as usize - 1 - y
+            } else {
+                y
+            };
+            let start = packed.len();
+            packed.extend_from_slice(&source[row * stride..row * stride + row_size]);
+            if self.bgra || self.premultiply_alpha {
+                for pixel in packed[start..].chunks_exact_mut(4) {
+                    if self.bgra {
+                        pixel.swap(0, 2);
+                    }
+                    if self.premultiply_alpha {
+                        let alpha = pixel[3] as u16;
+                        for channel in &mut pixel[..3] {
+                            *channel = ((*channel as u16 * alpha + 127) / 255) as u8;
+                        }
+                    }
+                }
+            }
         }
+        packed
     }
 }
 
@@ -402,6 +917,35 @@ pub struct TextureParams {
     pub sample_count: i32,
 }
 
+impl TextureParams {
+    /// Estimated GPU memory of the texture with all the cubemap faces,
+    /// samples and mip levels.
+    pub(crate) fn memory_size(&self) -> usize {
+        let faces = match self.kind {
+            TextureKind::Texture2D => 1,
+            TextureKind::CubeMap => 6,
+        };
+        let size = self.format.size(self.width, self.height) as usize
+            * faces
+            * self.sample_count.max(1) as usize;
+        if self.allocate_mipmaps {
+            // full mip chain adds a third of the base level
+            size + size / 3
+        } else {
+            size
+        }
+    }
+
+    /// Number of mip levels, 1 without `allocate_mipmaps`.
+    pub(crate) fn mip_levels(&self) -> u32 {
+        if self.allocate_mipmaps {
+            32 - self.width.max(self.height).max(1).leading_zeros()
+        } else {
+            1
+        }
+    }
+}
+
 impl Default for TextureParams {
     fn default() -> Self {
         TextureParams {
@@ -569,11 +1113,64 @@ impl Default for PassAction {
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct RenderPass(usize);
 
+/// Render pass target: one mip level of a texture, or of a single cubemap face.
+///
+/// `TextureId` converts to an `Attachment` of level 0, the only kind of
+/// target `new_render_pass_mrt` can use.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attachment {
+    pub texture: TextureId,
+    /// Cubemap face, in +X, -X, +Y, -Y, +Z, -Z order. Always 0 for `TextureKind::Texture2D`.
+    pub layer: u32,
+    /// Mip level, anything but 0 requires `TextureParams::allocate_mipmaps`.
+    pub mip_level: u32,
+}
+
+impl Attachment {
+    pub fn new(texture: TextureId) -> Attachment {
+        Attachment {
+            texture,
+            layer: 0,
+            mip_level: 0,
+        }
+    }
+
+    pub fn mip_level(texture: TextureId, mip_level: u32) -> Attachment {
+        Attachment {
+            texture,
+            layer: 0,
+            mip_level,
+        }
+    }
+
+    pub fn cubemap_face(texture: TextureId, face: u32, mip_level: u32) -> Attachment {
+        Attachment {
+            texture,
+            layer: face,
+            mip_level,
+        }
+    }
+}
+
+impl From<TextureId> for Attachment {
+    fn from(texture: TextureId) -> Attachment {
+        Attachment::new(texture)
+    }
+}
+
 pub const MAX_VERTEX_ATTRIBUTES: usize = 16;
 pub const MAX_SHADERSTAGE_IMAGES: usize = 12;
+pub const MAX_COLOR_ATTACHMENTS: usize = 4;
+pub const MAX_UNIFORM_BLOCKS: usize = 8;
+/// Metal buffer index of `ShaderMeta::uniform_blocks[0]`, the ones below are
+/// `ShaderMeta::uniforms` and the vertex buffers.
+pub const UNIFORM_BLOCKS_BUFFER_INDEX: usize = MAX_VERTEX_ATTRIBUTES + 1;
 
 #[derive(Clone, Debug)]
 pub struct Features {
+    /// Are `VertexStep::PerInstance` layouts and draws with more than one instance
+    /// supported. False on GLES2 and WebGL1, where `new_pipeline` panics on
+    /// per-instance layouts and instanced draws are skipped with a warning.
     pub instancing: bool,
     /// Does current rendering backend support automatic resolve of
     /// multisampled render passes on end_render_pass.
@@ -582,6 +1179,36 @@ pub struct Features {
     /// With resolve_attachments: false, not-none resolve_img in new_render_pass will
     /// result in a runtime panic.
     pub resolve_attachments: bool,
+    /// Can blending be configured separately for each color attachment
+    /// with `PipelineParams::attachment_blends`.
+    /// Requires GL 4.0, GLES 3.2 or WebGL 2 with `OES_draw_buffers_indexed`,
+    /// always true on metal.
+    pub independent_blend: bool,
+    /// Are `Equation::Min` and `Equation::Max` supported.
+    /// Requires desktop GL, GLES 3, WebGL 2 or `EXT_blend_minmax`, always true on metal.
+    /// When false, pipelines with them blend with `Equation::Add` instead.
+    pub blend_minmax: bool,
+    /// Is `PolygonMode::Line` supported.
+    /// Desktop GL and metal only, GLES and WebGL always fill polygons.
+    pub polygon_mode: bool,
+    /// Is `PipelineParams::depth_clamp` supported.
+    /// Requires GL 3.2, not available on GLES and WebGL.
+    pub depth_clamp: bool,
+    /// Is `PipelineParams::primitive_restart` supported.
+    /// Requires GL 4.3 or GLES 3.0. On WebGL 2 and metal restart is always enabled.
+    pub primitive_restart: bool,
+    /// Is `RenderingBackend::draw_base_instance` with a non-zero `base_instance` supported.
+    /// Requires GL 4.2 or `GL_ARB_base_instance`, not available on GLES and WebGL.
+    pub base_instance: bool,
+    /// Is `RenderingBackend::buffer_read` supported.
+    /// Requires GL 3, GLES 3 or WebGL 2, always true on metal.
+    pub buffer_read: bool,
+    /// Are `new_feedback_shader` and feedback passes supported.
+    /// Requires GL 3, GLES 3 or WebGL 2, not available on metal.
+    pub transform_feedback: bool,
+    /// Can textures be bound to `ImageType::Shadow` images.
+    /// Requires GL 3, GLES 3 or WebGL 2, always true on metal.
+    pub shadow_samplers: bool,
 }
 
 impl Default for Features {
@@ -589,6 +1216,15 @@ impl Default for Features {
         Features {
             instancing: true,
             resolve_attachments: true,
+            independent_blend: true,
+            blend_minmax: true,
+            polygon_mode: true,
+            depth_clamp: true,
+            primitive_restart: true,
+            base_instance: true,
+            buffer_read: true,
+            transform_feedback: true,
+            shadow_samplers: true,
         }
     }
 }
@@ -601,6 +1237,15 @@ pub enum CullFace {
     Back,
 }
 
+/// Specify how polygons are rasterized.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PolygonMode {
+    Fill,
+    /// Draw polygon edges only, mostly useful for debug wireframes.
+    /// Ignored unless `ctx.info().features.polygon_mode` is true.
+    Line,
+}
+
 /// Define front- and back-facing polygons.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum FrontFaceOrder {
@@ -650,6 +1295,14 @@ pub enum Equation {
     /// Subtracts source from destination. Source and destination are
     /// multiplied by blending parameters before subtraction.
     ReverseSubtract,
+    /// Component-wise minimum of source and destination.
+    /// Blending parameters are ignored.
+    /// Requires `ctx.info().features.blend_minmax`.
+    Min,
+    /// Component-wise maximum of source and destination.
+    /// Blending parameters are ignored.
+    /// Requires `ctx.info().features.blend_minmax`.
+    Max,
 }
 
 /// Blend values.
@@ -659,6 +1312,10 @@ pub enum BlendValue {
     SourceAlpha,
     DestinationColor,
     DestinationAlpha,
+    /// Constant color, set with `apply_blend_color`.
+    ConstantColor,
+    /// Alpha of the constant color, set with `apply_blend_color`.
+    ConstantAlpha,
 }
 
 /// Blend factors.
@@ -734,6 +1391,37 @@ pub struct PipelineParams {
     pub stencil_test: Option<StencilState>,
     pub color_write: ColorMask,
     pub primitive_type: PrimitiveType,
+    /// Per color attachment blend overrides for multiple render target passes.
+    /// `None` attachments use `color_blend` and `alpha_blend`.
+    ///
+    /// Only applied when `ctx.info().features.independent_blend` is true,
+    /// otherwise `color_blend` and `alpha_blend` are used for all the attachments.
+    pub attachment_blends: [Option<AttachmentBlend>; MAX_COLOR_ATTACHMENTS],
+    pub polygon_mode: PolygonMode,
+    /// Width of rasterized lines. Widths other than 1.0 are not guaranteed to be
+    /// supported, core GL profiles and most WebGL implementations only draw 1.0 lines.
+    /// Ignored on metal.
+    pub line_width: f32,
+    /// Clamp fragment depth to the depth range instead of clipping primitives
+    /// against the near and far planes.
+    /// Ignored unless `ctx.info().features.depth_clamp` is true.
+    pub depth_clamp: bool,
+    /// Derive a coverage mask from the fragment alpha, works with multisampled
+    /// render targets only.
+    pub alpha_to_coverage: bool,
+    /// Restart line and triangle strips on the maximum index value of
+    /// the index buffer type, 0xFFFF for u16 indices and 0xFFFFFFFF for u32.
+    /// Ignored unless `ctx.info().features.primitive_restart` is true.
+    pub primitive_restart: bool,
+}
+
+/// Blend setup of a single color attachment, see `PipelineParams::attachment_blends`.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct AttachmentBlend {
+    /// Color (RGB) blend function. If None - blending will be disabled for this attachment.
+    pub color_blend: Option<BlendState>,
+    /// Alpha blend function. If None - alpha will be blended with same equation than RGB colors.
+    pub alpha_blend: Option<BlendState>,
 }
 
 // TODO(next major version bump): should be PipelineId
@@ -753,6 +1441,12 @@ impl Default for PipelineParams {
             stencil_test: None,
             color_write: (true, true, true, true),
             primitive_type: PrimitiveType::Triangles,
+            attachment_blends: [None; MAX_COLOR_ATTACHMENTS],
+            polygon_mode: PolygonMode::Fill,
+            line_width: 1.0,
+            depth_clamp: false,
+            alpha_to_coverage: false,
+            primitive_restart: false,
         }
     }
 }
@@ -776,6 +1470,48 @@ pub struct Bindings {
     pub images: Vec<TextureId>,
 }
 
+impl Bindings {
+    /// `Bindings::images` in `meta.images` order, from `(name, texture)` pairs.
+    pub fn named_images(
+        meta: &ShaderMeta,
+        images: &[(&str, TextureId)],
+    ) -> Result<Vec<TextureId>, BindingError> {
+        if let Some((name, _)) = images
+            .iter()
+            .find(|(name, _)| meta.image_index(name).is_none())
+        {
+            return Err(BindingError::UnknownImage(name.to_string()));
+        }
+        meta.images
+            .iter()
+            .map(|image| {
+                images
+                    .iter()
+                    .find(|(name, _)| *name == image.name)
+                    .map(|(_, texture)| *texture)
+                    .ok_or_else(|| BindingError::MissingImage(image.name.clone()))
+            })
+            .collect()
+    }
+
+    /// Replace the texture bound to the `name` image of `meta`.
+    pub fn set_image(
+        &mut self,
+        meta: &ShaderMeta,
+        name: &str,
+        texture: TextureId,
+    ) -> Result<(), BindingError> {
+        let index = meta
+            .image_index(name)
+            .ok_or_else(|| BindingError::UnknownImage(name.to_string()))?;
+        match self.images.get_mut(index) {
+            Some(image) => *image = texture,
+            None => return Err(BindingError::MissingImage(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BufferType {
     VertexBuffer,
@@ -1069,6 +1805,66 @@ pub enum Backend {
     OpenGl,
 }
 
+/// Implementation limits of the rendering backend, never above what miniquad
+/// itself supports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// Largest `width`/`height` of a 2D texture.
+    pub max_texture_size: u32,
+    pub max_cube_map_texture_size: u32,
+    /// Largest `TextureParams::sample_count`, 1 without multisampled render textures.
+    pub max_samples: u32,
+    /// At most `MAX_VERTEX_ATTRIBUTES`.
+    pub max_vertex_attributes: u32,
+    /// Largest number of `Bindings::images` a shader can sample,
+    /// at most `MAX_SHADERSTAGE_IMAGES`.
+    pub max_texture_units: u32,
+    /// vec4 uniforms available to each stage.
+    pub max_vertex_uniform_vectors: u32,
+    pub max_fragment_uniform_vectors: u32,
+    /// Largest number of color attachments of a render pass,
+    /// at most `MAX_COLOR_ATTACHMENTS`.
+    pub max_draw_buffers: u32,
+}
+
+impl Limits {
+    /// Driver limits clamped to miniquad's own.
+    pub(crate) fn clamped(self) -> Limits {
+        Limits {
+            max_vertex_attributes: self.max_vertex_attributes.min(MAX_VERTEX_ATTRIBUTES as _),
+            max_texture_units: self.max_texture_units.min(MAX_SHADERSTAGE_IMAGES as _),
+            max_draw_buffers: self.max_draw_buffers.min(MAX_COLOR_ATTACHMENTS as _),
+            ..self
+        }
+    }
+}
+
+/// What a `TextureFormat` can be used for, see `RenderingBackend::texture_format_caps`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextureFormatCaps {
+    /// Can be a color or, for depth formats, depth attachment of a render pass.
+    pub renderable: bool,
+    /// Can be sampled with `FilterMode::Linear`.
+    pub filterable: bool,
+    /// Render passes with the format as a color attachment can use `PipelineParams::color_blend`.
+    pub blendable: bool,
+}
+
+/// The GL context actually created, which may differ from the requested `conf::GlConfig`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GlContextInfo {
+    /// Parsed from `ContextInfo::gl_version_string`, for GLES and WebGL as well.
+    pub version: (u8, u8),
+    pub gles: bool,
+    pub core_profile: bool,
+    pub debug: bool,
+    pub robust_access: bool,
+    pub no_error: bool,
+    /// Bits of the default framebuffer.
+    pub depth_bits: u8,
+    pub stencil_bits: u8,
+}
+
 #[derive(Clone, Debug)]
 pub struct ContextInfo {
     pub backend: Backend,
@@ -1084,9 +1880,62 @@ pub struct ContextInfo {
     /// List of platform-dependent features that miniquad failed to make cross-platforms
     /// and therefore they might be missing.
     pub features: Features,
+    pub limits: Limits,
+    /// Extensions reported by the GL driver, as in GL_EXTENSIONS.
+    /// WebGL names come without the "GL_" prefix. Always empty on metal.
+    pub extensions: Vec<String>,
+    /// All default on metal.
+    pub gl_context: GlContextInfo,
+    /// The default framebuffer converts the shader output from linear to sRGB,
+    /// see `conf::Conf::srgb_framebuffer`. Shaders should apply the gamma themselves otherwise.
+    pub srgb_framebuffer: bool,
+}
+
+/// Counters of a single frame, see `RenderingBackend::stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    pub draw_calls: usize,
+    /// `apply_pipeline` calls that changed the current pipeline.
+    pub pipeline_switches: usize,
+    /// Texture binds that actually reached the driver.
+    pub texture_binds: usize,
+    /// Bytes uploaded with `new_buffer` and `buffer_update`.
+    pub buffer_upload_bytes: usize,
+    /// Redundant state changes filtered out by the backend state cache.
+    /// Always 0 on metal.
+    pub skipped_state_changes: usize,
+}
+
+/// Live GPU resources, see `RenderingBackend::stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResourceStats {
+    pub textures: usize,
+    /// Estimated from `TextureFormat::size`, driver padding and alignment are not accounted.
+    pub texture_memory: usize,
+    pub buffers: usize,
+    pub buffer_memory: usize,
+    pub shaders: usize,
+    pub passes: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RenderingStats {
+    /// Counters of the frame in progress, reset on `commit_frame`.
+    pub frame: FrameStats,
+    /// Counters of the last committed frame.
+    pub last_frame: FrameStats,
+    pub resources: ResourceStats,
 }
 
 impl ContextInfo {
+    /// Is the extension in `extensions`, `name` may omit the "GL_" prefix.
+    pub fn has_extension(&self, name: &str) -> bool {
+        let name = name.strip_prefix("GL_").unwrap_or(name);
+        self.extensions
+            .iter()
+            .any(|extension| extension.strip_prefix("GL_").unwrap_or(extension) == name)
+    }
+
     pub fn has_integer_attributes(&self) -> bool {
         match self.backend {
             Backend::Metal => true,
@@ -1128,6 +1977,18 @@ pub trait RenderingBackend {
         shader: ShaderSource,
         meta: ShaderMeta,
     ) -> Result<ShaderId, ShaderError>;
+    /// Same as `new_shader`, but the vertex shader outputs named in `varyings` can be
+    /// captured by a feedback pass: `varyings[i]` is written into the `i`th output buffer
+    /// of `begin_feedback_pass`.
+    ///
+    /// Requires `features.transform_feedback` for non-empty `varyings`. On metal, which
+    /// never has it, such a shader fails with `ShaderError::LinkError`.
+    fn new_feedback_shader(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+        varyings: &[&str],
+    ) -> Result<ShaderId, ShaderError>;
     fn new_texture(
         &mut self,
         access: TextureAccess,
@@ -1164,6 +2025,8 @@ pub trait RenderingBackend {
         )
     }
     fn texture_params(&self, texture: TextureId) -> TextureParams;
+    /// Whether textures of `format` can be rendered to, filtered and blended.
+    fn texture_format_caps(&self, format: TextureFormat) -> TextureFormatCaps;
     fn texture_size(&self, texture: TextureId) -> (u32, u32) {
         let params = self.texture_params(texture);
         (params.width, params.height)
@@ -1203,6 +2066,25 @@ pub trait RenderingBackend {
     fn texture_generate_mipmaps(&mut self, texture: TextureId);
     fn texture_resize(&mut self, texture: TextureId, width: u32, height: u32, bytes: Option<&[u8]>);
     fn texture_read_pixels(&mut self, texture: TextureId, bytes: &mut [u8]);
+    /// Read a `width`x`height` rectangle of the texture's `mip_level`.
+    /// `bytes` receives `format.size(width, height)` bytes in the layout uploads use:
+    /// half floats for `RGBA16F`, `u16` for `Depth`, `f32` for `Depth32`, one byte for `Alpha`.
+    /// Rows go from the bottom of the texture up.
+    ///
+    /// Reading depth textures is only supported on desktop GL.
+    /// On metal it panics inside of a pass, call it between `end_render_pass`
+    /// and the next `begin_pass`.
+    #[allow(clippy::too_many_arguments)]
+    fn texture_read_pixels_region(
+        &mut self,
+        texture: TextureId,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        mip_level: u32,
+        bytes: &mut [u8],
+    );
     fn texture_update_part(
         &mut self,
         texture: TextureId,
@@ -1212,6 +2094,26 @@ pub trait RenderingBackend {
         height: i32,
         bytes: &[u8],
     );
+    /// Same as `texture_update_part`, with `bytes` laid out as `upload` describes,
+    /// at least `upload.source_size(format, width, height)` long.
+    ///
+    /// Row length and BGRA sources are uploaded without a CPU copy where GL supports them,
+    /// flipped and premultiplied ones are always converted on the CPU first.
+    #[allow(clippy::too_many_arguments)]
+    fn texture_update_region(
+        &mut self,
+        texture: TextureId,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        upload: TextureUpload,
+        bytes: &[u8],
+    ) {
+        let format = self.texture_params(texture).format;
+        let packed = upload.repack(format, width as _, height as _, bytes);
+        self.texture_update_part(texture, x_offset, y_offset, width, height, &packed);
+    }
     fn new_render_pass(
         &mut self,
         color_img: TextureId,
@@ -1231,6 +2133,29 @@ pub trait RenderingBackend {
         color_img: &[TextureId],
         resolve_img: Option<&[TextureId]>,
         depth_img: Option<TextureId>,
+    ) -> RenderPass {
+        let color_img: Vec<Attachment> = color_img.iter().map(|img| (*img).into()).collect();
+        let resolve_img: Option<Vec<Attachment>> =
+            resolve_img.map(|imgs| imgs.iter().map(|img| (*img).into()).collect());
+        self.new_render_pass_attachments(
+            &color_img,
+            resolve_img.as_deref(),
+            depth_img.map(Into::into),
+        )
+    }
+    /// Same as "new_render_pass_mrt", but each attachment may target a cubemap face
+    /// and a mip level, for example to render environment probes or a bloom chain.
+    /// The viewport of the pass is the size of the first attachment's mip level.
+    ///
+    /// The same texture may be attached to several render passes,
+    /// `delete_render_pass` only deletes textures no other pass is using.
+    ///
+    /// Note that on GLES2/WebGL1 mip levels other than 0 require `OES_fbo_render_mipmap`.
+    fn new_render_pass_attachments(
+        &mut self,
+        color_img: &[Attachment],
+        resolve_img: Option<&[Attachment]>,
+        depth_img: Option<Attachment>,
     ) -> RenderPass;
     /// panics for depth-only or multiple color attachment render pass
     /// This function is, mostly, legacy. Using "render_pass_color_attachments"
@@ -1248,6 +2173,9 @@ pub trait RenderingBackend {
     }
     /// For depth-only render pass returns empty slice.
     fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId];
+    /// Delete the pass together with its color, resolve and depth textures,
+    /// except for the ones another pass still renders into. Don't delete the
+    /// attachments again afterwards, on metal that panics.
     fn delete_render_pass(&mut self, render_pass: RenderPass);
     fn new_pipeline(
         &mut self,
@@ -1281,6 +2209,12 @@ pub trait RenderingBackend {
     fn new_buffer(&mut self, type_: BufferType, usage: BufferUsage, data: BufferSource)
         -> BufferId;
     fn buffer_update(&mut self, buffer: BufferId, data: BufferSource);
+    /// Copy `bytes.len()` bytes of the buffer, starting at `offset`, into `bytes`.
+    /// Waits for the GPU to finish writing the buffer, avoid calling it every frame
+    /// right after the buffer was written.
+    ///
+    /// Requires `features.buffer_read`.
+    fn buffer_read(&mut self, buffer: BufferId, offset: usize, bytes: &mut [u8]);
 
     /// Size of buffer in bytes.
     /// For 1 element, u16 buffer this will return 2.
@@ -1295,6 +2229,18 @@ pub trait RenderingBackend {
     /// and thats why this function is not marked as unsafe
     fn delete_buffer(&mut self, buffer: BufferId);
 
+    /// Register textures and buffers finished by `loader` since the last call.
+    /// Returned ids are ready to use, no further synchronization is required.
+    ///
+    /// ```ignore
+    /// for (id, loaded) in ctx.poll_loader(&loader) {
+    ///     if let Loaded::Texture(texture) = loaded {
+    ///         self.textures.insert(id, texture);
+    ///     }
+    /// }
+    /// ```
+    fn poll_loader(&mut self, loader: &Loader) -> Vec<(LoadId, Loaded)>;
+
     /// Delete GPU texture, leaving handle unmodified.
     ///
     /// More high-level code on top of miniquad probably is going to call this in Drop
@@ -1321,6 +2267,19 @@ pub trait RenderingBackend {
     /// Should be applied after begin_pass.
     fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32);
 
+    /// Override stencil reference values of the current pipeline, allowing
+    /// the same pipeline to be used with different `test_ref`.
+    /// Should be applied after apply_pipeline, apply_pipeline resets the reference
+    /// back to `StencilFaceState::test_ref`.
+    fn apply_stencil_reference(&mut self, front: i32, back: i32);
+
+    /// Set the constant color used by `BlendValue::ConstantColor` and
+    /// `BlendValue::ConstantAlpha` blend factors.
+    /// Not a part of the pipeline, stays the same until the next `apply_blend_color`.
+    fn apply_blend_color(&mut self, r: f32, g: f32, b: f32, a: f32);
+
+    /// Panics if the textures don't match the current pipeline's images,
+    /// `validate_bindings` reports the same errors without panicking.
     fn apply_bindings_from_slice(
         &mut self,
         vertex_buffers: &[BufferId],
@@ -1328,6 +2287,16 @@ pub trait RenderingBackend {
         textures: &[TextureId],
     );
 
+    /// Check the `bindings` textures against the images of `pipeline`'s shader.
+    /// `apply_bindings` panics on the same errors.
+    fn validate_bindings(
+        &self,
+        pipeline: &Pipeline,
+        bindings: &Bindings,
+    ) -> Result<(), BindingError>;
+
+    /// Panics if `bindings.images` don't match the current pipeline's images,
+    /// check them with `validate_bindings` first to get an error instead.
     fn apply_bindings(&mut self, bindings: &Bindings) {
         self.apply_bindings_from_slice(
             &bindings.vertex_buffers,
@@ -1341,6 +2310,13 @@ pub trait RenderingBackend {
     }
     fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize);
 
+    /// Upload `ShaderMeta::uniform_blocks[block]` of the current pipeline's shader,
+    /// the other blocks keep their values.
+    fn apply_uniform_block(&mut self, block: usize, uniforms: UniformsSource) {
+        self.apply_uniform_block_from_bytes(block, uniforms.0.ptr as _, uniforms.0.size)
+    }
+    fn apply_uniform_block_from_bytes(&mut self, block: usize, uniform_ptr: *const u8, size: usize);
+
     fn clear(
         &mut self,
         color: Option<(f32, f32, f32, f32)>,
@@ -1354,8 +2330,37 @@ pub trait RenderingBackend {
 
     fn end_render_pass(&mut self);
 
+    /// Apply `pipeline`, whose shader comes from `new_feedback_shader`, and start
+    /// capturing its vertex shader outputs into `outputs`, one buffer per varying.
+    /// Nothing is rasterized until `end_feedback_pass`.
+    ///
+    /// The pipeline can't be changed inside the pass: call `begin_feedback_pass`,
+    /// then `apply_bindings`, `apply_uniforms` and `draw` as often as needed, then
+    /// `end_feedback_pass`. `apply_pipeline` panics in between.
+    /// `draw` inside a feedback pass reads `num_elements` vertices, starting at `base_element`,
+    /// straight from the vertex buffers, the index buffer is ignored.
+    ///
+    /// Requires `features.transform_feedback`, panics without it.
+    fn begin_feedback_pass(&mut self, pipeline: &Pipeline, outputs: &[BufferId]);
+
+    fn end_feedback_pass(&mut self);
+
+    /// Draws the debug overlay when it is shown, see `window::set_debug_overlay`,
+    /// deletes everything queued in `deletion_queue` and finishes the frame.
     fn commit_frame(&mut self);
 
+    /// Queue shared by the owned wrappers from the `resources` module,
+    /// drained in `commit_frame`.
+    ///
+    /// Required since the `resources` module was added, which breaks backends implemented
+    /// outside of miniquad: they should keep one `DeletionQueue`, return clones of it here
+    /// and call `DeletionQueue::drain` in their `commit_frame`.
+    fn deletion_queue(&self) -> resources::DeletionQueue;
+
+    /// Rendering statistics of the current and the last frame, and
+    /// the resources currently alive.
+    fn stats(&self) -> RenderingStats;
+
     /// Draw elements using currently applied bindings and pipeline.
     ///
     /// + `base_element` specifies starting offset in `index_buffer`.
@@ -1365,4 +2370,384 @@ pub trait RenderingBackend {
     /// NOTE: num_instances > 1 might be not supported by the GPU (gl2.1 and gles2).
     /// `features.instancing` check is required.
     fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32);
+
+    /// Same as `draw`, but per-instance attributes start at `base_instance`
+    /// instead of the first element of their buffers.
+    ///
+    /// NOTE: base_instance != 0 requires `features.base_instance`.
+    fn draw_base_instance(
+        &self,
+        base_element: i32,
+        num_elements: i32,
+        num_instances: i32,
+        base_instance: i32,
+    );
+
+    /// Execute commands recorded with `CommandBuffer`, in the order they were recorded.
+    /// Should be called inside a pass, the same as the recorded calls themselves.
+    /// Buffers recorded on different threads are executed in the order of submission.
+    fn execute_commands(&mut self, commands: &CommandBuffer) {
+        commands.execute(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_memory_size() {
+        let params = TextureParams {
+            width: 16,
+            height: 8,
+            ..Default::default()
+        };
+        assert_eq!(params.memory_size(), 16 * 8 * 4);
+
+        let params = TextureParams {
+            kind: TextureKind::CubeMap,
+            format: TextureFormat::RGB8,
+            ..params
+        };
+        assert_eq!(params.memory_size(), 16 * 8 * 3 * 6);
+
+        let params = TextureParams {
+            format: TextureFormat::Depth32,
+            sample_count: 4,
+            width: 16,
+            height: 8,
+            ..Default::default()
+        };
+        assert_eq!(params.memory_size(), 16 * 8 * 4 * 4);
+
+        let params = TextureParams {
+            allocate_mipmaps: true,
+            width: 16,
+            height: 16,
+            ..Default::default()
+        };
+        assert_eq!(params.memory_size(), 1024 + 1024 / 3);
+    }
+
+    #[test]
+    fn shader_meta_default() {
+        let meta = ShaderMeta {
+            uniforms: UniformBlockLayout {
+                uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
+            },
+            ..Default::default()
+        };
+        assert_eq!(meta.uniforms.uniforms.len(), 1);
+        assert!(meta.uniform_blocks.is_empty());
+        assert!(meta.images.is_empty());
+        assert_eq!(meta.image_index("tex"), None);
+    }
+
+    #[test]
+    fn upload_source_size() {
+        let upload = TextureUpload::default();
+        assert_eq!(upload.source_size(TextureFormat::RGBA8, 4, 3), 4 * 3 * 4);
+        assert_eq!(upload.source_size(TextureFormat::RGBA8, 0, 3), 0);
+
+        // the last row stops at `width`, not at `row_length`
+        let upload = TextureUpload {
+            row_length: 10,
+            ..Default::default()
+        };
+        assert_eq!(
+            upload.source_size(TextureFormat::RGB8, 4, 3),
+            (10 * 2 + 4) * 3
+        );
+        // a row_length below width is ignored
+        let upload = TextureUpload {
+            row_length: 2,
+            ..Default::default()
+        };
+        assert_eq!(upload.source_size(TextureFormat::Alpha, 4, 3), 4 * 3);
+    }
+
+    #[test]
+    fn upload_repack_rows() {
+        // 2x2 part of a 3 pixels wide image, one byte per pixel
+        let source = [1, 2, 3, 4, 5, 6, 7, 8];
+        let upload = TextureUpload {
+            row_length: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            upload.repack(TextureFormat::Alpha, 2, 2, &source),
+            vec![1, 2, 4, 5]
+        );
+
+        let upload = TextureUpload {
+            flip_y: true,
+            ..upload
+        };
+        assert_eq!(
+            upload.repack(TextureFormat::Alpha, 2, 2, &source),
+            vec![4, 5, 1, 2]
+        );
+    }
+
+    #[test]
+    fn upload_repack_pixels() {
+        let upload = TextureUpload {
+            bgra: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            upload.repack(TextureFormat::RGBA8, 1, 1, &[1, 2, 3, 4]),
+            vec![3, 2, 1, 4]
+        );
+
+        let upload = TextureUpload {
+            premultiply_alpha: true,
+            ..Default::default()
+        };
+        // rounded to the nearest value: 255 * 128 / 255 = 128, 1 * 128 / 255 = 0.502,
+        // 200 * 128 / 255 = 100.39
+        assert_eq!(
+            upload.repack(TextureFormat::RGBA8, 1, 1, &[255, 1, 200, 128]),
+            vec![128, 1, 100, 128]
+        );
+        assert_eq!(
+            upload.repack(
+                TextureFormat::RGBA8,
+                2,
+                1,
+                &[10, 20, 30, 0, 10, 20, 30, 255]
+            ),
+            vec![0, 0, 0, 0, 10, 20, 30, 255]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "only supported for RGBA8")]
+    fn upload_bgra_non_rgba8() {
+        let upload = TextureUpload {
+            bgra: true,
+            ..Default::default()
+        };
+        upload.check_format(TextureFormat::RGB8);
+    }
+
+    fn texture(n: usize) -> TextureId {
+        TextureId(TextureIdInner::Managed(n))
+    }
+
+    fn images_meta() -> ShaderMeta {
+        ShaderMeta {
+            images: vec!["color".into(), ImageDesc::new("shadow", ImageType::Shadow)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn named_images() {
+        let meta = images_meta();
+        let images =
+            Bindings::named_images(&meta, &[("shadow", texture(1)), ("color", texture(0))])
+                .unwrap();
+        assert_eq!(images, vec![texture(0), texture(1)]);
+
+        let missing = Bindings::named_images(&meta, &[("color", texture(0))]);
+        assert!(matches!(missing, Err(BindingError::MissingImage(name)) if name == "shadow"));
+
+        let unknown = Bindings::named_images(
+            &meta,
+            &[
+                ("color", texture(0)),
+                ("shadow", texture(1)),
+                ("normal", texture(2)),
+            ],
+        );
+        assert!(matches!(unknown, Err(BindingError::UnknownImage(name)) if name == "normal"));
+    }
+
+    fn all_features() -> Features {
+        Features {
+            instancing: true,
+            resolve_attachments: true,
+            independent_blend: true,
+            blend_minmax: true,
+            polygon_mode: true,
+            depth_clamp: true,
+            primitive_restart: true,
+            base_instance: true,
+            buffer_read: true,
+            transform_feedback: true,
+            shadow_samplers: true,
+        }
+    }
+
+    #[test]
+    fn validate_shadow_images() {
+        let mut features = all_features();
+        let meta = images_meta();
+        let params = |texture: TextureId| match texture.0 {
+            TextureIdInner::Managed(0) => TextureParams::default(),
+            _ => TextureParams {
+                format: TextureFormat::Depth,
+                ..Default::default()
+            },
+        };
+        let textures = [texture(0), texture(1)];
+        assert!(validate_images(&meta.images, &textures, &features, params).is_ok());
+
+        let swapped = [texture(1), texture(0)];
+        assert!(matches!(
+            validate_images(&meta.images, &swapped, &features, params),
+            Err(BindingError::IncompatibleTexture { image, .. }) if image == "shadow"
+        ));
+
+        features.shadow_samplers = false;
+        assert!(matches!(
+            validate_images(&meta.images, &textures, &features, params),
+            Err(BindingError::UnsupportedImageType { image, .. }) if image == "shadow"
+        ));
+    }
+
+    #[test]
+    fn has_extension() {
+        let info = ContextInfo {
+            backend: Backend::OpenGl,
+            gl_version_string: "WebGL 2.0".to_string(),
+            glsl_support: Default::default(),
+            features: all_features(),
+            limits: Limits {
+                max_texture_size: 0,
+                max_cube_map_texture_size: 0,
+                max_samples: 1,
+                max_vertex_attributes: 0,
+                max_texture_units: 0,
+                max_vertex_uniform_vectors: 0,
+                max_fragment_uniform_vectors: 0,
+                max_draw_buffers: 1,
+            },
+            extensions: vec![
+                "GL_EXT_blend_minmax".to_string(),
+                "OES_draw_buffers_indexed".to_string(),
+            ],
+            gl_context: Default::default(),
+            srgb_framebuffer: false,
+        };
+        assert!(info.has_extension("GL_EXT_blend_minmax"));
+        assert!(info.has_extension("EXT_blend_minmax"));
+        assert!(info.has_extension("GL_OES_draw_buffers_indexed"));
+        assert!(info.has_extension("OES_draw_buffers_indexed"));
+        assert!(!info.has_extension("OES_draw_buffers"));
+        assert!(!info.has_extension("GL_EXT_color_buffer_float"));
+    }
+
+    #[test]
+    fn texture_mip_levels() {
+        let levels = |width, height, allocate_mipmaps| {
+            TextureParams {
+                width,
+                height,
+                allocate_mipmaps,
+                ..Default::default()
+            }
+            .mip_levels()
+        };
+        assert_eq!(levels(256, 256, false), 1);
+        assert_eq!(levels(256, 256, true), 9);
+        assert_eq!(levels(256, 16, true), 9);
+        assert_eq!(levels(3, 300, true), 9);
+        assert_eq!(levels(1, 1, true), 1);
+        assert_eq!(levels(0, 0, true), 1);
+    }
+
+    #[test]
+    fn vertex_format_sizes() {
+        let formats = [
+            VertexFormat::Float1,
+            VertexFormat::Float2,
+            VertexFormat::Float3,
+            VertexFormat::Float4,
+            VertexFormat::Byte1,
+            VertexFormat::Byte2,
+            VertexFormat::Byte3,
+            VertexFormat::Byte4,
+            VertexFormat::Short1,
+            VertexFormat::Short2,
+            VertexFormat::Short3,
+            VertexFormat::Short4,
+            VertexFormat::Int1,
+            VertexFormat::Int2,
+            VertexFormat::Int3,
+            VertexFormat::Int4,
+            VertexFormat::Byte1Norm,
+            VertexFormat::Byte2Norm,
+            VertexFormat::Byte3Norm,
+            VertexFormat::Byte4Norm,
+            VertexFormat::SByte1,
+            VertexFormat::SByte2,
+            VertexFormat::SByte3,
+            VertexFormat::SByte4,
+            VertexFormat::SByte1Norm,
+            VertexFormat::SByte2Norm,
+            VertexFormat::SByte3Norm,
+            VertexFormat::SByte4Norm,
+            VertexFormat::Short1Norm,
+            VertexFormat::Short2Norm,
+            VertexFormat::Short3Norm,
+            VertexFormat::Short4Norm,
+            VertexFormat::SShort1,
+            VertexFormat::SShort2,
+            VertexFormat::SShort3,
+            VertexFormat::SShort4,
+            VertexFormat::SShort1Norm,
+            VertexFormat::SShort2Norm,
+            VertexFormat::SShort3Norm,
+            VertexFormat::SShort4Norm,
+            VertexFormat::Half1,
+            VertexFormat::Half2,
+            VertexFormat::Half3,
+            VertexFormat::Half4,
+            VertexFormat::UInt2_10_10_10_Rev,
+            VertexFormat::Int2_10_10_10_Rev,
+            VertexFormat::Mat4,
+        ];
+        for format in formats {
+            let component_size = match format.type_() {
+                GL_BYTE | GL_UNSIGNED_BYTE => 1,
+                GL_SHORT | GL_UNSIGNED_SHORT | GL_HALF_FLOAT => 2,
+                GL_INT | GL_UNSIGNED_INT | GL_FLOAT => 4,
+                // all four components share one u32
+                GL_INT_2_10_10_10_REV | GL_UNSIGNED_INT_2_10_10_10_REV => 1,
+                type_ => panic!("{:?}: unexpected type {}", format, type_),
+            };
+            assert_eq!(
+                format.size_bytes(),
+                format.components() * component_size,
+                "{format:?}"
+            );
+        }
+        assert_eq!(VertexFormat::Int2_10_10_10_Rev.size_bytes(), 4);
+        assert_eq!(VertexFormat::Half3.size_bytes(), 6);
+        assert_eq!(VertexFormat::Mat4.size_bytes(), 64);
+    }
+
+    #[test]
+    fn clamped_limits() {
+        let limits = Limits {
+            max_texture_size: 16384,
+            max_cube_map_texture_size: 16384,
+            max_samples: 8,
+            max_vertex_attributes: 31,
+            max_texture_units: 16,
+            max_vertex_uniform_vectors: 4096,
+            max_fragment_uniform_vectors: 4096,
+            max_draw_buffers: 2,
+        };
+        assert_eq!(
+            limits.clamped(),
+            Limits {
+                max_vertex_attributes: MAX_VERTEX_ATTRIBUTES as _,
+                max_texture_units: MAX_SHADERSTAGE_IMAGES as _,
+                ..limits
+            }
+        );
+    }
 }
@@ -0,0 +1,754 @@
+//! Textured and colored 2D quads and triangles, batched into as few draw calls as possible.
+//!
+//! Built only on top of the public `RenderingBackend` API, works with any backend.
+//!
+//! ```ignore
+//! let mut batch = Batcher::new(&mut *ctx);
+//!
+//! // each frame
+//! ctx.begin_default_pass(PassAction::clear_color(0., 0., 0., 1.));
+//! batch.rect(10., 10., 100., 100., [1., 0., 0., 1.]);
+//! batch.set_texture(Some(sprite));
+//! batch.push_scissor(0, 0, 64, 64);
+//! batch.rect_uv(20., 20., 32., 32., [0., 0., 0.5, 0.5], [1., 1., 1., 1.]);
+//! batch.pop_scissor();
+//! batch.flush(&mut *ctx);
+//! ctx.end_render_pass();
+//! ctx.commit_frame();
+//! ```
+//!
+//! Primitives are recorded on the CPU and only sent to the GPU in `flush`, a new
+//! draw call is started each time the texture, the pipeline or the scissor
+//! rectangle changes between two primitives.
+
+use super::{resources, *};
+
+use std::mem;
+
+/// Vertex of the batcher's vertex buffer, `color` multiplies the texture color.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BatchVertex {
+    pub pos: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [u8; 4],
+}
+
+impl BatchVertex {
+    pub fn new(x: f32, y: f32, u: f32, v: f32, color: [f32; 4]) -> BatchVertex {
+        let [r, g, b, a] = color;
+        let byte = |c: f32| (c.clamp(0., 1.) * 255.).round() as u8;
+        BatchVertex {
+            pos: [x, y],
+            uv: [u, v],
+            color: [byte(r), byte(g), byte(b), byte(a)],
+        }
+    }
+}
+
+/// Vertex attributes of `BatchVertex`, for pipelines created outside of `Batcher::new_pipeline`.
+pub const ATTRIBUTES: [VertexAttribute; 3] = [
+    VertexAttribute::new("in_pos", VertexFormat::Float2),
+    VertexAttribute::new("in_uv", VertexFormat::Float2),
+    VertexAttribute::new("in_color", VertexFormat::Byte4Norm),
+];
+
+/// Projection used when none is set with `Batcher::set_projection`: pixel coordinates
+/// with the origin in the top-left corner of a `width`x`height` render target.
+pub fn pixel_projection(width: f32, height: f32) -> [f32; 16] {
+    #[rustfmt::skip]
+    let mvp = [
+        2. / width, 0., 0., 0.,
+        0., -2. / height, 0., 0.,
+        0., 0., 1., 0.,
+        -1., 1., 0., 1.,
+    ];
+    mvp
+}
+
+// everything that splits a batch into separate draw calls
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct State {
+    texture: TextureId,
+    pipeline: Pipeline,
+    // top-left origin
+    scissor: Option<(i32, i32, i32, i32)>,
+}
+
+struct Primitive {
+    state: State,
+    z: f32,
+    first_vertex: usize,
+    num_vertices: usize,
+    first_index: usize,
+    num_indices: usize,
+}
+
+// consecutive indices of a chunk drawn with the same state
+struct Run {
+    state: State,
+    first_index: usize,
+    num_indices: usize,
+}
+
+// what fits into the vertex and index buffers at once
+#[derive(Default)]
+struct Chunk {
+    runs: Vec<Run>,
+    vertices: Vec<BatchVertex>,
+    indices: Vec<u16>,
+}
+
+impl Chunk {
+    fn clear(&mut self) {
+        self.runs.clear();
+        self.vertices.clear();
+        self.indices.clear();
+    }
+}
+
+// primitives recorded since the last flush, everything but the GPU side of the batcher
+struct Recording {
+    max_vertices: usize,
+    max_indices: usize,
+    primitives: Vec<Primitive>,
+    // primitive's indices are relative to its first vertex
+    vertices: Vec<BatchVertex>,
+    indices: Vec<u16>,
+}
+
+impl Recording {
+    fn new(max_vertices: usize, max_indices: usize) -> Recording {
+        Recording {
+            max_vertices,
+            max_indices,
+            primitives: vec![],
+            vertices: vec![],
+            indices: vec![],
+        }
+    }
+
+    fn push(&mut self, state: State, z: f32, vertices: &[BatchVertex], indices: &[u16]) {
+        let (max_vertices, max_indices) = (self.max_vertices, self.max_indices);
+        match self.primitives.last_mut() {
+            Some(last)
+                if last.state == state
+                    && last.z == z
+                    && last.num_vertices + vertices.len() <= max_vertices
+                    && last.num_indices + indices.len() <= max_indices =>
+            {
+                let base = last.num_vertices as u16;
+                self.indices.extend(indices.iter().map(|i| base + i));
+                last.num_vertices += vertices.len();
+                last.num_indices += indices.len();
+            }
+            _ => {
+                self.primitives.push(Primitive {
+                    state,
+                    z,
+                    first_vertex: self.vertices.len(),
+                    num_vertices: vertices.len(),
+                    first_index: self.indices.len(),
+                    num_indices: indices.len(),
+                });
+                self.indices.extend_from_slice(indices);
+            }
+        }
+        self.vertices.extend_from_slice(vertices);
+    }
+
+    fn clear(&mut self) {
+        self.primitives.clear();
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    // indices of the primitives in drawing order
+    fn order(&self, z_sorting: bool, order: &mut Vec<usize>) {
+        order.clear();
+        order.extend(0..self.primitives.len());
+        if z_sorting {
+            // stable, the same z keeps the recording order
+            let primitives = &self.primitives;
+            order.sort_by(|a, b| primitives[*a].z.total_cmp(&primitives[*b].z));
+        }
+    }
+
+    // fill `chunk` with the primitives in `order`, calling `draw` each time
+    // the buffers are full and once at the end
+    fn chunks(&self, order: &[usize], chunk: &mut Chunk, mut draw: impl FnMut(&Chunk)) {
+        chunk.clear();
+        for &i in order {
+            let primitive = &self.primitives[i];
+            if chunk.vertices.len() + primitive.num_vertices > self.max_vertices
+                || chunk.indices.len() + primitive.num_indices > self.max_indices
+            {
+                draw(chunk);
+                chunk.clear();
+            }
+
+            let base = chunk.vertices.len() as u16;
+            let first_index = chunk.indices.len();
+            chunk.vertices.extend_from_slice(
+                &self.vertices
+                    [primitive.first_vertex..primitive.first_vertex + primitive.num_vertices],
+            );
+            chunk.indices.extend(
+                self.indices[primitive.first_index..primitive.first_index + primitive.num_indices]
+                    .iter()
+                    .map(|i| base + i),
+            );
+            match chunk.runs.last_mut() {
+                Some(run) if run.state == primitive.state => {
+                    run.num_indices += primitive.num_indices
+                }
+                _ => chunk.runs.push(Run {
+                    state: primitive.state,
+                    first_index,
+                    num_indices: primitive.num_indices,
+                }),
+            }
+        }
+        draw(chunk);
+    }
+}
+
+// intersection of two (x, y, w, h) rectangles, empty ones have 0 width or height
+fn intersect(a: (i32, i32, i32, i32), b: (i32, i32, i32, i32)) -> (i32, i32, i32, i32) {
+    let (x0, y0) = (a.0.max(b.0), a.1.max(b.1));
+    let (x1, y1) = ((a.0 + a.2).min(b.0 + b.2), (a.1 + a.3).min(b.1 + b.3));
+    (x0, y0, (x1 - x0).max(0), (y1 - y0).max(0))
+}
+
+// `scissor` clamped to the `width`x`height` target, with the bottom-left origin
+// of `apply_scissor_rect`. Never negative, metal takes unsigned rectangles.
+fn target_scissor(
+    scissor: Option<(i32, i32, i32, i32)>,
+    width: i32,
+    height: i32,
+) -> (i32, i32, i32, i32) {
+    let (x, y, w, h) = match scissor {
+        Some(rect) => intersect(rect, (0, 0, width, height)),
+        None => (0, 0, width, height),
+    };
+    // empty rectangles may start past the target
+    let (x, y) = (x.min(width), y.min(height));
+    (x, height - (y + h), w, h)
+}
+
+// whether `scissor` has to be applied, `applied` is None while the applied
+// rectangle is unknown
+fn scissor_changed(
+    applied: &mut Option<Option<(i32, i32, i32, i32)>>,
+    scissor: Option<(i32, i32, i32, i32)>,
+) -> bool {
+    if *applied == Some(scissor) {
+        return false;
+    }
+    *applied = Some(scissor);
+    true
+}
+
+pub struct Batcher {
+    vertex_buffer: resources::Buffer,
+    index_buffer: resources::Buffer,
+    // declared before the shader to be deleted first
+    pipeline: resources::PipelineHandle,
+    _shader: resources::Shader,
+    white_texture: resources::Texture,
+
+    target_size: Option<(f32, f32)>,
+    projection: Option<[f32; 16]>,
+    texture: Option<TextureId>,
+    custom_pipeline: Option<Pipeline>,
+    z: f32,
+    z_sorting: bool,
+    scissors: Vec<(i32, i32, i32, i32)>,
+
+    recording: Recording,
+
+    // flush storage, kept to reuse the allocations
+    order: Vec<usize>,
+    chunk: Chunk,
+}
+
+impl Batcher {
+    /// Batcher with room for 8192 vertices and 12288 indices per draw call chunk.
+    pub fn new(ctx: &mut dyn RenderingBackend) -> Batcher {
+        Batcher::with_capacity(ctx, 8192, 12288)
+    }
+
+    /// Primitives recorded between two `flush`es may take more than `max_vertices`
+    /// and `max_indices`, the buffers are then re-filled for each chunk.
+    /// A single primitive should fit, and `max_vertices` is at most 65536, indices are `u16`.
+    pub fn with_capacity(
+        ctx: &mut dyn RenderingBackend,
+        max_vertices: usize,
+        max_indices: usize,
+    ) -> Batcher {
+        assert!(
+            max_vertices > 0 && max_vertices <= u16::MAX as usize + 1,
+            "max_vertices should be in 1..=65536"
+        );
+        assert!(max_indices > 0, "max_indices should be more than 0");
+
+        let vertex_buffer = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<BatchVertex>(max_vertices),
+        );
+        let index_buffer = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<u16>(max_indices),
+        );
+        let source = match ctx.info().backend {
+            Backend::OpenGl => ShaderSource::Glsl {
+                vertex: shader::VERTEX,
+                fragment: shader::FRAGMENT,
+            },
+            Backend::Metal => ShaderSource::Msl {
+                program: shader::METAL,
+            },
+        };
+        let shader = ctx.new_shader(source, shader::meta()).unwrap();
+        let pipeline = Batcher::new_pipeline(
+            ctx,
+            shader,
+            PipelineParams {
+                color_blend: Some(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+        );
+        let white_texture = ctx.new_texture_from_rgba8(1, 1, &[255; 4]);
+
+        Batcher {
+            vertex_buffer: resources::Buffer::new(ctx, vertex_buffer),
+            index_buffer: resources::Buffer::new(ctx, index_buffer),
+            pipeline: resources::PipelineHandle::new(ctx, pipeline),
+            _shader: resources::Shader::new(ctx, shader),
+            white_texture: resources::Texture::new(ctx, white_texture),
+            target_size: None,
+            projection: None,
+            texture: None,
+            custom_pipeline: None,
+            z: 0.,
+            z_sorting: false,
+            scissors: vec![],
+            recording: Recording::new(max_vertices, max_indices),
+            order: vec![],
+            chunk: Chunk::default(),
+        }
+    }
+
+    /// Pipeline with the batcher's vertex layout, to be used with `set_pipeline`.
+    ///
+    /// `shader` gets `BatchVertex` as `in_pos`, `in_uv` and `in_color` attributes,
+    /// the `mvp` uniform and the `tex` image, see the `shader` module.
+    pub fn new_pipeline(
+        ctx: &mut dyn RenderingBackend,
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Pipeline {
+        ctx.new_pipeline(&[BufferLayout::default()], &ATTRIBUTES, shader, params)
+    }
+
+    /// Size of the render target in pixels, used for the default projection and
+    /// the scissor rectangles.
+    /// `None`, the default, is the window size, `window::screen_size`, at the time of `flush`.
+    pub fn set_target_size(&mut self, size: Option<(f32, f32)>) {
+        self.target_size = size;
+    }
+
+    /// Column-major matrix applied to all the vertices in `flush`.
+    /// `None`, the default, is `pixel_projection` of the target size.
+    pub fn set_projection(&mut self, projection: Option<[f32; 16]>) {
+        self.projection = projection;
+    }
+
+    /// Texture of the following primitives, `None` is a white texture,
+    /// to draw only with the vertex colors.
+    pub fn set_texture(&mut self, texture: Option<TextureId>) {
+        self.texture = texture;
+    }
+
+    /// Pipeline of the following primitives, created with `Batcher::new_pipeline`.
+    /// `None` is the default alpha blended one.
+    pub fn set_pipeline(&mut self, pipeline: Option<Pipeline>) {
+        self.custom_pipeline = pipeline;
+    }
+
+    /// Depth of the following primitives. Only used with `set_z_sorting(true)`.
+    pub fn set_z(&mut self, z: f32) {
+        self.z = z;
+    }
+
+    /// With z sorting, primitives are drawn from the lowest to the highest z,
+    /// primitives with the same z in the order they were recorded.
+    /// Without it, the default, everything is drawn in the recording order.
+    pub fn set_z_sorting(&mut self, z_sorting: bool) {
+        self.z_sorting = z_sorting;
+    }
+
+    /// Clip the following primitives to the rectangle, in pixels from the top-left
+    /// corner of the target, until the matching `pop_scissor`.
+    /// Nested rectangles are intersected with the enclosing ones, and all of them
+    /// with the target in `flush`.
+    pub fn push_scissor(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        let rect = match self.scissors.last() {
+            Some(&parent) => intersect((x, y, w, h), parent),
+            None => (x, y, w.max(0), h.max(0)),
+        };
+        self.scissors.push(rect);
+    }
+
+    pub fn pop_scissor(&mut self) {
+        let popped = self.scissors.pop();
+        assert!(
+            popped.is_some(),
+            "pop_scissor without a matching push_scissor"
+        );
+    }
+
+    /// Axis aligned rectangle with the whole current texture.
+    pub fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: [f32; 4]) {
+        self.rect_uv(x, y, w, h, [0., 0., 1., 1.], color);
+    }
+
+    /// Axis aligned rectangle with the `[u, v, width, height]` part of the current texture.
+    pub fn rect_uv(&mut self, x: f32, y: f32, w: f32, h: f32, uv: [f32; 4], color: [f32; 4]) {
+        let [u, v, uw, vh] = uv;
+        self.triangles(
+            &[
+                BatchVertex::new(x, y, u, v, color),
+                BatchVertex::new(x + w, y, u + uw, v, color),
+                BatchVertex::new(x + w, y + h, u + uw, v + vh, color),
+                BatchVertex::new(x, y + h, u, v + vh, color),
+            ],
+            &[0, 1, 2, 0, 2, 3],
+        );
+    }
+
+    /// Quad with arbitrary corners, in clockwise or counter-clockwise order.
+    pub fn quad(&mut self, corners: [BatchVertex; 4]) {
+        self.triangles(&corners, &[0, 1, 2, 0, 2, 3]);
+    }
+
+    pub fn triangle(&mut self, a: [f32; 2], b: [f32; 2], c: [f32; 2], color: [f32; 4]) {
+        self.triangles(
+            &[
+                BatchVertex::new(a[0], a[1], 0., 0., color),
+                BatchVertex::new(b[0], b[1], 0., 0., color),
+                BatchVertex::new(c[0], c[1], 0., 0., color),
+            ],
+            &[0, 1, 2],
+        );
+    }
+
+    /// Indexed triangle list, `indices` point into `vertices`.
+    pub fn triangles(&mut self, vertices: &[BatchVertex], indices: &[u16]) {
+        assert!(
+            vertices.len() <= self.recording.max_vertices
+                && indices.len() <= self.recording.max_indices,
+            "a single batch primitive does not fit into the batcher's capacity"
+        );
+        if vertices.is_empty() || indices.is_empty() {
+            return;
+        }
+
+        let state = State {
+            texture: self.texture.unwrap_or_else(|| self.white_texture.id()),
+            pipeline: self.custom_pipeline.unwrap_or_else(|| self.pipeline.id()),
+            scissor: self.scissors.last().copied(),
+        };
+        self.recording.push(state, self.z, vertices, indices);
+    }
+
+    /// Drop everything recorded since the last `flush`.
+    pub fn clear(&mut self) {
+        self.recording.clear();
+    }
+
+    /// Draw everything recorded since the last `flush`.
+    /// Should be called inside a render pass.
+    ///
+    /// Leaves the last used pipeline, bindings and scissor rectangle applied.
+    pub fn flush(&mut self, ctx: &mut dyn RenderingBackend) {
+        if self.recording.primitives.is_empty() {
+            return;
+        }
+
+        let target_size = self.target_size.unwrap_or_else(crate::window::screen_size);
+        let mvp = self
+            .projection
+            .unwrap_or_else(|| pixel_projection(target_size.0, target_size.1));
+
+        let mut order = mem::take(&mut self.order);
+        self.recording.order(self.z_sorting, &mut order);
+
+        let mut chunk = mem::take(&mut self.chunk);
+        // unknown, an earlier flush in the same pass or the user may have
+        // left any rectangle applied
+        let mut scissor = None;
+        self.recording.chunks(&order, &mut chunk, |chunk| {
+            self.draw_chunk(ctx, chunk, &mvp, target_size, &mut scissor)
+        });
+
+        self.order = order;
+        self.chunk = chunk;
+        self.clear();
+    }
+
+    fn draw_chunk(
+        &self,
+        ctx: &mut dyn RenderingBackend,
+        chunk: &Chunk,
+        mvp: &[f32; 16],
+        target_size: (f32, f32),
+        scissor: &mut Option<Option<(i32, i32, i32, i32)>>,
+    ) {
+        let vertex_buffer = self.vertex_buffer.id();
+        let index_buffer = self.index_buffer.id();
+        ctx.buffer_update(vertex_buffer, BufferSource::slice(&chunk.vertices));
+        ctx.buffer_update(index_buffer, BufferSource::slice(&chunk.indices));
+
+        // updated buffers may need to be re-bound, on Metal each update
+        // goes into a new copy, so nothing is reused from the previous chunk
+        let mut current: Option<State> = None;
+        for run in &chunk.runs {
+            let state = run.state;
+            let pipeline_changed = current.map_or(true, |c| c.pipeline != state.pipeline);
+            if pipeline_changed {
+                ctx.apply_pipeline(&state.pipeline);
+                ctx.apply_uniforms(UniformsSource::table(mvp));
+            }
+            if pipeline_changed || current.map_or(true, |c| c.texture != state.texture) {
+                ctx.apply_bindings_from_slice(&[vertex_buffer], index_buffer, &[state.texture]);
+            }
+            if scissor_changed(scissor, state.scissor) {
+                let (width, height) = (target_size.0 as i32, target_size.1 as i32);
+                let (x, y, w, h) = target_scissor(state.scissor, width, height);
+                ctx.apply_scissor_rect(x, y, w, h);
+            }
+            ctx.draw(run.first_index as i32, run.num_indices as i32, 1);
+            current = Some(state);
+        }
+    }
+}
+
+/// Sources of the default batcher shader, a starting point for custom
+/// `Batcher::new_pipeline` shaders.
+pub mod shader {
+    use crate::*;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec2 in_pos;
+    attribute vec2 in_uv;
+    attribute vec4 in_color;
+
+    uniform mat4 mvp;
+
+    varying mediump vec2 uv;
+    varying lowp vec4 color;
+
+    void main() {
+        gl_Position = mvp * vec4(in_pos, 0.0, 1.0);
+        uv = in_uv;
+        color = in_color;
+    }"#;
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying mediump vec2 uv;
+    varying lowp vec4 color;
+
+    uniform sampler2D tex;
+
+    void main() {
+        gl_FragColor = texture2D(tex, uv) * color;
+    }"#;
+
+    pub const METAL: &str = r#"
+    #include <metal_stdlib>
+
+    using namespace metal;
+
+    struct Uniforms
+    {
+        float4x4 mvp;
+    };
+
+    struct Vertex
+    {
+        float2 in_pos   [[attribute(0)]];
+        float2 in_uv    [[attribute(1)]];
+        float4 in_color [[attribute(2)]];
+    };
+
+    struct RasterizerData
+    {
+        float4 position [[position]];
+        float2 uv       [[user(locn0)]];
+        float4 color    [[user(locn1)]];
+    };
+
+    vertex RasterizerData vertexShader(
+      Vertex v [[stage_in]],
+      constant Uniforms& uniforms [[buffer(0)]])
+    {
+        RasterizerData out;
+
+        out.position = uniforms.mvp * float4(v.in_pos, 0.0, 1.0);
+        out.uv = v.in_uv;
+        out.color = v.in_color;
+
+        return out;
+    }
+
+    fragment float4 fragmentShader(RasterizerData in [[stage_in]], texture2d<float> tex [[texture(0)]], sampler texSmplr [[sampler(0)]])
+    {
+        return tex.sample(texSmplr, in.uv) * in.color;
+    }"#;
+
+    pub fn meta() -> ShaderMeta {
+        ShaderMeta {
+            images: vec![ImageDesc::new("tex", ImageType::Texture2D)],
+            uniforms: UniformBlockLayout {
+                uniforms: vec![UniformDesc::new("mvp", UniformType::Mat4)],
+            },
+            uniform_blocks: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(texture: usize) -> State {
+        State {
+            texture: TextureId(TextureIdInner::Managed(texture)),
+            pipeline: Pipeline(0),
+            scissor: None,
+        }
+    }
+
+    fn quad() -> [BatchVertex; 4] {
+        [BatchVertex::new(0., 0., 0., 0., [1.; 4]); 4]
+    }
+
+    #[test]
+    fn scissor_intersection() {
+        assert_eq!(
+            intersect((0, 0, 100, 100), (50, 20, 100, 100)),
+            (50, 20, 50, 80)
+        );
+        assert_eq!(
+            intersect((10, 10, 20, 20), (0, 0, 100, 100)),
+            (10, 10, 20, 20)
+        );
+        // disjoint rectangles are empty
+        assert_eq!(intersect((0, 0, 10, 10), (20, 0, 10, 10)), (20, 0, 0, 10));
+    }
+
+    #[test]
+    fn scissor_clamped_to_target() {
+        assert_eq!(target_scissor(None, 800, 600), (0, 0, 800, 600));
+        assert_eq!(
+            target_scissor(Some((10, 20, 30, 40)), 800, 600),
+            (10, 540, 30, 40)
+        );
+        assert_eq!(
+            target_scissor(Some((-10, -10, 20, 20)), 800, 600),
+            (0, 590, 10, 10)
+        );
+        assert_eq!(
+            target_scissor(Some((700, 500, 200, 200)), 800, 600),
+            (700, 0, 100, 100)
+        );
+        // completely outside, still no negative coordinates
+        assert_eq!(
+            target_scissor(Some((900, 700, 10, 10)), 800, 600),
+            (800, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn chunk_splitting() {
+        let mut recording = Recording::new(8, 12);
+        for _ in 0..3 {
+            recording.push(state(0), 0., &quad(), &[0, 1, 2, 0, 2, 3]);
+        }
+        // the first two quads merge, the third one does not fit
+        assert_eq!(recording.primitives.len(), 2);
+        assert_eq!(&recording.indices[6..12], &[4, 5, 6, 4, 6, 7]);
+
+        let mut order = vec![];
+        recording.order(false, &mut order);
+        let mut chunks = vec![];
+        recording.chunks(&order, &mut Chunk::default(), |chunk| {
+            chunks.push((
+                chunk.runs.len(),
+                chunk.vertices.len(),
+                chunk.indices.clone(),
+            ))
+        });
+        assert_eq!(
+            chunks,
+            vec![
+                (1, 8, vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7]),
+                (1, 4, vec![0, 1, 2, 0, 2, 3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn scissor_in_two_flushes() {
+        let scissored = State {
+            scissor: Some((0, 0, 10, 10)),
+            ..state(0)
+        };
+        // the scissor rectangles each flush applies, the same way `flush` does
+        let flush = |states: &[State]| {
+            let mut recording = Recording::new(64, 64);
+            for state in states {
+                recording.push(*state, 0., &quad(), &[0, 1, 2]);
+            }
+            let mut order = vec![];
+            recording.order(false, &mut order);
+            let mut scissor = None;
+            let mut applied = vec![];
+            recording.chunks(&order, &mut Chunk::default(), |chunk| {
+                for run in &chunk.runs {
+                    if scissor_changed(&mut scissor, run.state.scissor) {
+                        applied.push(run.state.scissor);
+                    }
+                }
+            });
+            applied
+        };
+
+        assert_eq!(
+            flush(&[state(0), scissored]),
+            vec![None, Some((0, 0, 10, 10))]
+        );
+        // the first flush left its rectangle applied, the second one has to reset it
+        assert_eq!(flush(&[state(0), state(1)]), vec![None]);
+    }
+
+    #[test]
+    fn z_sort_is_stable() {
+        let mut recording = Recording::new(64, 64);
+        for (texture, z) in [(0, 1.), (1, 0.), (2, 1.), (3, 0.)] {
+            recording.push(state(texture), z, &quad(), &[0, 1, 2]);
+        }
+        let mut order = vec![];
+        recording.order(true, &mut order);
+        assert_eq!(order, vec![1, 3, 0, 2]);
+        recording.order(false, &mut order);
+        assert_eq!(order, vec![0, 1, 2, 3]);
+    }
+}
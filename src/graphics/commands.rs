@@ -0,0 +1,192 @@
+use super::*;
+
+#[derive(Clone, Debug)]
+enum Command {
+    ApplyPipeline(Pipeline),
+    ApplyBindings {
+        vertex_buffers: (usize, usize),
+        index_buffer: BufferId,
+        images: (usize, usize),
+    },
+    ApplyUniforms(usize, usize),
+    ApplyUniformBlock(usize, usize, usize),
+    ApplyViewport(i32, i32, i32, i32),
+    ApplyScissorRect(i32, i32, i32, i32),
+    ApplyStencilReference(i32, i32),
+    ApplyBlendColor(f32, f32, f32, f32),
+    Draw {
+        base_element: i32,
+        num_elements: i32,
+        num_instances: i32,
+        base_instance: i32,
+    },
+}
+
+/// Pipeline, bindings, uniforms and draw calls recorded on any thread,
+/// to be executed later with `RenderingBackend::execute_commands`.
+///
+/// Methods mirror the ones on `RenderingBackend`, uniforms are copied at the
+/// time of recording. Nothing is validated until execution.
+///
+/// ```ignore
+/// let mut commands = CommandBuffer::new();
+/// commands.apply_pipeline(&pipeline);
+/// commands.apply_bindings(&bindings);
+/// commands.apply_uniforms(UniformsSource::table(&uniforms));
+/// commands.draw(0, 6, 1);
+///
+/// // back on the rendering thread
+/// ctx.begin_default_pass(PassAction::Nothing);
+/// ctx.execute_commands(&commands);
+/// ctx.end_render_pass();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CommandBuffer {
+    commands: Vec<Command>,
+    // storage for the variable sized command arguments, to keep
+    // the number of allocations independent of the number of commands
+    buffers: Vec<BufferId>,
+    images: Vec<TextureId>,
+    uniforms: Vec<u8>,
+}
+
+impl CommandBuffer {
+    pub fn new() -> CommandBuffer {
+        CommandBuffer::default()
+    }
+
+    /// Remove all the recorded commands, keeping the allocated memory.
+    pub fn clear(&mut self) {
+        self.commands.clear();
+        self.buffers.clear();
+        self.images.clear();
+        self.uniforms.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn apply_pipeline(&mut self, pipeline: &Pipeline) {
+        self.commands.push(Command::ApplyPipeline(*pipeline));
+    }
+
+    pub fn apply_bindings_from_slice(
+        &mut self,
+        vertex_buffers: &[BufferId],
+        index_buffer: BufferId,
+        textures: &[TextureId],
+    ) {
+        let buffers_start = self.buffers.len();
+        self.buffers.extend_from_slice(vertex_buffers);
+        let images_start = self.images.len();
+        self.images.extend_from_slice(textures);
+        self.commands.push(Command::ApplyBindings {
+            vertex_buffers: (buffers_start, self.buffers.len()),
+            index_buffer,
+            images: (images_start, self.images.len()),
+        });
+    }
+
+    pub fn apply_bindings(&mut self, bindings: &Bindings) {
+        self.apply_bindings_from_slice(
+            &bindings.vertex_buffers,
+            bindings.index_buffer,
+            &bindings.images,
+        );
+    }
+
+    pub fn apply_uniforms(&mut self, uniforms: UniformsSource) {
+        let (start, end) = self.push_uniforms(uniforms);
+        self.commands.push(Command::ApplyUniforms(start, end));
+    }
+
+    pub fn apply_uniform_block(&mut self, block: usize, uniforms: UniformsSource) {
+        let (start, end) = self.push_uniforms(uniforms);
+        self.commands
+            .push(Command::ApplyUniformBlock(block, start, end));
+    }
+
+    fn push_uniforms(&mut self, uniforms: UniformsSource) -> (usize, usize) {
+        let start = self.uniforms.len();
+        let bytes =
+            unsafe { std::slice::from_raw_parts(uniforms.0.ptr as *const u8, uniforms.0.size) };
+        self.uniforms.extend_from_slice(bytes);
+        (start, self.uniforms.len())
+    }
+
+    pub fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.commands.push(Command::ApplyViewport(x, y, w, h));
+    }
+
+    pub fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.commands.push(Command::ApplyScissorRect(x, y, w, h));
+    }
+
+    pub fn apply_stencil_reference(&mut self, front: i32, back: i32) {
+        self.commands
+            .push(Command::ApplyStencilReference(front, back));
+    }
+
+    pub fn apply_blend_color(&mut self, r: f32, g: f32, b: f32, a: f32) {
+        self.commands.push(Command::ApplyBlendColor(r, g, b, a));
+    }
+
+    pub fn draw(&mut self, base_element: i32, num_elements: i32, num_instances: i32) {
+        self.draw_base_instance(base_element, num_elements, num_instances, 0);
+    }
+
+    pub fn draw_base_instance(
+        &mut self,
+        base_element: i32,
+        num_elements: i32,
+        num_instances: i32,
+        base_instance: i32,
+    ) {
+        self.commands.push(Command::Draw {
+            base_element,
+            num_elements,
+            num_instances,
+            base_instance,
+        });
+    }
+
+    pub(crate) fn execute<B: RenderingBackend + ?Sized>(&self, ctx: &mut B) {
+        for command in &self.commands {
+            match *command {
+                Command::ApplyPipeline(pipeline) => ctx.apply_pipeline(&pipeline),
+                Command::ApplyBindings {
+                    vertex_buffers,
+                    index_buffer,
+                    images,
+                } => ctx.apply_bindings_from_slice(
+                    &self.buffers[vertex_buffers.0..vertex_buffers.1],
+                    index_buffer,
+                    &self.images[images.0..images.1],
+                ),
+                Command::ApplyUniforms(start, end) => {
+                    let uniforms = &self.uniforms[start..end];
+                    ctx.apply_uniforms_from_bytes(uniforms.as_ptr(), uniforms.len())
+                }
+                Command::ApplyUniformBlock(block, start, end) => {
+                    let uniforms = &self.uniforms[start..end];
+                    ctx.apply_uniform_block_from_bytes(block, uniforms.as_ptr(), uniforms.len())
+                }
+                Command::ApplyViewport(x, y, w, h) => ctx.apply_viewport(x, y, w, h),
+                Command::ApplyScissorRect(x, y, w, h) => ctx.apply_scissor_rect(x, y, w, h),
+                Command::ApplyStencilReference(front, back) => {
+                    ctx.apply_stencil_reference(front, back)
+                }
+                Command::ApplyBlendColor(r, g, b, a) => ctx.apply_blend_color(r, g, b, a),
+                Command::Draw {
+                    base_element,
+                    num_elements,
+                    num_instances,
+                    base_instance,
+                } => {
+                    ctx.draw_base_instance(base_element, num_elements, num_instances, base_instance)
+                }
+            }
+        }
+    }
+}
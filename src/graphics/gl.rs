@@ -1,11 +1,15 @@
-use std::ffi::CString;
+use std::{cell::Cell, collections::HashMap, ffi::CString};
 
 use crate::{window, ResourceManager};
 
 mod cache;
+mod loader;
+mod recovery;
 
 use super::*;
 use cache::*;
+pub use loader::{LoadId, Loaded, Loader};
+use recovery::*;
 
 /// Raw OpenGL bindings
 /// Highly unsafe, some of the functions could be missing due to incompatible GL version
@@ -42,7 +46,9 @@ struct ShaderUniform {
 struct ShaderInternal {
     program: GLuint,
     images: Vec<ShaderImage>,
+    image_descs: Vec<ImageDesc>,
     uniforms: Vec<ShaderUniform>,
+    uniform_blocks: Vec<Vec<ShaderUniform>>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -69,6 +75,8 @@ impl TextureOrRenderbuffer {
 struct Texture {
     raw: TextureOrRenderbuffer,
     params: TextureParams,
+    // GL_TEXTURE_COMPARE_MODE, set while bound to ImageType::Shadow images
+    compare: bool,
 }
 
 impl TextureFormat {
@@ -83,6 +91,8 @@ impl TextureFormat {
             TextureFormat::Alpha => GL_ALPHA,
             #[cfg(not(target_arch = "wasm32"))]
             TextureFormat::Alpha => GL_R8,
+            TextureFormat::SRGB8 => GL_SRGB8,
+            TextureFormat::SRGBA8 => GL_SRGB8_ALPHA8,
         }
     }
 }
@@ -100,6 +110,9 @@ impl From<TextureFormat> for (GLenum, GLenum, GLenum) {
             TextureFormat::Alpha => (GL_ALPHA, GL_ALPHA, GL_UNSIGNED_BYTE),
             #[cfg(not(target_arch = "wasm32"))]
             TextureFormat::Alpha => (GL_R8, GL_RED, GL_UNSIGNED_BYTE), // texture updates will swizzle Red -> Alpha to match WASM
+            // no unsized sRGB formats outside of GLES2 extensions
+            TextureFormat::SRGB8 => (GL_SRGB8, GL_RGB, GL_UNSIGNED_BYTE),
+            TextureFormat::SRGBA8 => (GL_SRGB8_ALPHA8, GL_RGBA, GL_UNSIGNED_BYTE),
         }
     }
 }
@@ -118,6 +131,8 @@ impl From<Equation> for GLenum {
             Equation::Add => GL_FUNC_ADD,
             Equation::Subtract => GL_FUNC_SUBTRACT,
             Equation::ReverseSubtract => GL_FUNC_REVERSE_SUBTRACT,
+            Equation::Min => GL_MIN,
+            Equation::Max => GL_MAX,
         }
     }
 }
@@ -131,10 +146,14 @@ impl From<BlendFactor> for GLenum {
             BlendFactor::Value(BlendValue::SourceAlpha) => GL_SRC_ALPHA,
             BlendFactor::Value(BlendValue::DestinationColor) => GL_DST_COLOR,
             BlendFactor::Value(BlendValue::DestinationAlpha) => GL_DST_ALPHA,
+            BlendFactor::Value(BlendValue::ConstantColor) => GL_CONSTANT_COLOR,
+            BlendFactor::Value(BlendValue::ConstantAlpha) => GL_CONSTANT_ALPHA,
             BlendFactor::OneMinusValue(BlendValue::SourceColor) => GL_ONE_MINUS_SRC_COLOR,
             BlendFactor::OneMinusValue(BlendValue::SourceAlpha) => GL_ONE_MINUS_SRC_ALPHA,
             BlendFactor::OneMinusValue(BlendValue::DestinationColor) => GL_ONE_MINUS_DST_COLOR,
             BlendFactor::OneMinusValue(BlendValue::DestinationAlpha) => GL_ONE_MINUS_DST_ALPHA,
+            BlendFactor::OneMinusValue(BlendValue::ConstantColor) => GL_ONE_MINUS_CONSTANT_COLOR,
+            BlendFactor::OneMinusValue(BlendValue::ConstantAlpha) => GL_ONE_MINUS_CONSTANT_ALPHA,
             BlendFactor::SourceAlphaSaturate => GL_SRC_ALPHA_SATURATE,
         }
     }
@@ -176,6 +195,22 @@ impl Texture {
         access: TextureAccess,
         source: TextureSource,
         params: TextureParams,
+    ) -> Texture {
+        ctx.cache.store_texture_binding(0);
+        let texture = Self::create(access, source, params, |target, texture| {
+            ctx.cache.bind_texture(0, target, texture)
+        });
+        ctx.cache.restore_texture_binding(0);
+        texture
+    }
+
+    /// Same as `new`, but binds the texture through `bind` instead of GlCache,
+    /// so it works on contexts without one, like Loader's.
+    fn create(
+        access: TextureAccess,
+        source: TextureSource,
+        params: TextureParams,
+        mut bind: impl FnMut(GLuint, GLuint),
     ) -> Texture {
         if let TextureSource::Bytes(bytes_data) = source {
             assert_eq!(
@@ -208,16 +243,15 @@ impl Texture {
             return Texture {
                 raw: TextureOrRenderbuffer::Renderbuffer(renderbuffer),
                 params,
+                compare: false,
             };
         }
 
-        ctx.cache.store_texture_binding(0);
-
         let mut texture: GLuint = 0;
 
         unsafe {
             glGenTextures(1, &mut texture as *mut _);
-            ctx.cache.bind_texture(0, params.kind.into(), texture);
+            bind(params.kind.into(), texture);
             glPixelStorei(GL_UNPACK_ALIGNMENT, 1); // miniquad always uses row alignment of 1
 
             if cfg!(not(target_arch = "wasm32")) {
@@ -236,17 +270,29 @@ impl Texture {
                 TextureSource::Empty => {
                     // not quite sure if glTexImage2D(null) is really a requirement
                     // but it was like this for quite a while and apparantly it works?
-                    glTexImage2D(
-                        GL_TEXTURE_2D,
-                        0,
-                        internal_format as i32,
-                        params.width as i32,
-                        params.height as i32,
-                        0,
-                        format,
-                        pixel_type,
-                        std::ptr::null() as _,
-                    );
+                    // Every cubemap face and mip level is allocated, so any of them
+                    // may be a render pass attachment.
+                    let targets = match params.kind {
+                        TextureKind::Texture2D => GL_TEXTURE_2D..GL_TEXTURE_2D + 1,
+                        TextureKind::CubeMap => {
+                            GL_TEXTURE_CUBE_MAP_POSITIVE_X..GL_TEXTURE_CUBE_MAP_POSITIVE_X + 6
+                        }
+                    };
+                    for target in targets {
+                        for level in 0..params.mip_levels() {
+                            glTexImage2D(
+                                target,
+                                level as _,
+                                internal_format as i32,
+                                (params.width >> level).max(1) as i32,
+                                (params.height >> level).max(1) as i32,
+                                0,
+                                format,
+                                pixel_type,
+                                std::ptr::null() as _,
+                            );
+                        }
+                    }
                 }
                 TextureSource::Bytes(source) => {
                     assert!(params.kind == TextureKind::Texture2D, "incompatible TextureKind and TextureSource. Cubemaps require TextureSource::Array of 6 textures.");
@@ -314,11 +360,11 @@ impl Texture {
             glTexParameteri(params.kind.into(), GL_TEXTURE_MIN_FILTER, min_filter as i32);
             glTexParameteri(params.kind.into(), GL_TEXTURE_MAG_FILTER, mag_filter as i32);
         }
-        ctx.cache.restore_texture_binding(0);
 
         Texture {
             raw: TextureOrRenderbuffer::Texture(texture),
             params,
+            compare: false,
         }
     }
 
@@ -355,6 +401,9 @@ impl Texture {
         ctx.cache.restore_texture_binding(0);
     }
 
+    /// `upload` may only have `row_length` and `bgra` set, the rest is done on the CPU
+    /// by the caller. `bgra` requires desktop GL, `row_length` GLES 3 or WebGL 2.
+    #[allow(clippy::too_many_arguments)]
     pub fn update_texture_part(
         &self,
         ctx: &mut GlContext,
@@ -362,9 +411,14 @@ impl Texture {
         y_offset: i32,
         width: i32,
         height: i32,
+        upload: TextureUpload,
         source: &[u8],
     ) {
-        assert_eq!(self.size(width as _, height as _), source.len());
+        debug_assert!(!upload.flip_y && !upload.premultiply_alpha);
+        assert_eq!(
+            upload.source_size(self.params.format, width as _, height as _),
+            source.len()
+        );
         assert!(x_offset + width <= self.params.width as _);
         assert!(y_offset + height <= self.params.height as _);
         let raw = self.raw.texture().expect(
@@ -375,9 +429,13 @@ impl Texture {
         ctx.cache.bind_texture(0, self.params.kind.into(), raw);
 
         let (_, format, pixel_type) = self.params.format.into();
+        let format = if upload.bgra { GL_BGRA } else { format };
 
         unsafe {
             glPixelStorei(GL_UNPACK_ALIGNMENT, 1); // miniquad always uses row alignment of 1
+            if upload.row_length != 0 {
+                glPixelStorei(GL_UNPACK_ROW_LENGTH, upload.row_length as _);
+            }
 
             if cfg!(not(target_arch = "wasm32")) {
                 // if not WASM
@@ -402,19 +460,63 @@ impl Texture {
                 pixel_type,
                 source.as_ptr() as *const _,
             );
+            if upload.row_length != 0 {
+                glPixelStorei(GL_UNPACK_ROW_LENGTH, 0);
+            }
         }
 
         ctx.cache.restore_texture_binding(0);
     }
 
     /// Read texture data into CPU memory
-    pub fn read_pixels(&self, bytes: &mut [u8]) {
+    /// Pixels are written in the same layout `TextureFormat` uploads use,
+    /// `TextureFormat::size(width, height)` bytes, rows from the bottom up.
+    #[allow(clippy::too_many_arguments)]
+    pub fn read_pixels_region(
+        &self,
+        desktop_gl: bool,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        mip_level: u32,
+        bytes: &mut [u8],
+    ) {
         let raw = self
             .raw
             .texture()
             .expect("read_pixels not yet implemented for RenderBuffer(multisampled) textures");
+        assert!(
+            self.params.kind == TextureKind::Texture2D,
+            "read_pixels not yet implemented for cubemaps"
+        );
+        assert!(
+            mip_level < self.params.mip_levels(),
+            "read_pixels at mip level {}, but the texture has {} levels",
+            mip_level,
+            self.params.mip_levels()
+        );
+        let level_width = (self.params.width >> mip_level).max(1) as i32;
+        let level_height = (self.params.height >> mip_level).max(1) as i32;
+        assert!(
+            x >= 0 && y >= 0 && width >= 0 && height >= 0,
+            "negative read_pixels region"
+        );
+        assert!(
+            x + width <= level_width && y + height <= level_height,
+            "read_pixels region outside of the {}x{} texture",
+            level_width,
+            level_height
+        );
+        assert_eq!(self.size(width as _, height as _), bytes.len());
 
-        let (_, format, pixel_type) = self.params.format.into();
+        let format = self.params.format;
+        let depth = matches!(format, TextureFormat::Depth | TextureFormat::Depth32);
+        assert!(
+            !depth || desktop_gl,
+            "reading depth textures is only supported on desktop GL"
+        );
+        let pixels = width as usize * height as usize;
 
         let mut fbo = 0;
         unsafe {
@@ -424,21 +526,76 @@ impl Texture {
             glBindFramebuffer(gl::GL_FRAMEBUFFER, fbo);
             glFramebufferTexture2D(
                 gl::GL_FRAMEBUFFER,
-                gl::GL_COLOR_ATTACHMENT0,
+                if depth {
+                    GL_DEPTH_ATTACHMENT
+                } else {
+                    gl::GL_COLOR_ATTACHMENT0
+                },
                 gl::GL_TEXTURE_2D,
                 raw,
-                0,
-            );
-
-            glReadPixels(
-                0,
-                0,
-                self.params.width as _,
-                self.params.height as _,
-                format,
-                pixel_type,
-                bytes.as_mut_ptr() as _,
+                mip_level as _,
             );
+            if depth {
+                // a framebuffer without color attachments is incomplete otherwise on GL < 4.1
+                glDrawBuffers(1, &GL_NONE as *const _);
+                glReadBuffer(GL_NONE);
+            }
+            glPixelStorei(GL_PACK_ALIGNMENT, 1);
+
+            // GLES and WebGL only guarantee GL_RGBA/GL_UNSIGNED_BYTE for 8-bit formats,
+            // the other channels are dropped here
+            match format {
+                // sRGB values are read back as stored, without decoding
+                TextureFormat::RGBA8 | TextureFormat::SRGBA8 => {
+                    read_pixels_into(x, y, width, height, GL_RGBA, GL_UNSIGNED_BYTE, bytes)
+                }
+                TextureFormat::RGB8 | TextureFormat::SRGB8 | TextureFormat::Alpha => {
+                    let mut rgba = vec![0u8; pixels * 4];
+                    read_pixels_into(x, y, width, height, GL_RGBA, GL_UNSIGNED_BYTE, &mut rgba);
+                    if format != TextureFormat::Alpha {
+                        for (rgb, rgba) in bytes.chunks_exact_mut(3).zip(rgba.chunks_exact(4)) {
+                            rgb.copy_from_slice(&rgba[..3]);
+                        }
+                    } else {
+                        // Alpha textures are stored in the red channel, except on WebGL
+                        let channel = if cfg!(target_arch = "wasm32") { 3 } else { 0 };
+                        for (alpha, rgba) in bytes.iter_mut().zip(rgba.chunks_exact(4)) {
+                            *alpha = rgba[channel];
+                        }
+                    }
+                }
+                TextureFormat::RGBA16F => {
+                    let mut halfs = vec![0u16; pixels * 4];
+                    read_pixels_into(x, y, width, height, GL_RGBA, GL_HALF_FLOAT, &mut halfs);
+                    copy_as_bytes(&halfs, bytes);
+                }
+                TextureFormat::Depth => {
+                    let mut depths = vec![0u16; pixels];
+                    read_pixels_into(
+                        x,
+                        y,
+                        width,
+                        height,
+                        GL_DEPTH_COMPONENT,
+                        GL_UNSIGNED_SHORT,
+                        &mut depths,
+                    );
+                    copy_as_bytes(&depths, bytes);
+                }
+                TextureFormat::Depth32 => {
+                    let mut depths = vec![0f32; pixels];
+                    read_pixels_into(
+                        x,
+                        y,
+                        width,
+                        height,
+                        GL_DEPTH_COMPONENT,
+                        GL_FLOAT,
+                        &mut depths,
+                    );
+                    copy_as_bytes(&depths, bytes);
+                }
+            }
 
             glBindFramebuffer(gl::GL_FRAMEBUFFER, binded_fbo as _);
             glDeleteFramebuffers(1, &fbo);
@@ -492,10 +649,15 @@ fn get_uniform_location(program: GLuint, name: &str) -> Option<i32> {
 pub(crate) struct RenderPassInternal {
     gl_fb: GLuint,
     color_textures: Vec<TextureId>,
-    resolves: Option<Vec<(u32, TextureId)>>,
+    resolves: Option<Vec<(u32, Attachment)>>,
     depth_texture: Option<TextureId>,
+    /// Mip level of the first attachment, the one the viewport is sized after.
+    mip_level: u32,
 }
 
+// VAO with the vertex buffers it was created for
+type VertexArray = (Vec<BufferId>, GLuint);
+
 struct Textures(Vec<Texture>);
 impl Textures {
     fn get(&self, texture: TextureId) -> Texture {
@@ -503,6 +665,7 @@ impl Textures {
             TextureIdInner::Raw(RawId::OpenGl(texture)) => Texture {
                 raw: TextureOrRenderbuffer::Texture(texture),
                 params: Default::default(),
+                compare: false,
             },
             #[cfg(target_vendor = "apple")]
             TextureIdInner::Raw(RawId::Metal(..)) => panic!("Metal texture in OpenGL context!"),
@@ -519,6 +682,24 @@ pub struct GlContext {
     default_framebuffer: GLuint,
     pub(crate) cache: GlCache,
     pub(crate) info: ContextInfo,
+    resources: ResourceStats,
+    last_frame_stats: FrameStats,
+    // draw takes &self, so draw calls are counted outside of the cache
+    draw_calls: Cell<usize>,
+    // CPU copies of all the resources, only with conf::Platform::context_loss_recovery
+    recovery: Option<ContextShadow>,
+    // VAO for each (pipeline, index buffer, vertex buffers) combination used in apply_bindings.
+    // None on GL2/WebGL1, attributes are set up on each apply_bindings there.
+    vertex_arrays: Option<HashMap<(Pipeline, BufferId), Vec<VertexArray>>>,
+    // number of output buffers bound by begin_feedback_pass, None outside of feedback passes
+    feedback_outputs: Option<usize>,
+    // resources dropped by the resources module wrappers, deleted in commit_frame
+    deletion_queue: resources::DeletionQueue,
+    // created when the overlay is shown, dropped when it is hidden
+    debug_overlay: Option<overlay::DebugOverlay>,
+    // GL_FRAMEBUFFER_SRGB can be toggled: GL 3.0, ARB/EXT_framebuffer_sRGB
+    // or GLES with EXT_sRGB_write_control
+    srgb_write_control: bool,
 }
 
 impl Default for GlContext {
@@ -539,7 +720,22 @@ impl GlContext {
 
             glGenVertexArrays(1, &mut vao as *mut _);
             glBindVertexArray(vao);
-            let info = gl_info();
+            let mut info = gl_info();
+            // VAOs are core since GL3/GLES3/WebGL2
+            let vao_cache = !is_gl2() && info.gl_version_string != "WebGL 1.0";
+            let recovery = {
+                let d = crate::native_display().lock().unwrap();
+                info.srgb_framebuffer = d.srgb_framebuffer;
+                d.context_loss_recovery
+                    .then(|| ContextShadow::new(d.context_generation))
+            };
+            let srgb_write_control = if info.gl_context.gles {
+                info.has_extension("EXT_sRGB_write_control")
+            } else {
+                info.gl_context.version.0 >= 3
+                    || info.has_extension("ARB_framebuffer_sRGB")
+                    || info.has_extension("EXT_framebuffer_sRGB")
+            };
             GlContext {
                 default_framebuffer,
                 shaders: ResourceManager::default(),
@@ -548,28 +744,20 @@ impl GlContext {
                 buffers: ResourceManager::default(),
                 textures: Textures(vec![]),
                 info,
+                resources: ResourceStats::default(),
+                last_frame_stats: FrameStats::default(),
+                draw_calls: Cell::new(0),
                 cache: GlCache {
-                    stored_index_buffer: 0,
-                    stored_index_type: None,
-                    stored_vertex_buffer: 0,
-                    index_buffer: 0,
-                    index_type: None,
-                    vertex_buffer: 0,
-                    cur_pipeline: None,
-                    cur_pass: None,
-                    color_blend: None,
-                    alpha_blend: None,
-                    stencil: None,
-                    color_write: (true, true, true, true),
-                    cull_face: CullFace::Nothing,
-                    stored_texture: 0,
-                    stored_target: 0,
-                    textures: [CachedTexture {
-                        target: 0,
-                        texture: 0,
-                    }; MAX_SHADERSTAGE_IMAGES],
-                    attributes: [None; MAX_VERTEX_ATTRIBUTES],
+                    vertex_array: vao,
+                    default_vertex_array: vao,
+                    ..GlCache::default()
                 },
+                recovery,
+                vertex_arrays: vao_cache.then(HashMap::new),
+                feedback_outputs: None,
+                deletion_queue: Default::default(),
+                debug_overlay: None,
+                srgb_write_control,
             }
         }
     }
@@ -577,12 +765,134 @@ impl GlContext {
     pub fn features(&self) -> &Features {
         &self.info.features
     }
+
+    /// Should be called before GL calls made outside of miniquad, like third party
+    /// renderers embedded into the same context. Until `end_external` any GL state
+    /// may be freely changed.
+    pub fn begin_external(&mut self) {
+        // foreign vertex attribute calls should not end up in one of the cached VAOs
+        self.cache.bind_default_vertex_array();
+    }
+
+    /// Bring the GL state back to what GlContext expects after `begin_external`.
+    ///
+    /// Everything GlCache tracks, the pixel store parameters and the transform feedback
+    /// binding are reset, and the current pass with its sRGB encoding, pipeline, viewport,
+    /// scissor rect, stencil reference and blend color are applied again.
+    /// Bindings are not restored, `apply_bindings` is required before the next draw.
+    pub fn end_external(&mut self) {
+        let cur_pass = self.cache.cur_pass;
+        let cur_pipeline = self.cache.cur_pipeline;
+        let viewport = self.cache.viewport;
+        let scissor = self.cache.scissor;
+        let scissor_test = self.cache.scissor_test;
+        let stencil_ref = self.cache.stencil_ref;
+        let blend_color = self.cache.blend_color;
+
+        unsafe {
+            self.reset_gl_state();
+        }
+        self.cache = GlCache {
+            vertex_array: self.cache.default_vertex_array,
+            default_vertex_array: self.cache.default_vertex_array,
+            cur_pass,
+            stats: self.cache.stats,
+            ..GlCache::default()
+        };
+
+        let framebuffer = match cur_pass {
+            Some(pass) => self.passes[pass.0].gl_fb,
+            None => self.default_framebuffer,
+        };
+        unsafe {
+            glBindFramebuffer(GL_FRAMEBUFFER, framebuffer);
+        }
+        self.apply_framebuffer_srgb(cur_pass);
+        if let Some(pipeline) = cur_pipeline {
+            self.apply_pipeline(&pipeline);
+            self.apply_stencil_reference(stencil_ref.0, stencil_ref.1);
+        }
+        if let Some((x, y, w, h)) = viewport {
+            self.apply_viewport(x, y, w, h);
+        }
+        if let Some((x, y, w, h)) = scissor {
+            self.apply_scissor_rect(x, y, w, h);
+        }
+        self.set_scissor_test(scissor_test);
+        let (r, g, b, a) = blend_color;
+        self.apply_blend_color(r, g, b, a);
+    }
+}
+
+fn shader_uniforms(program: GLuint, layout: &UniformBlockLayout) -> Vec<ShaderUniform> {
+    layout
+        .uniforms
+        .iter()
+        .map(|uniform| ShaderUniform {
+            gl_loc: get_uniform_location(program, &uniform.name),
+            uniform_type: uniform.uniform_type,
+            array_count: uniform.array_count as _,
+        })
+        .collect()
+}
+
+/// Upload a packed uniforms struct, laid out after `uniforms`.
+fn apply_shader_uniforms(uniforms: &[ShaderUniform], uniform_ptr: *const u8, size: usize) {
+    let mut offset = 0;
+
+    for uniform in uniforms.iter() {
+        use UniformType::*;
+
+        assert!(
+            offset as i32 <= size as i32 - uniform.uniform_type.size() as i32 / 4,
+            "Uniforms struct does not match shader uniforms layout"
+        );
+
+        unsafe {
+            let data = (uniform_ptr as *const f32).add(offset);
+            let data_int = (uniform_ptr as *const i32).add(offset);
+
+            if let Some(gl_loc) = uniform.gl_loc {
+                match uniform.uniform_type {
+                    Float1 => {
+                        glUniform1fv(gl_loc, uniform.array_count, data);
+                    }
+                    Float2 => {
+                        glUniform2fv(gl_loc, uniform.array_count, data);
+                    }
+                    Float3 => {
+                        glUniform3fv(gl_loc, uniform.array_count, data);
+                    }
+                    Float4 => {
+                        glUniform4fv(gl_loc, uniform.array_count, data);
+                    }
+                    Int1 => {
+                        glUniform1iv(gl_loc, uniform.array_count, data_int);
+                    }
+                    Int2 => {
+                        glUniform2iv(gl_loc, uniform.array_count, data_int);
+                    }
+                    Int3 => {
+                        glUniform3iv(gl_loc, uniform.array_count, data_int);
+                    }
+                    Int4 => {
+                        glUniform4iv(gl_loc, uniform.array_count, data_int);
+                    }
+                    Mat4 => {
+                        glUniformMatrix4fv(gl_loc, uniform.array_count, 0, data);
+                    }
+                }
+            }
+        }
+        offset += uniform.uniform_type.size() / 4 * uniform.array_count as usize;
+    }
 }
 
 fn load_shader_internal(
     vertex_shader: &str,
     fragment_shader: &str,
     meta: ShaderMeta,
+    varyings: &[&str],
 ) -> Result<ShaderInternal, ShaderError> {
     unsafe {
         let vertex_shader = load_shader(GL_VERTEX_SHADER, vertex_shader)?;
@@ -591,6 +901,19 @@ fn load_shader_internal(
         let program = glCreateProgram();
         glAttachShader(program, vertex_shader);
         glAttachShader(program, fragment_shader);
+        if !varyings.is_empty() {
+            let names: Vec<CString> = varyings
+                .iter()
+                .map(|name| CString::new(*name).unwrap_or_else(|e| panic!("{}", e)))
+                .collect();
+            let names: Vec<*const GLchar> = names.iter().map(|name| name.as_ptr()).collect();
+            glTransformFeedbackVaryings(
+                program,
+                names.len() as _,
+                names.as_ptr(),
+                GL_SEPARATE_ATTRIBS,
+            );
+        }
         glLinkProgram(program);
 
         // delete no longer used shaders
@@ -620,25 +943,28 @@ fn load_shader_internal(
         glUseProgram(program);
 
         #[rustfmt::skip]
-        let images = meta.images.iter().map(|name| ShaderImage {
-            gl_loc: get_uniform_location(program, name),
+        let images = meta.images.iter().map(|image| ShaderImage {
+            gl_loc: get_uniform_location(program, &image.name),
         }).collect();
 
-        #[rustfmt::skip]
-        let uniforms = meta.uniforms.uniforms.iter().scan(0, |offset, uniform| {
-            let res = ShaderUniform {
-                gl_loc: get_uniform_location(program, &uniform.name),
-                uniform_type: uniform.uniform_type,
-                array_count: uniform.array_count as _,
-            };
-            *offset += uniform.uniform_type.size() * uniform.array_count;
-            Some(res)
-        }).collect();
+        assert!(
+            meta.uniform_blocks.len() <= MAX_UNIFORM_BLOCKS,
+            "at most {} uniform blocks are supported",
+            MAX_UNIFORM_BLOCKS
+        );
+        let uniforms = shader_uniforms(program, &meta.uniforms);
+        let uniform_blocks = meta
+            .uniform_blocks
+            .iter()
+            .map(|block| shader_uniforms(program, &block.layout))
+            .collect();
 
         Ok(ShaderInternal {
             program,
             images,
+            image_descs: meta.images,
             uniforms,
+            uniform_blocks,
         })
     }
 }
@@ -691,55 +1017,364 @@ pub fn load_shader(shader_type: GLenum, source: &str) -> Result<GLuint, ShaderEr
     }
 }
 
+/// glReadPixels into a buffer of the matching element type, to keep it aligned.
+unsafe fn read_pixels_into<T>(
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    format: GLenum,
+    pixel_type: GLenum,
+    pixels: &mut [T],
+) {
+    glReadPixels(
+        x,
+        y,
+        width,
+        height,
+        format,
+        pixel_type,
+        pixels.as_mut_ptr() as _,
+    );
+}
+
+fn copy_as_bytes<T: Copy>(source: &[T], bytes: &mut [u8]) {
+    let source = unsafe {
+        std::slice::from_raw_parts(source.as_ptr() as *const u8, std::mem::size_of_val(source))
+    };
+    bytes.copy_from_slice(source);
+}
+
+unsafe fn enable_vertex_attribute(
+    attr_index: usize,
+    attribute: &VertexAttributeInternal,
+    instancing: bool,
+) {
+    match attribute.type_ {
+        GL_INT | GL_UNSIGNED_INT | GL_SHORT | GL_UNSIGNED_SHORT | GL_UNSIGNED_BYTE | GL_BYTE
+            if !attribute.gl_pass_as_float && !attribute.normalized =>
+        {
+            glVertexAttribIPointer(
+                attr_index as GLuint,
+                attribute.size,
+                attribute.type_,
+                attribute.stride,
+                attribute.offset as *mut _,
+            )
+        }
+        _ => glVertexAttribPointer(
+            attr_index as GLuint,
+            attribute.size,
+            attribute.type_,
+            attribute.normalized as u8,
+            attribute.stride,
+            attribute.offset as *mut _,
+        ),
+    }
+    if instancing {
+        glVertexAttribDivisor(attr_index as GLuint, attribute.divisor as u32);
+    }
+    glEnableVertexAttribArray(attr_index as GLuint);
+}
+
 impl GlContext {
-    fn set_blend(&mut self, color_blend: Option<BlendState>, alpha_blend: Option<BlendState>) {
+    fn frame_stats(&self) -> FrameStats {
+        FrameStats {
+            draw_calls: self.draw_calls.get(),
+            ..self.cache.stats
+        }
+    }
+
+    /// Attach to the currently bound GL_FRAMEBUFFER.
+    unsafe fn framebuffer_attachment(&self, attachment_point: GLenum, attachment: Attachment) {
+        let texture = self.textures.get(attachment.texture);
+        assert!(
+            attachment.mip_level < texture.params.mip_levels(),
+            "render pass attachment at mip level {}, but the texture has {} levels",
+            attachment.mip_level,
+            texture.params.mip_levels()
+        );
+        let target = match texture.params.kind {
+            TextureKind::Texture2D => {
+                assert!(attachment.layer == 0, "2D textures only have layer 0");
+                GL_TEXTURE_2D
+            }
+            TextureKind::CubeMap => {
+                assert!(attachment.layer < 6, "cubemaps only have 6 faces");
+                GL_TEXTURE_CUBE_MAP_POSITIVE_X + attachment.layer
+            }
+        };
+        if texture.params.sample_count > 1 {
+            let raw = texture.raw.renderbuffer().unwrap();
+            glFramebufferRenderbuffer(GL_FRAMEBUFFER, attachment_point, GL_RENDERBUFFER, raw);
+        } else {
+            let raw = texture.raw.texture().unwrap();
+            glFramebufferTexture2D(
+                GL_FRAMEBUFFER,
+                attachment_point,
+                target,
+                raw,
+                attachment.mip_level as _,
+            );
+        }
+    }
+
+    /// apply_bindings for GL3+, binds a VAO with all the attributes of the current
+    /// pipeline, creating it on the first use of this buffers combination.
+    fn apply_vertex_array(&mut self, vertex_buffers: &[BufferId], index_buffer: BufferId) {
+        let pipeline = self.cache.cur_pipeline.unwrap();
+        let index = self.buffers[index_buffer.0];
+        let vertex_arrays = self
+            .vertex_arrays
+            .as_mut()
+            .unwrap()
+            .entry((pipeline, index_buffer))
+            .or_default();
+
+        if let Some((_, vao)) = vertex_arrays
+            .iter()
+            .find(|(buffers, _)| buffers[..] == *vertex_buffers)
+        {
+            self.cache
+                .bind_vertex_array(*vao, index.gl_buf, index.index_type);
+            return;
+        }
+
+        let mut vao = 0;
+        unsafe {
+            glGenVertexArrays(1, &mut vao as *mut _);
+        }
+        vertex_arrays.push((vertex_buffers.to_vec(), vao));
+        self.cache
+            .bind_vertex_array(vao, index.gl_buf, index.index_type);
+        unsafe {
+            glBindBuffer(GL_ELEMENT_ARRAY_BUFFER, index.gl_buf);
+        }
+
+        let pip = &self.pipelines[pipeline.0];
+        for (attr_index, attribute) in pip.layout.iter().enumerate() {
+            if let Some(attribute) = attribute {
+                assert!(
+                    attribute.buffer_index < vertex_buffers.len(),
+                    "Attribute index outside of vertex_buffers length"
+                );
+                let vb = self.buffers[vertex_buffers[attribute.buffer_index].0];
+                self.cache
+                    .bind_buffer(GL_ARRAY_BUFFER, vb.gl_buf, vb.index_type);
+                unsafe {
+                    enable_vertex_attribute(attr_index, attribute, self.info.features.instancing)
+                };
+            }
+        }
+    }
+
+    /// Delete the cached VAOs matching `f`.
+    fn delete_vertex_arrays(&mut self, f: impl Fn(Pipeline, BufferId, &[BufferId]) -> bool) {
+        let vertex_arrays = match &mut self.vertex_arrays {
+            Some(vertex_arrays) => vertex_arrays,
+            None => return,
+        };
+        // deleting a bound VAO would silently bind VAO 0
+        self.cache.bind_default_vertex_array();
+        for ((pipeline, index_buffer), entries) in vertex_arrays.iter_mut() {
+            entries.retain(|(vertex_buffers, vao)| {
+                if f(*pipeline, *index_buffer, vertex_buffers) {
+                    unsafe { glDeleteVertexArrays(1, vao as *const _) };
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+        vertex_arrays.retain(|_, entries| !entries.is_empty());
+    }
+
+    /// Desktop GL only encodes to sRGB attachments with GL_FRAMEBUFFER_SRGB, GLES always does
+    /// unless EXT_sRGB_write_control turned it off.
+    /// Off for the default framebuffer on desktop GL unless asked for, some drivers make it
+    /// sRGB capable anyway.
+    fn apply_framebuffer_srgb(&self, pass: Option<RenderPass>) {
+        if !self.srgb_write_control {
+            return;
+        }
+        unsafe {
+            if self.info.gl_context.gles || pass.is_some() || self.info.srgb_framebuffer {
+                glEnable(GL_FRAMEBUFFER_SRGB);
+            } else {
+                glDisable(GL_FRAMEBUFFER_SRGB);
+            }
+        }
+    }
+
+    /// Set every piece of GL state GlCache tracks to the values of `GlCache::default()`.
+    unsafe fn reset_gl_state(&self) {
+        glBindVertexArray(self.cache.default_vertex_array);
+        glBindBuffer(GL_ARRAY_BUFFER, 0);
+        glBindBuffer(GL_ELEMENT_ARRAY_BUFFER, 0);
+        for attr_index in 0..MAX_VERTEX_ATTRIBUTES {
+            glDisableVertexAttribArray(attr_index as GLuint);
+        }
+        for slot_index in 0..MAX_SHADERSTAGE_IMAGES {
+            glActiveTexture(GL_TEXTURE0 + slot_index as GLuint);
+            glBindTexture(GL_TEXTURE_2D, 0);
+            glBindTexture(GL_TEXTURE_CUBE_MAP, 0);
+        }
+        glDisable(GL_BLEND);
+        glBlendColor(0., 0., 0., 0.);
+        glDisable(GL_STENCIL_TEST);
+        glDisable(GL_SCISSOR_TEST);
+        glColorMask(1, 1, 1, 1);
+        // never changed by miniquad, but clears and depth/stencil writes depend on them
+        glDepthMask(1);
+        glStencilMask(!0);
+        glDisable(GL_DEPTH_TEST);
+        glDisable(GL_CULL_FACE);
+        glFrontFace(GL_CCW);
+        if self.info.features.polygon_mode {
+            glPolygonMode(GL_FRONT_AND_BACK, GL_FILL);
+        }
+        glLineWidth(1.0);
+        if self.info.features.depth_clamp {
+            glDisable(GL_DEPTH_CLAMP);
+        }
+        glDisable(GL_SAMPLE_ALPHA_TO_COVERAGE);
+        if self.info.features.primitive_restart && cfg!(not(target_arch = "wasm32")) {
+            glDisable(GL_PRIMITIVE_RESTART_FIXED_INDEX);
+        }
+        // uploads and read_pixels only set what differs from these
+        glPixelStorei(GL_UNPACK_ALIGNMENT, 1);
+        glPixelStorei(GL_PACK_ALIGNMENT, 1);
+        let version = &self.info.gl_version_string;
+        let gles2 = version.starts_with("OpenGL ES 2") || version == "WebGL 1.0";
+        if !gles2 {
+            glPixelStorei(GL_UNPACK_ROW_LENGTH, 0);
+        }
+        // pixel buffer objects are GL 2.1, GLES 3 and WebGL 2
+        if !gles2 && !version.starts_with("2.0") {
+            glBindBuffer(GL_PIXEL_UNPACK_BUFFER, 0);
+            glBindBuffer(GL_PIXEL_PACK_BUFFER, 0);
+        }
+        // sampler objects are GL 3.3, GLES 3 and WebGL 2
+        let desktop_gl = version.starts_with(|c: char| c.is_ascii_digit());
+        let samplers = if desktop_gl {
+            self.info.gl_context.version >= (3, 3)
+                || self.info.has_extension("GL_ARB_sampler_objects")
+        } else {
+            !gles2
+        };
+        if samplers {
+            for slot_index in 0..MAX_SHADERSTAGE_IMAGES {
+                glBindSampler(slot_index as GLuint, 0);
+            }
+        }
+        if self.info.features.transform_feedback {
+            glBindTransformFeedback(GL_TRANSFORM_FEEDBACK, 0);
+            glBindBuffer(GL_TRANSFORM_FEEDBACK_BUFFER, 0);
+        }
+    }
+
+    fn set_blend(
+        &mut self,
+        color_blend: Option<BlendState>,
+        alpha_blend: Option<BlendState>,
+        attachment_blends: [Option<AttachmentBlend>; MAX_COLOR_ATTACHMENTS],
+    ) {
         if color_blend.is_none() && alpha_blend.is_some() {
             panic!("AlphaBlend without ColorBlend");
         }
-        if self.cache.color_blend == color_blend && self.cache.alpha_blend == alpha_blend {
+        for attachment in attachment_blends.iter().flatten() {
+            if attachment.color_blend.is_none() && attachment.alpha_blend.is_some() {
+                panic!("AlphaBlend without ColorBlend");
+            }
+        }
+        // without indexed blend functions the overrides are ignored
+        let attachment_blends = if self.info.features.independent_blend {
+            attachment_blends
+        } else {
+            [None; MAX_COLOR_ATTACHMENTS]
+        };
+        if self.cache.color_blend == color_blend
+            && self.cache.alpha_blend == alpha_blend
+            && self.cache.attachment_blends == attachment_blends
+        {
+            self.cache.stats.skipped_state_changes += 1;
             return;
         }
 
+        // after indexed calls GL_BLEND may differ between the attachments,
+        // so the "enabled" part of the cache can't be trusted anymore
+        let indexed_before = self.cache.attachment_blends.iter().any(Option::is_some);
+
         unsafe {
-            if let Some(color_blend) = color_blend {
-                if self.cache.color_blend.is_none() {
-                    glEnable(GL_BLEND);
-                }
+            if attachment_blends.iter().all(Option::is_none) {
+                if let Some(color_blend) = color_blend {
+                    if self.cache.color_blend.is_none() || indexed_before {
+                        glEnable(GL_BLEND);
+                    }
 
-                let BlendState {
-                    equation: eq_rgb,
-                    sfactor: src_rgb,
-                    dfactor: dst_rgb,
-                } = color_blend;
-
-                if let Some(BlendState {
-                    equation: eq_alpha,
-                    sfactor: src_alpha,
-                    dfactor: dst_alpha,
-                }) = alpha_blend
-                {
-                    glBlendFuncSeparate(
-                        src_rgb.into(),
-                        dst_rgb.into(),
-                        src_alpha.into(),
-                        dst_alpha.into(),
-                    );
-                    glBlendEquationSeparate(eq_rgb.into(), eq_alpha.into());
-                } else {
-                    glBlendFunc(src_rgb.into(), dst_rgb.into());
-                    glBlendEquationSeparate(eq_rgb.into(), eq_rgb.into());
+                    let BlendState {
+                        equation: eq_rgb,
+                        sfactor: src_rgb,
+                        dfactor: dst_rgb,
+                    } = color_blend;
+
+                    if let Some(BlendState {
+                        equation: eq_alpha,
+                        sfactor: src_alpha,
+                        dfactor: dst_alpha,
+                    }) = alpha_blend
+                    {
+                        glBlendFuncSeparate(
+                            src_rgb.into(),
+                            dst_rgb.into(),
+                            src_alpha.into(),
+                            dst_alpha.into(),
+                        );
+                        glBlendEquationSeparate(eq_rgb.into(), eq_alpha.into());
+                    } else {
+                        glBlendFunc(src_rgb.into(), dst_rgb.into());
+                        glBlendEquationSeparate(eq_rgb.into(), eq_rgb.into());
+                    }
+                } else if self.cache.color_blend.is_some() || indexed_before {
+                    glDisable(GL_BLEND);
+                }
+            } else {
+                for (i, attachment) in attachment_blends.iter().enumerate() {
+                    let (color_blend, alpha_blend) = match attachment {
+                        Some(attachment) => (attachment.color_blend, attachment.alpha_blend),
+                        None => (color_blend, alpha_blend),
+                    };
+                    let i = i as GLuint;
+                    if let Some(color_blend) = color_blend {
+                        let alpha_blend = alpha_blend.unwrap_or(color_blend);
+                        glEnablei(GL_BLEND, i);
+                        glBlendFuncSeparatei(
+                            i,
+                            color_blend.sfactor.into(),
+                            color_blend.dfactor.into(),
+                            alpha_blend.sfactor.into(),
+                            alpha_blend.dfactor.into(),
+                        );
+                        glBlendEquationSeparatei(
+                            i,
+                            color_blend.equation.into(),
+                            alpha_blend.equation.into(),
+                        );
+                    } else {
+                        glDisablei(GL_BLEND, i);
+                    }
                 }
-            } else if self.cache.color_blend.is_some() {
-                glDisable(GL_BLEND);
             }
         }
 
         self.cache.color_blend = color_blend;
         self.cache.alpha_blend = alpha_blend;
+        self.cache.attachment_blends = attachment_blends;
     }
 
     fn set_stencil(&mut self, stencil_test: Option<StencilState>) {
         if self.cache.stencil == stencil_test {
+            self.cache.stats.skipped_state_changes += 1;
             return;
         }
         unsafe {
@@ -777,6 +1412,8 @@ impl GlContext {
                     back.test_mask,
                 );
                 glStencilMaskSeparate(GL_BACK, back.write_mask);
+
+                self.cache.stencil_ref = (front.test_ref, back.test_ref);
             } else if self.cache.stencil.is_some() {
                 glDisable(GL_STENCIL_TEST);
             }
@@ -785,8 +1422,41 @@ impl GlContext {
         self.cache.stencil = stencil_test;
     }
 
+    fn draw_debug_overlay(&mut self) {
+        if !overlay::enabled() {
+            self.debug_overlay = None;
+            return;
+        }
+        let stats = self.stats();
+        let mut debug_overlay = self
+            .debug_overlay
+            .take()
+            .unwrap_or_else(|| overlay::DebugOverlay::new(self));
+        debug_overlay.draw(self, &stats);
+        self.debug_overlay = Some(debug_overlay);
+        // the overlay is not a part of the user's frame
+        self.cache.stats = stats.frame;
+        self.draw_calls.set(stats.frame.draw_calls);
+    }
+
+    fn set_scissor_test(&mut self, scissor_test: bool) {
+        if self.cache.scissor_test == scissor_test {
+            self.cache.stats.skipped_state_changes += 1;
+            return;
+        }
+        unsafe {
+            if scissor_test {
+                glEnable(GL_SCISSOR_TEST);
+            } else {
+                glDisable(GL_SCISSOR_TEST);
+            }
+        }
+        self.cache.scissor_test = scissor_test;
+    }
+
     fn set_cull_face(&mut self, cull_face: CullFace) {
         if self.cache.cull_face == cull_face {
+            self.cache.stats.skipped_state_changes += 1;
             return;
         }
 
@@ -808,12 +1478,230 @@ impl GlContext {
 
     fn set_color_write(&mut self, color_write: ColorMask) {
         if self.cache.color_write == color_write {
+            self.cache.stats.skipped_state_changes += 1;
             return;
         }
         let (r, g, b, a) = color_write;
         unsafe { glColorMask(r as _, g as _, b as _, a as _) }
         self.cache.color_write = color_write;
     }
+
+    fn set_polygon_mode(&mut self, polygon_mode: PolygonMode) {
+        let polygon_mode = if self.info.features.polygon_mode {
+            polygon_mode
+        } else {
+            PolygonMode::Fill
+        };
+        if self.cache.polygon_mode == polygon_mode {
+            self.cache.stats.skipped_state_changes += 1;
+            return;
+        }
+
+        match polygon_mode {
+            PolygonMode::Fill => unsafe {
+                glPolygonMode(GL_FRONT_AND_BACK, GL_FILL);
+            },
+            PolygonMode::Line => unsafe {
+                glPolygonMode(GL_FRONT_AND_BACK, GL_LINE);
+            },
+        }
+        self.cache.polygon_mode = polygon_mode;
+    }
+
+    fn set_line_width(&mut self, line_width: f32) {
+        if self.cache.line_width == line_width {
+            self.cache.stats.skipped_state_changes += 1;
+            return;
+        }
+        unsafe { glLineWidth(line_width) }
+        self.cache.line_width = line_width;
+    }
+
+    fn set_depth_clamp(&mut self, depth_clamp: bool) {
+        let depth_clamp = depth_clamp && self.info.features.depth_clamp;
+        if self.cache.depth_clamp == depth_clamp {
+            self.cache.stats.skipped_state_changes += 1;
+            return;
+        }
+        unsafe {
+            if depth_clamp {
+                glEnable(GL_DEPTH_CLAMP);
+            } else {
+                glDisable(GL_DEPTH_CLAMP);
+            }
+        }
+        self.cache.depth_clamp = depth_clamp;
+    }
+
+    fn set_alpha_to_coverage(&mut self, alpha_to_coverage: bool) {
+        if self.cache.alpha_to_coverage == alpha_to_coverage {
+            self.cache.stats.skipped_state_changes += 1;
+            return;
+        }
+        unsafe {
+            if alpha_to_coverage {
+                glEnable(GL_SAMPLE_ALPHA_TO_COVERAGE);
+            } else {
+                glDisable(GL_SAMPLE_ALPHA_TO_COVERAGE);
+            }
+        }
+        self.cache.alpha_to_coverage = alpha_to_coverage;
+    }
+
+    fn set_primitive_restart(&mut self, primitive_restart: bool) {
+        // webgl2 always restarts on the fixed index and can't toggle it
+        let primitive_restart = primitive_restart
+            && self.info.features.primitive_restart
+            && cfg!(not(target_arch = "wasm32"));
+        if self.cache.primitive_restart == primitive_restart {
+            self.cache.stats.skipped_state_changes += 1;
+            return;
+        }
+        unsafe {
+            if primitive_restart {
+                glEnable(GL_PRIMITIVE_RESTART_FIXED_INDEX);
+            } else {
+                glDisable(GL_PRIMITIVE_RESTART_FIXED_INDEX);
+            }
+        }
+        self.cache.primitive_restart = primitive_restart;
+    }
+}
+
+/// Whether any blend state uses `Equation::Min` or `Equation::Max`.
+fn uses_minmax(params: &PipelineParams) -> bool {
+    let minmax = |blend: &Option<BlendState>| {
+        matches!(
+            blend,
+            Some(BlendState {
+                equation: Equation::Min | Equation::Max,
+                ..
+            })
+        )
+    };
+    minmax(&params.color_blend)
+        || minmax(&params.alpha_blend)
+        || params
+            .attachment_blends
+            .iter()
+            .flatten()
+            .any(|attachment| minmax(&attachment.color_blend) || minmax(&attachment.alpha_blend))
+}
+
+/// GL 3.0+ only, glGetStringi is not there on GL2.
+#[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+fn gl_extensions(gl2: bool) -> Vec<String> {
+    // GL3 core profiles only have glGetStringi, GL2, GLES2 and WebGL only glGetString
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        if !gl2 {
+            let count = gl_get_integer(GL_NUM_EXTENSIONS);
+            return (0..count)
+                .filter_map(|i| {
+                    let extension = unsafe { glGetStringi(GL_EXTENSIONS, i) };
+                    if extension.is_null() {
+                        return None;
+                    }
+                    let extension = unsafe { std::ffi::CStr::from_ptr(extension as _) };
+                    Some(extension.to_string_lossy().into_owned())
+                })
+                .collect();
+        }
+    }
+    let extensions = unsafe { glGetString(GL_EXTENSIONS) };
+    if extensions.is_null() {
+        return vec![];
+    }
+    unsafe { std::ffi::CStr::from_ptr(extensions as _) }
+        .to_string_lossy()
+        .split_whitespace()
+        .map(|extension| extension.to_string())
+        .collect()
+}
+
+fn gl_get_integer(name: GLenum) -> u32 {
+    let mut value: GLint = 0;
+    unsafe { glGetIntegerv(name, &mut value as *mut _) };
+    value.max(0) as u32
+}
+
+/// "4.6.0 NVIDIA 535", "OpenGL ES 3.2 Mesa" or "WebGL 2.0" into (major, minor).
+fn gl_parse_version(version_string: &str) -> (u8, u8) {
+    let start = version_string
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(version_string.len());
+    let mut numbers = version_string[start..]
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|n| n.parse().unwrap_or(0));
+    (numbers.next().unwrap_or(0), numbers.next().unwrap_or(0))
+}
+
+fn gl_context_info(gl_version_string: &str, desktop_gl: bool) -> GlContextInfo {
+    let version = gl_parse_version(gl_version_string);
+    let flags = if (desktop_gl && version.0 >= 3) || (!desktop_gl && version >= (3, 2)) {
+        gl_get_integer(GL_CONTEXT_FLAGS)
+    } else {
+        0
+    };
+    let core_profile = desktop_gl
+        && version >= (3, 2)
+        && gl_get_integer(GL_CONTEXT_PROFILE_MASK) & GL_CONTEXT_CORE_PROFILE_BIT != 0;
+
+    // GL_DEPTH_BITS/GL_STENCIL_BITS are gone from core profiles
+    #[cfg(not(target_arch = "wasm32"))]
+    let attachment_parameter = |attachment, pname| {
+        let mut value: GLint = 0;
+        unsafe {
+            glGetFramebufferAttachmentParameteriv(
+                GL_FRAMEBUFFER,
+                attachment,
+                pname,
+                &mut value as *mut _,
+            )
+        };
+        value
+    };
+    // the size of a missing attachment is GL_INVALID_OPERATION
+    #[cfg(not(target_arch = "wasm32"))]
+    let attachment_size = |attachment, pname| {
+        if attachment_parameter(attachment, GL_FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE) == GL_NONE as _ {
+            return 0;
+        }
+        attachment_parameter(attachment, pname).clamp(0, u8::MAX as _) as u8
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    let (depth_bits, stencil_bits) = if desktop_gl && version.0 >= 3 {
+        let mut framebuffer: GLint = 0;
+        unsafe { glGetIntegerv(GL_FRAMEBUFFER_BINDING, &mut framebuffer as *mut _) };
+        unsafe { glBindFramebuffer(GL_FRAMEBUFFER, 0) };
+        let bits = (
+            attachment_size(GL_DEPTH, GL_FRAMEBUFFER_ATTACHMENT_DEPTH_SIZE),
+            attachment_size(GL_STENCIL, GL_FRAMEBUFFER_ATTACHMENT_STENCIL_SIZE),
+        );
+        unsafe { glBindFramebuffer(GL_FRAMEBUFFER, framebuffer as _) };
+        bits
+    } else {
+        (
+            gl_get_integer(GL_DEPTH_BITS) as u8,
+            gl_get_integer(GL_STENCIL_BITS) as u8,
+        )
+    };
+    #[cfg(target_arch = "wasm32")]
+    let (depth_bits, stencil_bits) = (
+        gl_get_integer(GL_DEPTH_BITS) as u8,
+        gl_get_integer(GL_STENCIL_BITS) as u8,
+    );
+
+    GlContextInfo {
+        version,
+        gles: !desktop_gl,
+        core_profile,
+        debug: flags & GL_CONTEXT_FLAG_DEBUG_BIT != 0,
+        robust_access: flags & GL_CONTEXT_FLAG_ROBUST_ACCESS_BIT != 0,
+        no_error: flags & GL_CONTEXT_FLAG_NO_ERROR_BIT != 0,
+        depth_bits,
+        stencil_bits,
+    }
 }
 
 #[allow(clippy::field_reassign_with_default)]
@@ -830,10 +1718,85 @@ fn gl_info() -> ContextInfo {
         || gl_version_string.starts_with("OpenGL ES 2");
     let webgl1 = gl_version_string == "WebGL 1.0";
 
+    // desktop GL version strings start with the version number,
+    // GLES and WebGL ones with "OpenGL ES" and "WebGL"
+    let desktop_gl = gl_version_string.starts_with(|c: char| c.is_ascii_digit());
+    let gl32 = gl_version_string.starts_with('4')
+        || gl_version_string.starts_with("3.2")
+        || gl_version_string.starts_with("3.3");
+    let gl43 = ["4.3", "4.4", "4.5", "4.6"]
+        .iter()
+        .any(|v| gl_version_string.starts_with(v));
+    let primitive_restart = gl43
+        || gl_version_string.contains("OpenGL ES 3")
+        || gl_version_string.contains("WebGL 2.0");
+    let extensions = gl_extensions(gl2);
+    let has_extension = |name: &str| extensions.iter().any(|e| e == name);
+    // glEnablei/glBlendFuncSeparatei are core since GL 4.0 and GLES 3.2,
+    // js/gl.js maps them to OES_draw_buffers_indexed on WebGL 2
+    let independent_blend = gl_version_string.starts_with('4')
+        || gl_version_string.contains("OpenGL ES 3.2")
+        || (gl_version_string.contains("WebGL 2.0") && has_extension("OES_draw_buffers_indexed"));
+    // GL_MIN/GL_MAX are core on desktop GL, GLES 3 and WebGL 2
+    let blend_minmax = desktop_gl
+        || (!gl2 && !webgl1)
+        || has_extension("GL_EXT_blend_minmax")
+        || has_extension("EXT_blend_minmax");
+    // glDrawElementsInstancedBaseInstance is not in WebGL at all
+    let base_instance = cfg!(not(target_arch = "wasm32"))
+        && desktop_gl
+        && (gl43 || gl_version_string.starts_with("4.2") || has_extension("GL_ARB_base_instance"));
+
     let features = Features {
         instancing: !gl2,
         resolve_attachments: !webgl1 && !gl2,
+        independent_blend,
+        blend_minmax,
+        polygon_mode: desktop_gl,
+        depth_clamp: desktop_gl && gl32,
+        primitive_restart,
+        base_instance,
+        // glMapBufferRange/glGetBufferSubData and transform feedback came together with GL3
+        buffer_read: !gl2 && !webgl1,
+        transform_feedback: !gl2 && !webgl1,
+        shadow_samplers: !gl2 && !webgl1,
+    };
+
+    // desktop GL counts uniform components, GLES and WebGL vec4s
+    let uniform_vectors = |vectors, components| {
+        if desktop_gl {
+            gl_get_integer(components) / 4
+        } else {
+            gl_get_integer(vectors)
+        }
     };
+    let limits = Limits {
+        max_texture_size: gl_get_integer(GL_MAX_TEXTURE_SIZE),
+        max_cube_map_texture_size: gl_get_integer(GL_MAX_CUBE_MAP_TEXTURE_SIZE),
+        max_samples: if gl2 || webgl1 {
+            1
+        } else {
+            gl_get_integer(GL_MAX_SAMPLES)
+        },
+        max_vertex_attributes: gl_get_integer(GL_MAX_VERTEX_ATTRIBS),
+        max_texture_units: gl_get_integer(GL_MAX_TEXTURE_IMAGE_UNITS),
+        max_vertex_uniform_vectors: uniform_vectors(
+            GL_MAX_VERTEX_UNIFORM_VECTORS,
+            GL_MAX_VERTEX_UNIFORM_COMPONENTS,
+        ),
+        max_fragment_uniform_vectors: uniform_vectors(
+            GL_MAX_FRAGMENT_UNIFORM_VECTORS,
+            GL_MAX_FRAGMENT_UNIFORM_COMPONENTS,
+        ),
+        max_draw_buffers: if gl2 || webgl1 {
+            1
+        } else {
+            gl_get_integer(GL_MAX_DRAW_BUFFERS)
+        },
+    }
+    .clamped();
+
+    let gl_context = gl_context_info(&gl_version_string, desktop_gl);
 
     let mut glsl_support = GlslSupport::default();
 
@@ -878,6 +1841,11 @@ fn gl_info() -> ContextInfo {
         gl_version_string,
         glsl_support,
         features,
+        limits,
+        extensions,
+        gl_context,
+        // only the windowing system knows, filled in GlContext::new
+        srgb_framebuffer: false,
     }
 }
 
@@ -890,13 +1858,38 @@ impl RenderingBackend for GlContext {
         &mut self,
         shader: ShaderSource,
         meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        self.new_feedback_shader(shader, meta, &[])
+    }
+
+    fn new_feedback_shader(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+        varyings: &[&str],
     ) -> Result<ShaderId, ShaderError> {
         let (fragment, vertex) = match shader {
             ShaderSource::Glsl { fragment, vertex } => (fragment, vertex),
             _ => panic!("Metal source on OpenGl context"),
         };
-        let shader = load_shader_internal(vertex, fragment, meta)?;
-        Ok(ShaderId(self.shaders.add(shader)))
+        assert!(
+            varyings.is_empty() || self.info.features.transform_feedback,
+            "transform feedback is not supported by the GPU"
+        );
+        self.check_context_loss();
+        let shader = load_shader_internal(vertex, fragment, meta.clone(), varyings)?;
+        self.resources.shaders += 1;
+        let id = self.shaders.add(shader);
+        if let Some(recovery) = &mut self.recovery {
+            let shader = ShaderShadow {
+                vertex: vertex.to_string(),
+                fragment: fragment.to_string(),
+                meta,
+                varyings: varyings.iter().map(|name| name.to_string()).collect(),
+            };
+            recovery.shaders.insert(id, shader);
+        }
+        Ok(ShaderId(id))
     }
 
     fn new_texture(
@@ -905,7 +1898,20 @@ impl RenderingBackend for GlContext {
         source: TextureSource,
         params: TextureParams,
     ) -> TextureId {
+        self.check_context_loss();
+        if let Some(recovery) = &mut self.recovery {
+            let texture = TextureShadow {
+                access,
+                params,
+                wrap: None,
+                data: TextureData::new(&source),
+                mipmaps: false,
+            };
+            recovery.textures.insert(self.textures.0.len(), texture);
+        }
         let texture = Texture::new(self, access, source, params);
+        self.resources.textures += 1;
+        self.resources.texture_memory += texture.params.memory_size();
         self.textures.0.push(texture);
         TextureId(TextureIdInner::Managed(self.textures.0.len() - 1))
     }
@@ -922,19 +1928,42 @@ impl RenderingBackend for GlContext {
                 glDeleteRenderbuffers(1, raw as *const _);
             },
         }
+        if let TextureIdInner::Managed(id) = texture.0 {
+            if let Some(recovery) = &mut self.recovery {
+                recovery.textures.remove(&id);
+            }
+            self.resources.textures = self.resources.textures.saturating_sub(1);
+            self.resources.texture_memory = self
+                .resources
+                .texture_memory
+                .saturating_sub(t.params.memory_size());
+        }
     }
 
     fn delete_shader(&mut self, program: ShaderId) {
         unsafe { glDeleteProgram(self.shaders[program.0].program) };
         self.shaders.remove(program.0);
+        self.resources.shaders = self.resources.shaders.saturating_sub(1);
+        if let Some(recovery) = &mut self.recovery {
+            recovery.shaders.remove(&program.0);
+        }
         self.cache.cur_pipeline = None;
     }
 
     fn delete_pipeline(&mut self, pipeline: Pipeline) {
+        self.delete_vertex_arrays(|vao_pipeline, _, _| vao_pipeline == pipeline);
         self.pipelines.remove(pipeline.0);
+        if let Some(recovery) = &mut self.recovery {
+            recovery.pipelines.remove(&pipeline.0);
+        }
     }
 
     fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap) {
+        if let (Some(recovery), TextureIdInner::Managed(id)) = (&mut self.recovery, texture.0) {
+            if let Some(shadow) = recovery.textures.get_mut(&id) {
+                shadow.wrap = Some((wrap_x, wrap_y));
+            }
+        }
         let t = self.textures.get(texture);
         let raw = t
             .raw
@@ -968,6 +1997,12 @@ impl RenderingBackend for GlContext {
         filter: FilterMode,
         mipmap_filter: MipmapFilterMode,
     ) {
+        if let (Some(recovery), TextureIdInner::Managed(id)) = (&mut self.recovery, texture.0) {
+            if let Some(shadow) = recovery.textures.get_mut(&id) {
+                shadow.params.min_filter = filter;
+                shadow.params.mipmap_filter = mipmap_filter;
+            }
+        }
         let t = self.textures.get(texture);
         let raw = t.raw.texture().expect(
             "texture_set_min_filter not yet implemented for RenderBuffer(multisampled) textures",
@@ -983,6 +2018,11 @@ impl RenderingBackend for GlContext {
         self.cache.restore_texture_binding(0);
     }
     fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode) {
+        if let (Some(recovery), TextureIdInner::Managed(id)) = (&mut self.recovery, texture.0) {
+            if let Some(shadow) = recovery.textures.get_mut(&id) {
+                shadow.params.mag_filter = filter;
+            }
+        }
         let t = self.textures.get(texture);
         let raw = t
             .raw
@@ -1009,16 +2049,60 @@ impl RenderingBackend for GlContext {
         source: Option<&[u8]>,
     ) {
         let mut t = self.textures.get(texture);
+        let old_memory = t.params.memory_size();
         t.resize(self, width, height, source);
         if let TextureIdInner::Managed(tex_id) = texture.0 {
+            if let Some(shadow) = self
+                .recovery
+                .as_mut()
+                .and_then(|recovery| recovery.textures.get_mut(&tex_id))
+            {
+                shadow.params = t.params;
+                shadow.data = match source {
+                    Some(source) => TextureData::Bytes(source.to_vec()),
+                    None => TextureData::Empty,
+                };
+            }
             self.textures.0[tex_id].params = t.params;
+            self.resources.texture_memory =
+                self.resources.texture_memory.saturating_sub(old_memory) + t.params.memory_size();
         };
     }
     fn texture_read_pixels(&mut self, texture: TextureId, source: &mut [u8]) {
+        let params = self.textures.get(texture).params;
+        self.texture_read_pixels_region(
+            texture,
+            0,
+            0,
+            params.width as _,
+            params.height as _,
+            0,
+            source,
+        );
+    }
+    fn texture_read_pixels_region(
+        &mut self,
+        texture: TextureId,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        mip_level: u32,
+        bytes: &mut [u8],
+    ) {
+        let desktop_gl = self
+            .info
+            .gl_version_string
+            .starts_with(|c: char| c.is_ascii_digit());
         let t = self.textures.get(texture);
-        t.read_pixels(source);
+        t.read_pixels_region(desktop_gl, x, y, width, height, mip_level, bytes);
     }
     fn texture_generate_mipmaps(&mut self, texture: TextureId) {
+        if let (Some(recovery), TextureIdInner::Managed(id)) = (&mut self.recovery, texture.0) {
+            if let Some(shadow) = recovery.textures.get_mut(&id) {
+                shadow.mipmaps = true;
+            }
+        }
         let t = self.textures.get(texture);
         let raw = t.raw.texture().expect(
             "texture_generate_mipmaps not yet implemented for RenderBuffer(multisampled) textures",
@@ -1039,14 +2123,142 @@ impl RenderingBackend for GlContext {
         width: i32,
         height: i32,
         source: &[u8],
+    ) {
+        if let (Some(recovery), TextureIdInner::Managed(id)) = (&mut self.recovery, texture.0) {
+            if let Some(shadow) = recovery.textures.get_mut(&id) {
+                shadow.update_part(x_offset, y_offset, width, height, source);
+            }
+        }
+        let t = self.textures.get(texture);
+        t.update_texture_part(
+            self,
+            x_offset,
+            y_offset,
+            width,
+            height,
+            TextureUpload::default(),
+            source,
+        );
+    }
+    fn texture_update_region(
+        &mut self,
+        texture: TextureId,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        upload: TextureUpload,
+        bytes: &[u8],
     ) {
         let t = self.textures.get(texture);
-        t.update_texture_part(self, x_offset, y_offset, width, height, source);
+        // the direct path would pass GL_BGRA for any format
+        upload.check_format(t.params.format);
+        let version = &self.info.gl_version_string;
+        // GL_BGRA is a desktop GL format. GL_UNPACK_ROW_LENGTH is in all but GLES 2 and
+        // WebGL 1, but js/gl.js sizes the upload view to tightly packed rows, so WebGL 2
+        // repacks as well
+        let direct = !upload.flip_y
+            && !upload.premultiply_alpha
+            && (!upload.bgra || version.starts_with(|c: char| c.is_ascii_digit()))
+            && (upload.row_length == 0
+                || !(version.starts_with("OpenGL ES 2") || version.starts_with("WebGL")));
+        if !direct || self.recovery.is_some() {
+            let packed = upload.repack(t.params.format, width as _, height as _, bytes);
+            if !direct {
+                self.texture_update_part(texture, x_offset, y_offset, width, height, &packed);
+                return;
+            }
+            // the shadow copy only takes tightly packed data
+            if let (Some(recovery), TextureIdInner::Managed(id)) = (&mut self.recovery, texture.0) {
+                if let Some(shadow) = recovery.textures.get_mut(&id) {
+                    shadow.update_part(x_offset, y_offset, width, height, &packed);
+                }
+            }
+        }
+        let size = upload.source_size(t.params.format, width as _, height as _);
+        assert!(bytes.len() >= size);
+        t.update_texture_part(
+            self,
+            x_offset,
+            y_offset,
+            width,
+            height,
+            upload,
+            &bytes[..size],
+        );
     }
     fn texture_params(&self, texture: TextureId) -> TextureParams {
         let texture = self.textures.get(texture);
         texture.params
     }
+    fn texture_format_caps(&self, format: TextureFormat) -> TextureFormatCaps {
+        let info = &self.info;
+        let desktop_gl = info
+            .gl_version_string
+            .starts_with(|c: char| c.is_ascii_digit());
+        let gles2 = info.gl_version_string.starts_with("OpenGL ES 2")
+            || info.gl_version_string == "WebGL 1.0";
+        match format {
+            TextureFormat::RGBA8 => TextureFormatCaps {
+                renderable: true,
+                filterable: true,
+                blendable: true,
+            },
+            TextureFormat::RGB8 => {
+                let renderable = !gles2 || info.has_extension("OES_rgb8_rgba8");
+                TextureFormatCaps {
+                    renderable,
+                    filterable: true,
+                    blendable: renderable,
+                }
+            }
+            TextureFormat::RGBA16F if desktop_gl => TextureFormatCaps {
+                renderable: true,
+                filterable: true,
+                blendable: true,
+            },
+            TextureFormat::RGBA16F => {
+                let renderable = info.has_extension("EXT_color_buffer_half_float")
+                    || (!gles2 && info.has_extension("EXT_color_buffer_float"));
+                TextureFormatCaps {
+                    renderable,
+                    filterable: !gles2 || info.has_extension("OES_texture_half_float_linear"),
+                    blendable: renderable,
+                }
+            }
+            // GLES and WebGL only filter depth textures through shadow samplers
+            TextureFormat::Depth | TextureFormat::Depth32 => TextureFormatCaps {
+                renderable: !gles2
+                    || info.has_extension("OES_depth_texture")
+                    || info.has_extension("WEBGL_depth_texture"),
+                filterable: desktop_gl,
+                blendable: false,
+            },
+            // GL_R8 everywhere but on WebGL, where GL_ALPHA is never renderable
+            TextureFormat::Alpha => {
+                let renderable = cfg!(not(target_arch = "wasm32"))
+                    && (!gles2 || info.has_extension("EXT_texture_rg"));
+                TextureFormatCaps {
+                    renderable,
+                    filterable: true,
+                    blendable: renderable,
+                }
+            }
+            // GLES2 and WebGL1 only have them through EXT_sRGB, with unsized formats
+            _ if gles2 => TextureFormatCaps::default(),
+            TextureFormat::SRGBA8 => TextureFormatCaps {
+                renderable: true,
+                filterable: true,
+                blendable: true,
+            },
+            // GL_SRGB8 is not color renderable on GLES
+            TextureFormat::SRGB8 => TextureFormatCaps {
+                renderable: desktop_gl,
+                filterable: true,
+                blendable: desktop_gl,
+            },
+        }
+    }
     unsafe fn texture_raw_id(&self, texture: TextureId) -> RawId {
         let texture = self.textures.get(texture);
         let raw = texture
@@ -1057,15 +2269,16 @@ impl RenderingBackend for GlContext {
         RawId::OpenGl(raw)
     }
 
-    fn new_render_pass_mrt(
+    fn new_render_pass_attachments(
         &mut self,
-        color_img: &[TextureId],
-        resolve_img: Option<&[TextureId]>,
-        depth_img: Option<TextureId>,
+        color_img: &[Attachment],
+        resolve_img: Option<&[Attachment]>,
+        depth_img: Option<Attachment>,
     ) -> RenderPass {
         if color_img.is_empty() && depth_img.is_none() {
             panic!("Render pass should have at least one non-none target");
         }
+        self.check_context_loss();
         let mut gl_fb = 0;
 
         let mut resolves = None;
@@ -1073,46 +2286,10 @@ impl RenderingBackend for GlContext {
             glGenFramebuffers(1, &mut gl_fb as *mut _);
             glBindFramebuffer(GL_FRAMEBUFFER, gl_fb);
             for (i, color_img) in color_img.iter().enumerate() {
-                let texture = self.textures.get(*color_img);
-                if texture.params.sample_count > 1 {
-                    let raw = texture.raw.renderbuffer().unwrap();
-                    glFramebufferRenderbuffer(
-                        GL_FRAMEBUFFER,
-                        GL_COLOR_ATTACHMENT0 + i as u32,
-                        GL_RENDERBUFFER,
-                        raw,
-                    );
-                } else {
-                    let raw = texture.raw.texture().unwrap();
-                    glFramebufferTexture2D(
-                        GL_FRAMEBUFFER,
-                        GL_COLOR_ATTACHMENT0 + i as u32,
-                        GL_TEXTURE_2D,
-                        raw,
-                        0,
-                    );
-                }
+                self.framebuffer_attachment(GL_COLOR_ATTACHMENT0 + i as u32, *color_img);
             }
             if let Some(depth_img) = depth_img {
-                let texture = self.textures.get(depth_img);
-                if texture.params.sample_count > 1 {
-                    let raw = texture.raw.renderbuffer().unwrap();
-                    glFramebufferRenderbuffer(
-                        GL_FRAMEBUFFER,
-                        GL_DEPTH_ATTACHMENT,
-                        GL_RENDERBUFFER,
-                        raw,
-                    );
-                } else {
-                    let raw = texture.raw.texture().unwrap();
-                    glFramebufferTexture2D(
-                        GL_FRAMEBUFFER,
-                        GL_DEPTH_ATTACHMENT,
-                        GL_TEXTURE_2D,
-                        raw,
-                        0,
-                    );
-                }
+                self.framebuffer_attachment(GL_DEPTH_ATTACHMENT, depth_img);
             }
             let mut attachments = vec![];
             for i in 0..color_img.len() {
@@ -1131,15 +2308,7 @@ impl RenderingBackend for GlContext {
                     glGenFramebuffers(1, &mut resolve_fb as *mut _);
                     glBindFramebuffer(GL_FRAMEBUFFER, resolve_fb);
                     resolves.push((resolve_fb, *resolve_img));
-                    let texture = self.textures.get(*resolve_img);
-                    let raw = texture.raw.texture().unwrap();
-                    glFramebufferTexture2D(
-                        GL_FRAMEBUFFER,
-                        GL_COLOR_ATTACHMENT0 + i as u32,
-                        GL_TEXTURE_2D,
-                        raw,
-                        0,
-                    );
+                    self.framebuffer_attachment(GL_COLOR_ATTACHMENT0 + i as u32, *resolve_img);
                     let fb_status = glCheckFramebufferStatus(GL_FRAMEBUFFER);
                     assert!(fb_status != 0);
                     glDrawBuffers(1, attachments.as_ptr() as _);
@@ -1149,12 +2318,23 @@ impl RenderingBackend for GlContext {
         }
         let pass = RenderPassInternal {
             gl_fb,
-            color_textures: color_img.to_vec(),
+            color_textures: color_img.iter().map(|img| img.texture).collect(),
             resolves,
-            depth_texture: depth_img,
+            depth_texture: depth_img.map(|img| img.texture),
+            mip_level: color_img.first().copied().or(depth_img).unwrap().mip_level,
         };
 
-        RenderPass(self.passes.add(pass))
+        self.resources.passes += 1;
+        let id = self.passes.add(pass);
+        if let Some(recovery) = &mut self.recovery {
+            let pass = PassShadow {
+                color_img: color_img.to_vec(),
+                resolve_img: resolve_img.map(|resolve_img| resolve_img.to_vec()),
+                depth_img,
+            };
+            recovery.passes.insert(id, pass);
+        }
+        RenderPass(id)
     }
     fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
         &self.passes[render_pass.0].color_textures
@@ -1163,21 +2343,39 @@ impl RenderingBackend for GlContext {
         let pass_id = render_pass.0;
 
         let render_pass = self.passes.remove(pass_id);
+        self.resources.passes = self.resources.passes.saturating_sub(1);
+        if let Some(recovery) = &mut self.recovery {
+            recovery.passes.remove(&pass_id);
+        }
 
         unsafe { glDeleteFramebuffers(1, &render_pass.gl_fb as *const _) }
 
-        for color_texture in &render_pass.color_textures {
-            self.delete_texture(*color_texture);
+        // a cubemap or a mip chain may be the target of several passes
+        let mut textures = render_pass.color_textures.clone();
+        if let Some(resolves) = &render_pass.resolves {
+            for (fb, attachment) in resolves {
+                unsafe { glDeleteFramebuffers(1, fb as *const _) }
+                textures.push(attachment.texture);
+            }
         }
-        if let Some(resolves) = render_pass.resolves {
-            for (fb, texture) in resolves {
-                unsafe { glDeleteFramebuffers(1, &fb as *const _) }
+        textures.extend(render_pass.depth_texture);
+        for (i, &texture) in textures.iter().enumerate() {
+            if textures[..i].contains(&texture) {
+                continue;
+            }
+            let in_use = self.passes.values().any(|pass| {
+                pass.color_textures.contains(&texture)
+                    || pass.depth_texture == Some(texture)
+                    || pass
+                        .resolves
+                        .iter()
+                        .flatten()
+                        .any(|(_, resolve)| resolve.texture == texture)
+            });
+            if !in_use {
                 self.delete_texture(texture);
             }
         }
-        if let Some(depth_texture) = render_pass.depth_texture {
-            self.delete_texture(depth_texture);
-        }
     }
 
     fn new_pipeline(
@@ -1187,6 +2385,14 @@ impl RenderingBackend for GlContext {
         shader: ShaderId,
         params: PipelineParams,
     ) -> Pipeline {
+        self.check_context_loss();
+
+        // GL_MIN and GL_MAX are invalid enums without blend_minmax
+        assert!(
+            self.info.features.blend_minmax || !uses_minmax(&params),
+            "min/max blend equations require features.blend_minmax"
+        );
+
         #[derive(Clone, Copy, Default)]
         struct BufferCacheData {
             stride: i32,
@@ -1246,6 +2452,17 @@ impl RenderingBackend for GlContext {
             let divisor = if layout.step_func == VertexStep::PerVertex {
                 0
             } else {
+                assert!(
+                    self.info.features.instancing,
+                    "attribute: {} is per-instance, but instancing is not supported by the GPU",
+                    name
+                );
+                assert!(
+                    layout.step_rate >= 1,
+                    "attribute: {} has an invalid step_rate: {}",
+                    name,
+                    layout.step_rate
+                );
                 layout.step_rate
             };
 
@@ -1268,6 +2485,7 @@ impl RenderingBackend for GlContext {
                         stride: buffer_data.stride,
                         buffer_index: *buffer_index,
                         divisor,
+                        normalized: format.normalized(),
                         gl_pass_as_float: *gl_pass_as_float,
                     };
 
@@ -1289,10 +2507,27 @@ impl RenderingBackend for GlContext {
             params,
         };
 
-        Pipeline(self.pipelines.add(pipeline))
+        let id = self.pipelines.add(pipeline);
+        if let Some(recovery) = &mut self.recovery {
+            let pipeline = PipelineShadow {
+                buffer_layout: buffer_layout.to_vec(),
+                attributes: attributes.to_vec(),
+                shader,
+                params,
+            };
+            recovery.pipelines.insert(id, pipeline);
+        }
+        Pipeline(id)
     }
 
     fn apply_pipeline(&mut self, pipeline: &Pipeline) {
+        assert!(
+            self.feedback_outputs.is_none(),
+            "apply_pipeline inside of a feedback pass, pass the pipeline to begin_feedback_pass"
+        );
+        if self.cache.cur_pipeline != Some(*pipeline) {
+            self.cache.stats.pipeline_switches += 1;
+        }
         self.cache.cur_pipeline = Some(*pipeline);
 
         {
@@ -1302,10 +2537,6 @@ impl RenderingBackend for GlContext {
                 glUseProgram(shader.program);
             }
 
-            unsafe {
-                glEnable(GL_SCISSOR_TEST);
-            }
-
             if pipeline.params.depth_write {
                 unsafe {
                     glEnable(GL_DEPTH_TEST);
@@ -1331,10 +2562,19 @@ impl RenderingBackend for GlContext {
         self.set_blend(
             self.pipelines[pipeline.0].params.color_blend,
             self.pipelines[pipeline.0].params.alpha_blend,
+            self.pipelines[pipeline.0].params.attachment_blends,
         );
 
         self.set_stencil(self.pipelines[pipeline.0].params.stencil_test);
+        if let Some(stencil) = self.pipelines[pipeline.0].params.stencil_test {
+            self.apply_stencil_reference(stencil.front.test_ref, stencil.back.test_ref);
+        }
         self.set_color_write(self.pipelines[pipeline.0].params.color_write);
+        self.set_polygon_mode(self.pipelines[pipeline.0].params.polygon_mode);
+        self.set_line_width(self.pipelines[pipeline.0].params.line_width);
+        self.set_depth_clamp(self.pipelines[pipeline.0].params.depth_clamp);
+        self.set_alpha_to_coverage(self.pipelines[pipeline.0].params.alpha_to_coverage);
+        self.set_primitive_restart(self.pipelines[pipeline.0].params.primitive_restart);
     }
 
     fn new_buffer(
@@ -1343,6 +2583,7 @@ impl RenderingBackend for GlContext {
         usage: BufferUsage,
         data: BufferSource,
     ) -> BufferId {
+        self.check_context_loss();
         let gl_target = gl_buffer_target(&type_);
         let gl_usage = gl_usage(&usage);
         let (size, element_size) = match &data {
@@ -1358,8 +2599,17 @@ impl RenderingBackend for GlContext {
             BufferType::IndexBuffer => panic!("unsupported index buffer dimension"),
             BufferType::VertexBuffer => None,
         };
+        let shadow = match &data {
+            BufferSource::Slice(data) if self.recovery.is_some() => unsafe {
+                std::slice::from_raw_parts(data.ptr as *const u8, data.size).to_vec()
+            },
+            _ => vec![],
+        };
         let mut gl_buf: u32 = 0;
 
+        if gl_target == GL_ELEMENT_ARRAY_BUFFER {
+            self.cache.bind_default_vertex_array();
+        }
         unsafe {
             glGenBuffers(1, &mut gl_buf as *mut _);
             self.cache.store_buffer_binding(gl_target);
@@ -1369,6 +2619,7 @@ impl RenderingBackend for GlContext {
             if let BufferSource::Slice(data) = data {
                 debug_assert!(data.is_slice);
                 glBufferSubData(gl_target, 0, size as _, data.ptr as _);
+                self.cache.stats.buffer_upload_bytes += size;
             }
             self.cache.restore_buffer_binding(gl_target);
         }
@@ -1380,7 +2631,19 @@ impl RenderingBackend for GlContext {
             index_type,
         };
 
-        BufferId(self.buffers.add(buffer))
+        self.resources.buffers += 1;
+        self.resources.buffer_memory += size;
+        let id = self.buffers.add(buffer);
+        if let Some(recovery) = &mut self.recovery {
+            recovery.buffers.insert(
+                id,
+                BufferShadow {
+                    usage,
+                    data: shadow,
+                },
+            );
+        }
+        BufferId(id)
     }
 
     fn buffer_update(&mut self, buffer: BufferId, data: BufferSource) {
@@ -1389,6 +2652,13 @@ impl RenderingBackend for GlContext {
             _ => panic!("buffer_update expects BufferSource::slice"),
         };
         debug_assert!(data.is_slice);
+        if let Some(shadow) = self
+            .recovery
+            .as_mut()
+            .and_then(|recovery| recovery.buffers.get_mut(&buffer.0))
+        {
+            shadow.update(unsafe { std::slice::from_raw_parts(data.ptr as *const u8, data.size) });
+        }
         let buffer = &self.buffers[buffer.0];
 
         if matches!(buffer.buffer_type, BufferType::IndexBuffer) {
@@ -1401,11 +2671,57 @@ impl RenderingBackend for GlContext {
         assert!(size <= buffer.size);
 
         let gl_target = gl_buffer_target(&buffer.buffer_type);
+        if gl_target == GL_ELEMENT_ARRAY_BUFFER {
+            self.cache.bind_default_vertex_array();
+        }
         self.cache.store_buffer_binding(gl_target);
         self.cache
             .bind_buffer(gl_target, buffer.gl_buf, buffer.index_type);
         unsafe { glBufferSubData(gl_target, 0, size as _, data.ptr as _) };
         self.cache.restore_buffer_binding(gl_target);
+        self.cache.stats.buffer_upload_bytes += size;
+    }
+
+    fn buffer_read(&mut self, buffer: BufferId, offset: usize, bytes: &mut [u8]) {
+        assert!(
+            self.info.features.buffer_read,
+            "buffer_read is not supported by the GPU"
+        );
+        self.check_context_loss();
+        let buffer = &self.buffers[buffer.0];
+        assert!(
+            offset + bytes.len() <= buffer.size,
+            "buffer_read outside of the {} bytes buffer",
+            buffer.size
+        );
+        if bytes.is_empty() {
+            return;
+        }
+        // GL_COPY_READ_BUFFER is not a part of the VAO or any other tracked state
+        unsafe {
+            glBindBuffer(GL_COPY_READ_BUFFER, buffer.gl_buf);
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                let mapped = glMapBufferRange(
+                    GL_COPY_READ_BUFFER,
+                    offset as _,
+                    bytes.len() as _,
+                    GL_MAP_READ_BIT,
+                );
+                assert!(!mapped.is_null(), "failed to map the buffer");
+                std::ptr::copy_nonoverlapping(mapped as *const u8, bytes.as_mut_ptr(), bytes.len());
+                glUnmapBuffer(GL_COPY_READ_BUFFER);
+            }
+            // WebGL 2 has no buffer mapping
+            #[cfg(target_arch = "wasm32")]
+            glGetBufferSubData(
+                GL_COPY_READ_BUFFER,
+                offset as _,
+                bytes.len() as _,
+                bytes.as_mut_ptr() as _,
+            );
+            glBindBuffer(GL_COPY_READ_BUFFER, 0);
+        }
     }
 
     /// Size of buffer in bytes
@@ -1421,26 +2737,104 @@ impl RenderingBackend for GlContext {
     /// There is no protection against using deleted textures later. However its not an UB in OpenGl and thats why
     /// this function is not marked as unsafe
     fn delete_buffer(&mut self, buffer: BufferId) {
+        self.delete_vertex_arrays(|_, index_buffer, vertex_buffers| {
+            index_buffer == buffer || vertex_buffers.contains(&buffer)
+        });
         unsafe { glDeleteBuffers(1, &self.buffers[buffer.0].gl_buf as *const _) }
         self.cache.clear_buffer_bindings();
         self.cache.clear_vertex_attributes();
-        self.buffers.remove(buffer.0);
+        if let Some(recovery) = &mut self.recovery {
+            recovery.buffers.remove(&buffer.0);
+        }
+        let buffer = self.buffers.remove(buffer.0);
+        self.resources.buffers = self.resources.buffers.saturating_sub(1);
+        self.resources.buffer_memory -= buffer.size;
+    }
+
+    fn poll_loader(&mut self, loader: &Loader) -> Vec<(LoadId, Loaded)> {
+        self.check_context_loss();
+        let mut loaded = vec![];
+        while let Some(done) = loader.try_recv() {
+            loaded.push(self.register_loaded(done));
+        }
+        loaded
     }
 
     /// Set a new viewport rectangle.
     /// Should be applied after begin_pass.
     fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        if self.cache.viewport == Some((x, y, w, h)) {
+            self.cache.stats.skipped_state_changes += 1;
+            return;
+        }
         unsafe {
             glViewport(x, y, w, h);
         }
+        self.cache.viewport = Some((x, y, w, h));
     }
 
     /// Set a new scissor rectangle.
     /// Should be applied after begin_pass.
     fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.set_scissor_test(true);
+        if self.cache.scissor == Some((x, y, w, h)) {
+            self.cache.stats.skipped_state_changes += 1;
+            return;
+        }
         unsafe {
             glScissor(x, y, w, h);
         }
+        self.cache.scissor = Some((x, y, w, h));
+    }
+
+    fn apply_stencil_reference(&mut self, front: i32, back: i32) {
+        if self.cache.stencil_ref == (front, back) {
+            self.cache.stats.skipped_state_changes += 1;
+            return;
+        }
+        // with the stencil test disabled the next set_stencil will set the reference
+        if let Some(stencil) = self.cache.stencil {
+            unsafe {
+                glStencilFuncSeparate(
+                    GL_FRONT,
+                    stencil.front.test_func.into(),
+                    front,
+                    stencil.front.test_mask,
+                );
+                glStencilFuncSeparate(
+                    GL_BACK,
+                    stencil.back.test_func.into(),
+                    back,
+                    stencil.back.test_mask,
+                );
+            }
+            self.cache.stencil_ref = (front, back);
+        }
+    }
+
+    fn apply_blend_color(&mut self, r: f32, g: f32, b: f32, a: f32) {
+        if self.cache.blend_color == (r, g, b, a) {
+            self.cache.stats.skipped_state_changes += 1;
+            return;
+        }
+        unsafe {
+            glBlendColor(r, g, b, a);
+        }
+        self.cache.blend_color = (r, g, b, a);
+    }
+
+    fn validate_bindings(
+        &self,
+        pipeline: &Pipeline,
+        bindings: &Bindings,
+    ) -> Result<(), BindingError> {
+        let shader = &self.shaders[self.pipelines[pipeline.0].shader.0];
+        validate_images(
+            &shader.image_descs,
+            &bindings.images,
+            &self.info.features,
+            |texture| self.textures.get(texture).params,
+        )
     }
 
     fn apply_bindings_from_slice(
@@ -1452,12 +2846,18 @@ impl RenderingBackend for GlContext {
         let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
         let shader = &self.shaders[pip.shader.0];
 
-        for (n, shader_image) in shader.images.iter().enumerate() {
-            let bindings_image = textures
-                .get(n)
-                .unwrap_or_else(|| panic!("Image count in bindings and shader did not match!"));
+        validate_images(
+            &shader.image_descs,
+            textures,
+            &self.info.features,
+            |texture| self.textures.get(texture).params,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+        for (n, (shader_image, desc)) in shader.images.iter().zip(&shader.image_descs).enumerate() {
+            let bindings_image = textures[n];
             if let Some(gl_loc) = shader_image.gl_loc {
-                let texture = self.textures.get(*bindings_image);
+                let texture = self.textures.get(bindings_image);
                 let raw = match texture.raw {
                     TextureOrRenderbuffer::Texture(id) => id,
                     TextureOrRenderbuffer::Renderbuffer(id) => id,
@@ -1466,9 +2866,36 @@ impl RenderingBackend for GlContext {
                     self.cache.bind_texture(n, texture.params.kind.into(), raw);
                     glUniform1i(gl_loc, n as i32);
                 }
+
+                // the same depth texture may be sampled both ways, so the comparison
+                // is toggled on bind, raw textures are left as they are
+                let compare = desc.image_type == ImageType::Shadow;
+                if let TextureIdInner::Managed(index) = bindings_image.0 {
+                    if self.info.features.shadow_samplers && texture.compare != compare {
+                        let target = texture.params.kind.into();
+                        unsafe {
+                            if compare {
+                                glTexParameteri(
+                                    target,
+                                    GL_TEXTURE_COMPARE_MODE,
+                                    GL_COMPARE_REF_TO_TEXTURE as i32,
+                                );
+                                glTexParameteri(target, GL_TEXTURE_COMPARE_FUNC, GL_LEQUAL as i32);
+                            } else {
+                                glTexParameteri(target, GL_TEXTURE_COMPARE_MODE, GL_NONE as i32);
+                            }
+                        }
+                        self.textures.0[index].compare = compare;
+                    }
+                }
             }
         }
 
+        if self.vertex_arrays.is_some() {
+            self.apply_vertex_array(vertex_buffers, index_buffer);
+            return;
+        }
+
         self.cache.bind_buffer(
             GL_ELEMENT_ARRAY_BUFFER,
             self.buffers[index_buffer.0].gl_buf,
@@ -1495,34 +2922,12 @@ impl RenderingBackend for GlContext {
                 }) {
                     self.cache
                         .bind_buffer(GL_ARRAY_BUFFER, vb.gl_buf, vb.index_type);
-
                     unsafe {
-                        match attribute.type_ {
-                            GL_INT | GL_UNSIGNED_INT | GL_SHORT | GL_UNSIGNED_SHORT
-                            | GL_UNSIGNED_BYTE | GL_BYTE
-                                if !attribute.gl_pass_as_float =>
-                            {
-                                glVertexAttribIPointer(
-                                    attr_index as GLuint,
-                                    attribute.size,
-                                    attribute.type_,
-                                    attribute.stride,
-                                    attribute.offset as *mut _,
-                                )
-                            }
-                            _ => glVertexAttribPointer(
-                                attr_index as GLuint,
-                                attribute.size,
-                                attribute.type_,
-                                GL_FALSE as u8,
-                                attribute.stride,
-                                attribute.offset as *mut _,
-                            ),
-                        }
-                        if self.info.features.instancing {
-                            glVertexAttribDivisor(attr_index as GLuint, attribute.divisor as u32);
-                        }
-                        glEnableVertexAttribArray(attr_index as GLuint);
+                        enable_vertex_attribute(
+                            attr_index,
+                            &attribute,
+                            self.info.features.instancing,
+                        )
                     };
 
                     let cached_attr = &mut self.cache.attributes[attr_index];
@@ -1543,55 +2948,22 @@ impl RenderingBackend for GlContext {
     fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
         let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
         let shader = &self.shaders[pip.shader.0];
+        apply_shader_uniforms(&shader.uniforms, uniform_ptr, size);
+    }
 
-        let mut offset = 0;
-
-        for uniform in shader.uniforms.iter() {
-            use UniformType::*;
-
-            assert!(
-                offset as i32 <= size as i32 - uniform.uniform_type.size() as i32 / 4,
-                "Uniforms struct does not match shader uniforms layout"
-            );
-
-            unsafe {
-                let data = (uniform_ptr as *const f32).add(offset);
-                let data_int = (uniform_ptr as *const i32).add(offset);
-
-                if let Some(gl_loc) = uniform.gl_loc {
-                    match uniform.uniform_type {
-                        Float1 => {
-                            glUniform1fv(gl_loc, uniform.array_count, data);
-                        }
-                        Float2 => {
-                            glUniform2fv(gl_loc, uniform.array_count, data);
-                        }
-                        Float3 => {
-                            glUniform3fv(gl_loc, uniform.array_count, data);
-                        }
-                        Float4 => {
-                            glUniform4fv(gl_loc, uniform.array_count, data);
-                        }
-                        Int1 => {
-                            glUniform1iv(gl_loc, uniform.array_count, data_int);
-                        }
-                        Int2 => {
-                            glUniform2iv(gl_loc, uniform.array_count, data_int);
-                        }
-                        Int3 => {
-                            glUniform3iv(gl_loc, uniform.array_count, data_int);
-                        }
-                        Int4 => {
-                            glUniform4iv(gl_loc, uniform.array_count, data_int);
-                        }
-                        Mat4 => {
-                            glUniformMatrix4fv(gl_loc, uniform.array_count, 0, data);
-                        }
-                    }
-                }
-            }
-            offset += uniform.uniform_type.size() / 4 * uniform.array_count as usize;
-        }
+    fn apply_uniform_block_from_bytes(
+        &mut self,
+        block: usize,
+        uniform_ptr: *const u8,
+        size: usize,
+    ) {
+        let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
+        let shader = &self.shaders[pip.shader.0];
+        let uniforms = shader
+            .uniform_blocks
+            .get(block)
+            .unwrap_or_else(|| panic!("shader has no uniform block {}", block));
+        apply_shader_uniforms(uniforms, uniform_ptr, size);
     }
 
     fn clear(
@@ -1634,6 +3006,7 @@ impl RenderingBackend for GlContext {
     }
 
     fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction) {
+        self.check_context_loss();
         self.cache.cur_pass = pass;
         let (framebuffer, w, h) = match pass {
             None => {
@@ -1655,18 +3028,21 @@ impl RenderingBackend for GlContext {
                     .copied()
                     .or(pass.depth_texture)
                     .unwrap();
+                let params = self.textures.get(texture).params;
                 (
                     pass.gl_fb,
-                    self.textures.get(texture).params.width as i32,
-                    self.textures.get(texture).params.height as i32,
+                    (params.width >> pass.mip_level).max(1) as i32,
+                    (params.height >> pass.mip_level).max(1) as i32,
                 )
             }
         };
         unsafe {
             glBindFramebuffer(GL_FRAMEBUFFER, framebuffer);
-            glViewport(0, 0, w, h);
-            glScissor(0, 0, w, h);
         }
+        self.apply_framebuffer_srgb(pass);
+        // the whole framebuffer is drawn until the first apply_scissor_rect
+        self.apply_viewport(0, 0, w, h);
+        self.set_scissor_test(false);
         match action {
             PassAction::Nothing => {}
             PassAction::Clear {
@@ -1679,6 +3055,44 @@ impl RenderingBackend for GlContext {
         }
     }
 
+    fn begin_feedback_pass(&mut self, pipeline: &Pipeline, outputs: &[BufferId]) {
+        assert!(
+            self.info.features.transform_feedback,
+            "transform feedback is not supported by the GPU"
+        );
+        assert!(
+            self.feedback_outputs.is_none(),
+            "begin_feedback_pass inside of a feedback pass"
+        );
+        self.check_context_loss();
+        // glUseProgram is GL_INVALID_OPERATION while transform feedback is active
+        self.apply_pipeline(pipeline);
+        let primitive_type = self.pipelines[pipeline.0].params.primitive_type;
+        unsafe {
+            for (i, output) in outputs.iter().enumerate() {
+                let gl_buf = self.buffers[output.0].gl_buf;
+                glBindBufferBase(GL_TRANSFORM_FEEDBACK_BUFFER, i as GLuint, gl_buf);
+            }
+            glEnable(GL_RASTERIZER_DISCARD);
+            glBeginTransformFeedback(primitive_type.into());
+        }
+        self.feedback_outputs = Some(outputs.len());
+    }
+
+    fn end_feedback_pass(&mut self) {
+        let outputs = self
+            .feedback_outputs
+            .take()
+            .expect("end_feedback_pass without begin_feedback_pass");
+        unsafe {
+            glEndTransformFeedback();
+            glDisable(GL_RASTERIZER_DISCARD);
+            for i in 0..outputs {
+                glBindBufferBase(GL_TRANSFORM_FEEDBACK_BUFFER, i as GLuint, 0);
+            }
+        }
+    }
+
     fn end_render_pass(&mut self) {
         unsafe {
             if let Some(pass) = self.cache.cur_pass.take() {
@@ -1686,9 +3100,9 @@ impl RenderingBackend for GlContext {
                 if let Some(resolves) = &pass.resolves {
                     glBindFramebuffer(GL_READ_FRAMEBUFFER, pass.gl_fb);
                     for (i, (resolve_fb, resolve_img)) in resolves.iter().enumerate() {
-                        let texture = self.textures.get(*resolve_img);
-                        let w = texture.params.width;
-                        let h = texture.params.height;
+                        let texture = self.textures.get(resolve_img.texture);
+                        let w = (texture.params.width >> resolve_img.mip_level).max(1);
+                        let h = (texture.params.height >> resolve_img.mip_level).max(1);
                         glBindFramebuffer(GL_DRAW_FRAMEBUFFER, *resolve_fb);
                         glReadBuffer(GL_COLOR_ATTACHMENT0 + i as u32);
                         glBlitFramebuffer(
@@ -1713,11 +3127,42 @@ impl RenderingBackend for GlContext {
     }
 
     fn commit_frame(&mut self) {
+        // like metal, the overlay is drawn before the queued deletions
+        self.draw_debug_overlay();
+        let deletion_queue = self.deletion_queue.clone();
+        deletion_queue.drain(self);
+
         self.cache.clear_buffer_bindings();
         self.cache.clear_texture_bindings();
+
+        self.last_frame_stats = self.frame_stats();
+        self.cache.stats = FrameStats::default();
+        self.draw_calls.set(0);
+    }
+
+    fn deletion_queue(&self) -> resources::DeletionQueue {
+        self.deletion_queue.clone()
+    }
+
+    fn stats(&self) -> RenderingStats {
+        RenderingStats {
+            frame: self.frame_stats(),
+            last_frame: self.last_frame_stats,
+            resources: self.resources,
+        }
     }
 
     fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        self.draw_base_instance(base_element, num_elements, num_instances, 0);
+    }
+
+    fn draw_base_instance(
+        &self,
+        base_element: i32,
+        num_elements: i32,
+        num_instances: i32,
+        base_instance: i32,
+    ) {
         assert!(
             self.cache.cur_pipeline.is_some(),
             "Drawing without any binded pipeline"
@@ -1728,24 +3173,90 @@ impl RenderingBackend for GlContext {
             eprintln!("Ignoring this draw call");
             return;
         }
+        if !self.info.features.base_instance && base_instance != 0 {
+            eprintln!("base_instance is not supported by the GPU");
+            eprintln!("Ignoring this draw call");
+            return;
+        }
 
         let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
         let primitive_type = pip.params.primitive_type.into();
+
+        if self.feedback_outputs.is_some() {
+            if base_instance != 0 {
+                eprintln!("base_instance is not supported in feedback passes");
+                eprintln!("Ignoring this draw call");
+                return;
+            }
+            // GLES3 does not allow indexed draws while capturing
+            unsafe {
+                glDrawArraysInstanced(primitive_type, base_element, num_elements, num_instances);
+            }
+            self.draw_calls.set(self.draw_calls.get() + 1);
+            return;
+        }
+
         let index_type = self.cache.index_type.expect("Unset index buffer type");
 
+        let gl_index_type = match index_type {
+            1 => GL_UNSIGNED_BYTE,
+            2 => GL_UNSIGNED_SHORT,
+            4 => GL_UNSIGNED_INT,
+            _ => panic!("Unsupported index buffer type!"),
+        };
+        let offset = (index_type as i32 * base_element) as *mut _;
         unsafe {
-            glDrawElementsInstanced(
-                primitive_type,
-                num_elements,
-                match index_type {
-                    1 => GL_UNSIGNED_BYTE,
-                    2 => GL_UNSIGNED_SHORT,
-                    4 => GL_UNSIGNED_INT,
-                    _ => panic!("Unsupported index buffer type!"),
-                },
-                (index_type as i32 * base_element) as *mut _,
-                num_instances,
-            );
+            if base_instance == 0 {
+                glDrawElementsInstanced(
+                    primitive_type,
+                    num_elements,
+                    gl_index_type,
+                    offset,
+                    num_instances,
+                );
+            } else {
+                glDrawElementsInstancedBaseInstance(
+                    primitive_type,
+                    num_elements,
+                    gl_index_type,
+                    offset,
+                    num_instances,
+                    base_instance as GLuint,
+                );
+            }
         }
+        self.draw_calls.set(self.draw_calls.get() + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_minmax() {
+        let blend = |equation| {
+            Some(BlendState::new(
+                equation,
+                BlendFactor::One,
+                BlendFactor::One,
+            ))
+        };
+        let mut params = PipelineParams {
+            color_blend: blend(Equation::Add),
+            alpha_blend: blend(Equation::Subtract),
+            ..Default::default()
+        };
+        assert!(!super::uses_minmax(&params));
+
+        params.attachment_blends[1] = Some(AttachmentBlend {
+            color_blend: None,
+            alpha_blend: blend(Equation::Max),
+        });
+        assert!(super::uses_minmax(&params));
+
+        params.attachment_blends[1] = None;
+        params.color_blend = blend(Equation::Min);
+        assert!(super::uses_minmax(&params));
     }
 }
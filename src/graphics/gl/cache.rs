@@ -9,6 +9,7 @@ pub struct VertexAttributeInternal {
     pub stride: i32,
     pub buffer_index: usize,
     pub divisor: i32,
+    pub normalized: bool,
     pub gl_pass_as_float: bool,
 }
 
@@ -39,10 +40,70 @@ pub struct GlCache {
     pub cur_pass: Option<RenderPass>,
     pub color_blend: Option<BlendState>,
     pub alpha_blend: Option<BlendState>,
+    pub attachment_blends: [Option<AttachmentBlend>; MAX_COLOR_ATTACHMENTS],
+    pub blend_color: (f32, f32, f32, f32),
     pub stencil: Option<StencilState>,
+    pub stencil_ref: (i32, i32),
+    pub viewport: Option<(i32, i32, i32, i32)>,
+    pub scissor: Option<(i32, i32, i32, i32)>,
+    pub scissor_test: bool,
     pub color_write: ColorMask,
     pub cull_face: CullFace,
+    pub polygon_mode: PolygonMode,
+    pub line_width: f32,
+    pub depth_clamp: bool,
+    pub alpha_to_coverage: bool,
+    pub primitive_restart: bool,
     pub attributes: [Option<CachedAttribute>; MAX_VERTEX_ATTRIBUTES],
+    pub vertex_array: GLuint,
+    // VAO created by GlContext::new, `attributes` describe its state
+    pub default_vertex_array: GLuint,
+    // GL_ELEMENT_ARRAY_BUFFER is a part of VAO state, index buffer of the
+    // default VAO while one of the cached VAOs is bound
+    pub default_index_buffer: (GLuint, Option<u32>),
+    pub stats: FrameStats,
+}
+
+impl Default for GlCache {
+    fn default() -> Self {
+        GlCache {
+            stored_index_buffer: 0,
+            stored_index_type: None,
+            stored_vertex_buffer: 0,
+            index_buffer: 0,
+            index_type: None,
+            vertex_buffer: 0,
+            cur_pipeline: None,
+            cur_pass: None,
+            color_blend: None,
+            alpha_blend: None,
+            attachment_blends: [None; MAX_COLOR_ATTACHMENTS],
+            blend_color: (0., 0., 0., 0.),
+            stencil: None,
+            stencil_ref: (0, 0),
+            viewport: None,
+            scissor: None,
+            scissor_test: false,
+            color_write: (true, true, true, true),
+            cull_face: CullFace::Nothing,
+            polygon_mode: PolygonMode::Fill,
+            line_width: 1.0,
+            depth_clamp: false,
+            alpha_to_coverage: false,
+            primitive_restart: false,
+            stored_texture: 0,
+            stored_target: 0,
+            textures: [CachedTexture {
+                target: 0,
+                texture: 0,
+            }; MAX_SHADERSTAGE_IMAGES],
+            attributes: [None; MAX_VERTEX_ATTRIBUTES],
+            vertex_array: 0,
+            default_vertex_array: 0,
+            default_index_buffer: (0, None),
+            stats: FrameStats::default(),
+        }
+    }
 }
 
 impl GlCache {
@@ -53,6 +114,8 @@ impl GlCache {
                 unsafe {
                     glBindBuffer(target, buffer);
                 }
+            } else {
+                self.stats.skipped_state_changes += 1;
             }
         } else {
             if self.index_buffer != buffer {
@@ -60,6 +123,8 @@ impl GlCache {
                 unsafe {
                     glBindBuffer(target, buffer);
                 }
+            } else {
+                self.stats.skipped_state_changes += 1;
             }
             self.index_type = index_type;
         }
@@ -95,6 +160,9 @@ impl GlCache {
                 let target = if target == 0 { GL_TEXTURE_2D } else { target };
                 glBindTexture(target, texture);
                 self.textures[slot_index] = CachedTexture { target, texture };
+                self.stats.texture_binds += 1;
+            } else {
+                self.stats.skipped_state_changes += 1;
             }
         }
     }
@@ -128,6 +196,40 @@ impl GlCache {
         }
     }
 
+    /// Bind one of the cached VAOs, `index_buffer` is the element buffer it was created with.
+    pub fn bind_vertex_array(
+        &mut self,
+        vao: GLuint,
+        index_buffer: GLuint,
+        index_type: Option<u32>,
+    ) {
+        if self.vertex_array == vao {
+            self.stats.skipped_state_changes += 1;
+            return;
+        }
+        if self.vertex_array == self.default_vertex_array {
+            self.default_index_buffer = (self.index_buffer, self.index_type);
+        }
+        unsafe {
+            glBindVertexArray(vao);
+        }
+        self.vertex_array = vao;
+        self.index_buffer = index_buffer;
+        self.index_type = index_type;
+    }
+
+    /// Go back to the default VAO, should be done before touching GL_ELEMENT_ARRAY_BUFFER
+    /// or vertex attributes outside of apply_bindings, not to modify one of the cached VAOs.
+    pub fn bind_default_vertex_array(&mut self) {
+        if self.vertex_array != self.default_vertex_array {
+            unsafe {
+                glBindVertexArray(self.default_vertex_array);
+            }
+            self.vertex_array = self.default_vertex_array;
+            (self.index_buffer, self.index_type) = self.default_index_buffer;
+        }
+    }
+
     pub fn clear_vertex_attributes(&mut self) {
         for attr_index in 0..MAX_VERTEX_ATTRIBUTES {
             let cached_attr = &mut self.attributes[attr_index];
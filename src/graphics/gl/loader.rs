@@ -0,0 +1,263 @@
+//! Background thread with its own GL context, sharing objects with the main one.
+//! Textures and buffers are created there, fenced, and only then registered
+//! in the main GlContext.
+
+use std::{cell::Cell, sync::mpsc, thread};
+
+use super::*;
+use crate::native::SharedGlContext;
+
+/// Returned by `Loader::new_texture`/`Loader::new_buffer` and reported back
+/// by `RenderingBackend::poll_loader` once the resource is ready.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LoadId(u64);
+
+#[derive(Clone, Copy, Debug)]
+pub enum Loaded {
+    Texture(TextureId),
+    Buffer(BufferId),
+}
+
+enum Job {
+    Texture {
+        id: LoadId,
+        bytes: Vec<u8>,
+        params: TextureParams,
+    },
+    Buffer {
+        id: LoadId,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: Vec<u8>,
+        element_size: usize,
+    },
+}
+
+// the data is sent back to keep a shadow copy with context_loss_recovery
+pub(super) enum Done {
+    Texture {
+        id: LoadId,
+        texture: Texture,
+        bytes: Vec<u8>,
+    },
+    Buffer {
+        id: LoadId,
+        buffer: Buffer,
+        usage: BufferUsage,
+        data: Vec<u8>,
+    },
+}
+
+/// Creates textures and buffers on a background thread, see `window::new_loader`.
+///
+/// After a context loss (`EventHandler::context_restored_event`) the loader's context
+/// is lost as well, a new loader should be created.
+pub struct Loader {
+    jobs: Option<mpsc::Sender<Job>>,
+    done: mpsc::Receiver<Done>,
+    next_id: Cell<u64>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Loader {
+    /// Returns None if `context` can't be made current on the loader thread.
+    pub(crate) fn new(mut context: Box<dyn SharedGlContext>) -> Option<Loader> {
+        let (jobs_tx, jobs_rx) = mpsc::channel::<Job>();
+        let (done_tx, done_rx) = mpsc::channel();
+        let (current_tx, current_rx) = mpsc::channel();
+
+        let thread = thread::spawn(move || {
+            let current = context.make_current();
+            let _ = current_tx.send(current);
+            if !current {
+                return;
+            }
+            // glFenceSync is GL 3.2/GLES 3.0
+            let fence = unsafe { !is_gl2() };
+            for job in jobs_rx {
+                let done = unsafe { job.run() };
+                unsafe {
+                    if fence {
+                        let sync = glFenceSync(GL_SYNC_GPU_COMMANDS_COMPLETE, 0);
+                        while glClientWaitSync(sync, GL_SYNC_FLUSH_COMMANDS_BIT, 1_000_000)
+                            == GL_TIMEOUT_EXPIRED
+                        {}
+                        glDeleteSync(sync);
+                    } else {
+                        glFinish();
+                    }
+                }
+                if done_tx.send(done).is_err() {
+                    break;
+                }
+            }
+        });
+
+        // a panic on the thread drops the sender, which is a failure as well
+        if !current_rx.recv().unwrap_or(false) {
+            let _ = thread.join();
+            return None;
+        }
+
+        Some(Loader {
+            jobs: Some(jobs_tx),
+            done: done_rx,
+            next_id: Cell::new(0),
+            thread: Some(thread),
+        })
+    }
+
+    fn next_id(&self) -> LoadId {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        LoadId(id)
+    }
+
+    fn send(&self, job: Job) {
+        // if the thread is gone, poll_loader will never report the job, nothing else to do
+        let _ = self.jobs.as_ref().unwrap().send(job);
+    }
+
+    /// Queue a `TextureAccess::Static` texture creation, the same as
+    /// `RenderingBackend::new_texture` with `TextureSource::Bytes`.
+    pub fn new_texture(&self, bytes: Vec<u8>, params: TextureParams) -> LoadId {
+        assert_eq!(
+            params.format.size(params.width, params.height) as usize,
+            bytes.len()
+        );
+        let id = self.next_id();
+        self.send(Job::Texture { id, bytes, params });
+        id
+    }
+
+    /// Queue a buffer creation, the same as
+    /// `RenderingBackend::new_buffer` with `BufferSource::slice(data)`.
+    pub fn new_buffer<T>(&self, type_: BufferType, usage: BufferUsage, data: &[T]) -> LoadId {
+        let element_size = std::mem::size_of::<T>();
+        let data = unsafe {
+            std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data))
+        }
+        .to_vec();
+        let id = self.next_id();
+        self.send(Job::Buffer {
+            id,
+            type_,
+            usage,
+            data,
+            element_size,
+        });
+        id
+    }
+
+    pub(super) fn try_recv(&self) -> Option<Done> {
+        self.done.try_recv().ok()
+    }
+}
+
+impl Drop for Loader {
+    fn drop(&mut self) {
+        // closing the channel ends the loop on the loader thread
+        drop(self.jobs.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Job {
+    unsafe fn run(self) -> Done {
+        match self {
+            Job::Texture { id, bytes, params } => {
+                let texture = Texture::create(
+                    TextureAccess::Static,
+                    TextureSource::Bytes(&bytes),
+                    params,
+                    |target, texture| glBindTexture(target, texture),
+                );
+                glBindTexture(params.kind.into(), 0);
+                Done::Texture { id, texture, bytes }
+            }
+            Job::Buffer {
+                id,
+                type_,
+                usage,
+                data,
+                element_size,
+            } => {
+                let gl_target = gl_buffer_target(&type_);
+                let index_type = match type_ {
+                    BufferType::IndexBuffer
+                        if element_size == 1 || element_size == 2 || element_size == 4 =>
+                    {
+                        Some(element_size as u32)
+                    }
+                    BufferType::IndexBuffer => panic!("unsupported index buffer dimension"),
+                    BufferType::VertexBuffer => None,
+                };
+                let mut gl_buf: u32 = 0;
+                glGenBuffers(1, &mut gl_buf as *mut _);
+                glBindBuffer(gl_target, gl_buf);
+                glBufferData(
+                    gl_target,
+                    data.len() as _,
+                    data.as_ptr() as _,
+                    gl_usage(&usage),
+                );
+                glBindBuffer(gl_target, 0);
+                let buffer = Buffer {
+                    gl_buf,
+                    buffer_type: type_,
+                    size: data.len(),
+                    index_type,
+                };
+                Done::Buffer {
+                    id,
+                    buffer,
+                    usage,
+                    data,
+                }
+            }
+        }
+    }
+}
+
+impl GlContext {
+    pub(super) fn register_loaded(&mut self, done: Done) -> (LoadId, Loaded) {
+        match done {
+            Done::Texture { id, texture, bytes } => {
+                let index = self.textures.0.len();
+                if let Some(recovery) = &mut self.recovery {
+                    let shadow = TextureShadow {
+                        access: TextureAccess::Static,
+                        params: texture.params,
+                        wrap: None,
+                        data: TextureData::Bytes(bytes),
+                        mipmaps: false,
+                    };
+                    recovery.textures.insert(index, shadow);
+                }
+                self.resources.textures += 1;
+                self.resources.texture_memory += texture.params.memory_size();
+                self.textures.0.push(texture);
+                (
+                    id,
+                    Loaded::Texture(TextureId(TextureIdInner::Managed(index))),
+                )
+            }
+            Done::Buffer {
+                id,
+                buffer,
+                usage,
+                data,
+            } => {
+                self.resources.buffers += 1;
+                self.resources.buffer_memory += buffer.size;
+                let index = self.buffers.add(buffer);
+                if let Some(recovery) = &mut self.recovery {
+                    recovery.buffers.insert(index, BufferShadow { usage, data });
+                }
+                (id, Loaded::Buffer(BufferId(index)))
+            }
+        }
+    }
+}
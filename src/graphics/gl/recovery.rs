@@ -0,0 +1,278 @@
+//! CPU side copies of everything GlContext created, used to recreate
+//! the resources after a GL context loss.
+//! Only exists with `conf::Platform::context_loss_recovery`.
+
+use std::collections::HashMap;
+
+use super::*;
+
+pub(super) struct ShaderShadow {
+    pub vertex: String,
+    pub fragment: String,
+    pub meta: ShaderMeta,
+    pub varyings: Vec<String>,
+}
+
+pub(super) enum TextureData {
+    Empty,
+    Bytes(Vec<u8>),
+    /// `[cubemap_face][mipmap_level][bytes]`
+    Array(Vec<Vec<Vec<u8>>>),
+}
+
+impl TextureData {
+    pub fn new(source: &TextureSource) -> TextureData {
+        match source {
+            TextureSource::Empty => TextureData::Empty,
+            TextureSource::Bytes(bytes) => TextureData::Bytes(bytes.to_vec()),
+            TextureSource::Array(array) => TextureData::Array(
+                array
+                    .iter()
+                    .map(|face| face.iter().map(|level| level.to_vec()).collect())
+                    .collect(),
+            ),
+        }
+    }
+}
+
+pub(super) struct TextureShadow {
+    pub access: TextureAccess,
+    pub params: TextureParams,
+    pub wrap: Option<(TextureWrap, TextureWrap)>,
+    pub data: TextureData,
+    pub mipmaps: bool,
+}
+
+impl TextureShadow {
+    /// Mirror of `texture_update_part`.
+    /// Render targets are not shadowed, their content comes from the GPU.
+    pub fn update_part(
+        &mut self,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        source: &[u8],
+    ) {
+        if self.access == TextureAccess::RenderTarget {
+            return;
+        }
+        if let TextureData::Empty = self.data {
+            // textures like font atlases are usually created empty and filled part by part
+            let size = self
+                .params
+                .format
+                .size(self.params.width, self.params.height);
+            self.data = TextureData::Bytes(vec![0; size as usize]);
+        }
+        if let TextureData::Bytes(data) = &mut self.data {
+            let pixel_size = self.params.format.size(1, 1) as usize;
+            let row_size = self.params.width as usize * pixel_size;
+            let part_row_size = width as usize * pixel_size;
+            for y in 0..height as usize {
+                let dst = (y_offset as usize + y) * row_size + x_offset as usize * pixel_size;
+                let src = y * part_row_size;
+                data[dst..dst + part_row_size].copy_from_slice(&source[src..src + part_row_size]);
+            }
+        }
+    }
+}
+
+pub(super) struct BufferShadow {
+    pub usage: BufferUsage,
+    pub data: Vec<u8>,
+}
+
+impl BufferShadow {
+    /// Mirror of `buffer_update`, which always writes from the start of the buffer.
+    pub fn update(&mut self, source: &[u8]) {
+        if self.data.len() < source.len() {
+            self.data.resize(source.len(), 0);
+        }
+        self.data[..source.len()].copy_from_slice(source);
+    }
+}
+
+pub(super) struct PipelineShadow {
+    pub buffer_layout: Vec<BufferLayout>,
+    pub attributes: Vec<VertexAttribute>,
+    pub shader: ShaderId,
+    pub params: PipelineParams,
+}
+
+pub(super) struct PassShadow {
+    pub color_img: Vec<Attachment>,
+    pub resolve_img: Option<Vec<Attachment>>,
+    pub depth_img: Option<Attachment>,
+}
+
+pub(super) struct ContextShadow {
+    /// `NativeDisplayData::context_generation` the resources were created for.
+    pub generation: u32,
+    pub shaders: HashMap<usize, ShaderShadow>,
+    pub textures: HashMap<usize, TextureShadow>,
+    pub buffers: HashMap<usize, BufferShadow>,
+    pub pipelines: HashMap<usize, PipelineShadow>,
+    pub passes: HashMap<usize, PassShadow>,
+}
+
+impl ContextShadow {
+    pub fn new(generation: u32) -> ContextShadow {
+        ContextShadow {
+            generation,
+            shaders: HashMap::new(),
+            textures: HashMap::new(),
+            buffers: HashMap::new(),
+            pipelines: HashMap::new(),
+            passes: HashMap::new(),
+        }
+    }
+}
+
+impl GlContext {
+    /// Recreate all the resources if the context was lost since the last check.
+    pub(super) fn check_context_loss(&mut self) {
+        let generation = match &self.recovery {
+            Some(recovery) => recovery.generation,
+            None => return,
+        };
+        let current_generation = crate::native_display().lock().unwrap().context_generation;
+        if generation != current_generation {
+            self.restore_resources(current_generation);
+        }
+    }
+
+    fn restore_resources(&mut self, generation: u32) {
+        // taken out for the duration of the restore, so new_* calls below
+        // won't shadow the temporary resources
+        let mut shadow = self.recovery.take().unwrap();
+        shadow.generation = generation;
+
+        let mut vao = 0;
+        unsafe {
+            glGetIntegerv(
+                GL_FRAMEBUFFER_BINDING,
+                &mut self.default_framebuffer as *mut _ as *mut _,
+            );
+            glGenVertexArrays(1, &mut vao as *mut _);
+            glBindVertexArray(vao);
+        }
+        // nothing from the old context is bound anymore
+        self.cache = GlCache {
+            vertex_array: vao,
+            default_vertex_array: vao,
+            stats: self.cache.stats,
+            ..GlCache::default()
+        };
+        if let Some(vertex_arrays) = &mut self.vertex_arrays {
+            vertex_arrays.clear();
+        }
+
+        for (id, shader) in &shadow.shaders {
+            let varyings: Vec<&str> = shader.varyings.iter().map(|name| &name[..]).collect();
+            match load_shader_internal(
+                &shader.vertex,
+                &shader.fragment,
+                shader.meta.clone(),
+                &varyings,
+            ) {
+                Ok(restored) => self.shaders[*id] = restored,
+                Err(err) => {
+                    // the old program died with the context, pipelines using
+                    // this shader draw nothing from now on
+                    eprintln!(
+                        "Failed to restore a shader after the context loss: {:?}",
+                        err
+                    );
+                    self.shaders[*id].program = 0;
+                }
+            }
+        }
+
+        for (id, texture) in &shadow.textures {
+            let raw = match &texture.data {
+                TextureData::Empty => {
+                    Texture::new(self, texture.access, TextureSource::Empty, texture.params)
+                }
+                TextureData::Bytes(bytes) => Texture::new(
+                    self,
+                    texture.access,
+                    TextureSource::Bytes(bytes),
+                    texture.params,
+                ),
+                TextureData::Array(array) => {
+                    let levels: Vec<Vec<&[u8]>> = array
+                        .iter()
+                        .map(|face| face.iter().map(|level| &level[..]).collect())
+                        .collect();
+                    let faces: Vec<&[&[u8]]> = levels.iter().map(|face| &face[..]).collect();
+                    Texture::new(
+                        self,
+                        texture.access,
+                        TextureSource::Array(&faces),
+                        texture.params,
+                    )
+                }
+            };
+            self.textures.0[*id] = raw;
+
+            let texture_id = TextureId(TextureIdInner::Managed(*id));
+            if let Some((wrap_x, wrap_y)) = texture.wrap {
+                self.texture_set_wrap(texture_id, wrap_x, wrap_y);
+            }
+            if texture.mipmaps {
+                self.texture_generate_mipmaps(texture_id);
+            }
+        }
+
+        for (id, buffer) in &shadow.buffers {
+            let gl_target = gl_buffer_target(&self.buffers[*id].buffer_type);
+            let index_type = self.buffers[*id].index_type;
+            let size = self.buffers[*id].size;
+            let mut gl_buf: u32 = 0;
+            unsafe {
+                glGenBuffers(1, &mut gl_buf as *mut _);
+                self.cache.store_buffer_binding(gl_target);
+                self.cache.bind_buffer(gl_target, gl_buf, index_type);
+                glBufferData(
+                    gl_target,
+                    size as _,
+                    std::ptr::null() as *const _,
+                    gl_usage(&buffer.usage),
+                );
+                if !buffer.data.is_empty() {
+                    glBufferSubData(
+                        gl_target,
+                        0,
+                        buffer.data.len() as _,
+                        buffer.data.as_ptr() as _,
+                    );
+                }
+                self.cache.restore_buffer_binding(gl_target);
+            }
+            self.buffers[*id].gl_buf = gl_buf;
+        }
+
+        for (id, pipeline) in &shadow.pipelines {
+            let restored = self.new_pipeline(
+                &pipeline.buffer_layout,
+                &pipeline.attributes,
+                pipeline.shader,
+                pipeline.params,
+            );
+            self.pipelines[*id] = self.pipelines.remove(restored.0);
+        }
+
+        for (id, pass) in &shadow.passes {
+            let restored = self.new_render_pass_attachments(
+                &pass.color_img,
+                pass.resolve_img.as_deref(),
+                pass.depth_img,
+            );
+            self.passes[*id] = self.passes.remove(restored.0);
+            self.resources.passes -= 1;
+        }
+
+        self.recovery = Some(shadow);
+    }
+}
@@ -1,5 +1,7 @@
 #![allow(non_snake_case)]
 
+use std::cell::Cell;
+
 use crate::native::apple::{
     apple_util::{self, msg_send_},
     frameworks::*,
@@ -34,6 +36,36 @@ impl From<VertexFormat> for MTLVertexFormat {
             VertexFormat::Int2 => MTLVertexFormat::Int2,
             VertexFormat::Int3 => MTLVertexFormat::Int3,
             VertexFormat::Int4 => MTLVertexFormat::Int4,
+            VertexFormat::Byte1Norm => MTLVertexFormat::UCharNormalized,
+            VertexFormat::Byte2Norm => MTLVertexFormat::UChar2Normalized,
+            VertexFormat::Byte3Norm => MTLVertexFormat::UChar3Normalized,
+            VertexFormat::Byte4Norm => MTLVertexFormat::UChar4Normalized,
+            VertexFormat::SByte1 => MTLVertexFormat::Char,
+            VertexFormat::SByte2 => MTLVertexFormat::Char2,
+            VertexFormat::SByte3 => MTLVertexFormat::Char3,
+            VertexFormat::SByte4 => MTLVertexFormat::Char4,
+            VertexFormat::SByte1Norm => MTLVertexFormat::CharNormalized,
+            VertexFormat::SByte2Norm => MTLVertexFormat::Char2Normalized,
+            VertexFormat::SByte3Norm => MTLVertexFormat::Char3Normalized,
+            VertexFormat::SByte4Norm => MTLVertexFormat::Char4Normalized,
+            VertexFormat::Short1Norm => MTLVertexFormat::UShortNormalized,
+            VertexFormat::Short2Norm => MTLVertexFormat::UShort2Normalized,
+            VertexFormat::Short3Norm => MTLVertexFormat::UShort3Normalized,
+            VertexFormat::Short4Norm => MTLVertexFormat::UShort4Normalized,
+            VertexFormat::SShort1 => MTLVertexFormat::Short,
+            VertexFormat::SShort2 => MTLVertexFormat::Short2,
+            VertexFormat::SShort3 => MTLVertexFormat::Short3,
+            VertexFormat::SShort4 => MTLVertexFormat::Short4,
+            VertexFormat::SShort1Norm => MTLVertexFormat::ShortNormalized,
+            VertexFormat::SShort2Norm => MTLVertexFormat::Short2Normalized,
+            VertexFormat::SShort3Norm => MTLVertexFormat::Short3Normalized,
+            VertexFormat::SShort4Norm => MTLVertexFormat::Short4Normalized,
+            VertexFormat::Half1 => MTLVertexFormat::Half,
+            VertexFormat::Half2 => MTLVertexFormat::Half2,
+            VertexFormat::Half3 => MTLVertexFormat::Half3,
+            VertexFormat::Half4 => MTLVertexFormat::Half4,
+            VertexFormat::UInt2_10_10_10_Rev => MTLVertexFormat::UInt1010102Normalized,
+            VertexFormat::Int2_10_10_10_Rev => MTLVertexFormat::Int1010102Normalized,
             VertexFormat::Mat4 => MTLVertexFormat::Float4,
         }
     }
@@ -79,6 +111,8 @@ impl From<BlendFactor> for MTLBlendFactor {
             BlendFactor::Value(BlendValue::SourceAlpha) => MTLBlendFactor::SourceAlpha,
             BlendFactor::Value(BlendValue::DestinationColor) => MTLBlendFactor::DestinationColor,
             BlendFactor::Value(BlendValue::DestinationAlpha) => MTLBlendFactor::DestinationAlpha,
+            BlendFactor::Value(BlendValue::ConstantColor) => MTLBlendFactor::BlendColor,
+            BlendFactor::Value(BlendValue::ConstantAlpha) => MTLBlendFactor::BlendAlpha,
             BlendFactor::OneMinusValue(BlendValue::SourceColor) => {
                 MTLBlendFactor::OneMinusSourceColor
             }
@@ -91,6 +125,12 @@ impl From<BlendFactor> for MTLBlendFactor {
             BlendFactor::OneMinusValue(BlendValue::DestinationAlpha) => {
                 MTLBlendFactor::OneMinusDestinationAlpha
             }
+            BlendFactor::OneMinusValue(BlendValue::ConstantColor) => {
+                MTLBlendFactor::OneMinusBlendColor
+            }
+            BlendFactor::OneMinusValue(BlendValue::ConstantAlpha) => {
+                MTLBlendFactor::OneMinusBlendAlpha
+            }
             BlendFactor::SourceAlphaSaturate => MTLBlendFactor::SourceAlphaSaturated,
         }
     }
@@ -117,6 +157,8 @@ impl From<Equation> for MTLBlendOperation {
             Equation::Add => MTLBlendOperation::Add,
             Equation::Subtract => MTLBlendOperation::Subtract,
             Equation::ReverseSubtract => MTLBlendOperation::ReverseSubtract,
+            Equation::Min => MTLBlendOperation::Min,
+            Equation::Max => MTLBlendOperation::Max,
         }
     }
 }
@@ -159,6 +201,7 @@ impl From<TextureFormat> for MTLPixelFormat {
     fn from(format: TextureFormat) -> Self {
         match format {
             TextureFormat::RGBA8 => MTLPixelFormat::RGBA8Unorm,
+            TextureFormat::SRGBA8 => MTLPixelFormat::RGBA8Unorm_sRGB,
             //TODO: Depth16Unorm ?
             TextureFormat::Depth => MTLPixelFormat::Depth32Float_Stencil8,
             TextureFormat::RGBA16F => MTLPixelFormat::RGBA16Float,
@@ -214,6 +257,9 @@ pub struct Buffer {
 struct ShaderInternal {
     vertex_function: ObjcId,
     fragment_function: ObjcId,
+    // stages of each ShaderMeta::uniform_blocks
+    uniform_block_stages: Vec<Option<ShaderType>>,
+    images: Vec<ImageDesc>,
     //uniforms: Vec<ShaderUniform>,
     // the distance, in bytes, between two uniforms in uniforms buffer
     //stride: u64,
@@ -222,7 +268,10 @@ struct ShaderInternal {
 struct RenderPassInternal {
     render_pass_desc: ObjcId,
     texture: Vec<TextureId>,
-    _depth_texture: Option<TextureId>,
+    depth_texture: Option<TextureId>,
+    mip_level: u32,
+    // passes are never removed from the Vec, only released
+    deleted: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -231,8 +280,8 @@ struct PipelineInternal {
     depth_stencil_state: ObjcId,
     //layout: Vec<BufferLayout>,
     //attributes: Vec<VertexAttributeInternal>,
-    _shader: ShaderId,
-    //params: PipelineParams,
+    shader: ShaderId,
+    params: PipelineParams,
 }
 
 #[derive(Clone, Copy)]
@@ -279,6 +328,19 @@ pub struct MetalContext {
     // cached pipeline from apply_pipeline
     current_pipeline: Option<Pipeline>,
     current_ub_offset: u64,
+    // blend color is a render encoder state, re-applied on each begin_pass
+    blend_color: (f32, f32, f32, f32),
+    // comparison sampler used for ImageType::Shadow images instead of the texture's one
+    shadow_sampler: ObjcId,
+    frame_stats: FrameStats,
+    last_frame_stats: FrameStats,
+    resources: ResourceStats,
+    // draw takes &self, so draw calls are counted outside of frame_stats
+    draw_calls: Cell<usize>,
+    // resources dropped by the resources module wrappers, deleted in commit_frame
+    deletion_queue: resources::DeletionQueue,
+    // created when the overlay is shown, dropped when it is hidden
+    debug_overlay: Option<overlay::DebugOverlay>,
 }
 
 impl Default for MetalContext {
@@ -357,6 +419,16 @@ impl MetalContext {
                           options:options],
             ];
 
+            let shadow_sampler = {
+                let descriptor = msg_send_![class!(MTLSamplerDescriptor), new];
+                msg_send_![descriptor, setMinFilter: MTLSamplerMinMagFilter::Linear];
+                msg_send_![descriptor, setMagFilter: MTLSamplerMinMagFilter::Linear];
+                msg_send_![descriptor, setCompareFunction: MTLCompareFunction::LessEqual];
+                let sampler = msg_send_![device, newSamplerStateWithDescriptor: descriptor];
+                msg_send_![descriptor, release];
+                sampler
+            };
+
             MetalContext {
                 command_queue,
                 command_buffer: None,
@@ -373,9 +445,108 @@ impl MetalContext {
                 uniform_buffers,
                 current_frame_index: 1,
                 current_ub_offset: 0,
+                blend_color: (0., 0., 0., 0.),
+                shadow_sampler,
+                frame_stats: FrameStats::default(),
+                last_frame_stats: FrameStats::default(),
+                resources: ResourceStats::default(),
+                draw_calls: Cell::new(0),
+                deletion_queue: Default::default(),
+                debug_overlay: None,
             }
         }
     }
+
+    fn draw_debug_overlay(&mut self) {
+        if !overlay::enabled() {
+            self.debug_overlay = None;
+            return;
+        }
+        let stats = self.stats();
+        let mut debug_overlay = self
+            .debug_overlay
+            .take()
+            .unwrap_or_else(|| overlay::DebugOverlay::new(self));
+        debug_overlay.draw(self, &stats);
+        self.debug_overlay = Some(debug_overlay);
+        // the overlay is not a part of the user's frame
+        self.frame_stats = stats.frame;
+        self.draw_calls.set(stats.frame.draw_calls);
+    }
+
+    fn features() -> Features {
+        Features {
+            instancing: true,
+            resolve_attachments: false,
+            independent_blend: true,
+            blend_minmax: true,
+            polygon_mode: true,
+            depth_clamp: true,
+            primitive_restart: true,
+            base_instance: true,
+            buffer_read: true,
+            transform_feedback: false,
+            shadow_samplers: true,
+        }
+    }
+
+    fn supports_sample_count(&self, count: u64) -> bool {
+        unsafe { msg_send![self.device, supportsTextureSampleCount: count] }
+    }
+
+    /// Copy uniforms into the uniform buffer of the frame and bind them at `index`
+    /// for `stage`, or both stages with `None`.
+    fn upload_uniforms(
+        &mut self,
+        stage: Option<ShaderType>,
+        index: u64,
+        uniform_ptr: *const u8,
+        size: usize,
+    ) {
+        assert!(
+            self.current_pipeline.is_some(),
+            "apply_uniforms before apply_pipeline"
+        );
+        assert!(
+            self.render_encoder.is_some(),
+            "apply_uniforms before begin_pass"
+        );
+
+        let render_encoder = self.render_encoder.unwrap();
+
+        self.current_frame_index = (self.current_frame_index + 1) % NUM_INFLIGHT_FRAMES;
+
+        assert!(size < MAX_UNIFORM_BUFFER_SIZE as usize);
+
+        assert!(self.current_ub_offset < MAX_UNIFORM_BUFFER_SIZE);
+
+        let buffer = self.uniform_buffers[self.current_frame_index];
+        unsafe {
+            let dest: *mut std::ffi::c_void = msg_send![buffer, contents];
+            std::ptr::copy(
+                uniform_ptr as _,
+                dest.add(self.current_ub_offset as usize),
+                size,
+            );
+
+            #[cfg(target_os = "macos")]
+            msg_send_![buffer, didModifyRange:NSRange::new(self.current_ub_offset, size as u64)];
+
+            if stage != Some(ShaderType::Fragment) {
+                msg_send_![render_encoder,
+                           setVertexBuffer:buffer
+                           offset:self.current_ub_offset
+                           atIndex:index];
+            }
+            if stage != Some(ShaderType::Vertex) {
+                msg_send_![render_encoder,
+                           setFragmentBuffer:buffer
+                           offset:self.current_ub_offset
+                           atIndex:index];
+            }
+        }
+        self.current_ub_offset = roundup_ub_buffer(self.current_ub_offset + size as u64);
+    }
 }
 
 impl RenderingBackend for MetalContext {
@@ -384,9 +555,24 @@ impl RenderingBackend for MetalContext {
             backend: Backend::Metal,
             gl_version_string: Default::default(),
             glsl_support: Default::default(),
-            features: Features {
-                instancing: true,
-                resolve_attachments: false,
+            features: MetalContext::features(),
+            limits: Limits {
+                max_texture_size: 16384,
+                max_cube_map_texture_size: 16384,
+                max_samples: if self.supports_sample_count(8) { 8 } else { 4 },
+                max_vertex_attributes: 31,
+                // each image takes a sampler state, there are only 16 of them
+                max_texture_units: 16,
+                max_vertex_uniform_vectors: 4096,
+                max_fragment_uniform_vectors: 4096,
+                max_draw_buffers: 8,
+            }
+            .clamped(),
+            extensions: vec![],
+            gl_context: Default::default(),
+            srgb_framebuffer: unsafe {
+                let pixel_format: MTLPixelFormat = msg_send![self.view, colorPixelFormat];
+                pixel_format == MTLPixelFormat::BGRA8Unorm_sRGB
             },
         }
     }
@@ -401,12 +587,31 @@ impl RenderingBackend for MetalContext {
                 msg_send_![*buffer, release];
             }
         }
+        self.resources.buffers = self.resources.buffers.saturating_sub(1);
+        self.resources.buffer_memory = self
+            .resources
+            .buffer_memory
+            .saturating_sub(buffer.size * BUFFERS_IN_ROTATION);
+    }
+
+    fn poll_loader(&mut self, _loader: &Loader) -> Vec<(LoadId, Loaded)> {
+        // loaders require shared GL contexts, window::new_loader never returns one with metal
+        vec![]
     }
     fn delete_texture(&mut self, texture: TextureId) {
-        let texture = self.textures.get(texture);
+        let texture = self.textures.get_mut(texture);
+        // a second release would free whatever reused the object
+        assert!(!texture.texture.is_null(), "texture deleted twice");
         unsafe {
             msg_send_![texture.texture, release];
         }
+        texture.texture = nil;
+        let texture = *texture;
+        self.resources.textures = self.resources.textures.saturating_sub(1);
+        self.resources.texture_memory = self
+            .resources
+            .texture_memory
+            .saturating_sub(texture.params.memory_size());
     }
     fn apply_viewport(&mut self, _x: i32, _y: i32, _w: i32, _h: i32) {}
     fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
@@ -421,6 +626,31 @@ impl RenderingBackend for MetalContext {
         };
         unsafe { msg_send_![self.render_encoder.unwrap(), setScissorRect: r] };
     }
+    fn apply_stencil_reference(&mut self, front: i32, back: i32) {
+        assert!(self.render_encoder.is_some());
+
+        unsafe {
+            msg_send_![
+                self.render_encoder.unwrap(),
+                setStencilFrontReferenceValue: front as u32
+                backReferenceValue: back as u32
+            ]
+        };
+    }
+    fn apply_blend_color(&mut self, r: f32, g: f32, b: f32, a: f32) {
+        self.blend_color = (r, g, b, a);
+        if let Some(render_encoder) = self.render_encoder {
+            unsafe {
+                msg_send_![
+                    render_encoder,
+                    setBlendColorRed: r
+                    green: g
+                    blue: b
+                    alpha: a
+                ]
+            };
+        }
+    }
     fn texture_set_min_filter(
         &mut self,
         texture: TextureId,
@@ -494,8 +724,116 @@ impl RenderingBackend for MetalContext {
     ) {
         unimplemented!()
     }
-    fn texture_read_pixels(&mut self, _texture: TextureId, _bytes: &mut [u8]) {
-        unimplemented!()
+    fn texture_read_pixels(&mut self, texture: TextureId, bytes: &mut [u8]) {
+        let params = self.textures.get(texture).params;
+        self.texture_read_pixels_region(
+            texture,
+            0,
+            0,
+            params.width as _,
+            params.height as _,
+            0,
+            bytes,
+        );
+    }
+    fn texture_read_pixels_region(
+        &mut self,
+        texture: TextureId,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        mip_level: u32,
+        bytes: &mut [u8],
+    ) {
+        // the frame's command buffer is committed, which can't happen with an open encoder
+        assert!(
+            self.render_encoder.is_none(),
+            "texture_read_pixels_region inside a render pass"
+        );
+        let raw_texture = self.textures.get(texture).texture;
+        let params = self.textures.get(texture).params;
+        assert!(
+            params.kind == TextureKind::Texture2D,
+            "read_pixels not yet implemented for cubemaps"
+        );
+        assert!(
+            mip_level < params.mip_levels(),
+            "read_pixels at mip level {}, but the texture has {} levels",
+            mip_level,
+            params.mip_levels()
+        );
+        let level_width = (params.width >> mip_level).max(1) as i32;
+        let level_height = (params.height >> mip_level).max(1) as i32;
+        assert!(
+            x >= 0 && y >= 0 && width >= 0 && height >= 0,
+            "negative read_pixels region"
+        );
+        assert!(
+            x + width <= level_width && y + height <= level_height,
+            "read_pixels region outside of the {}x{} texture",
+            level_width,
+            level_height
+        );
+        assert!(
+            params.format != TextureFormat::Depth,
+            "reading depth textures is only supported on desktop GL"
+        );
+        assert_eq!(
+            params.format.size(width as _, height as _) as usize,
+            bytes.len()
+        );
+        if bytes.is_empty() {
+            return;
+        }
+        let row_size = params.format.size(width as _, 1) as usize;
+
+        unsafe {
+            // render targets are private to the GPU, so the region is blitted into
+            // a shared buffer. The frame's commands so far are flushed with it,
+            // for the read to see what was rendered this frame.
+            let staging: ObjcId = msg_send![self.device,
+                                            newBufferWithLength:bytes.len() as u64
+                                            options:MTLResourceOptions::StorageModeShared];
+            let command_buffer = match self.command_buffer.take() {
+                Some(command_buffer) => command_buffer,
+                None => msg_send![self.command_queue, commandBuffer],
+            };
+            let encoder = msg_send_![command_buffer, blitCommandEncoder];
+            // metal's origin is the top left corner, miniquad's is the bottom left one
+            let origin = MTLOrigin {
+                x: x as u64,
+                y: (level_height - y - height) as u64,
+                z: 0,
+            };
+            let size = MTLSize {
+                width: width as u64,
+                height: height as u64,
+                depth: 1,
+            };
+            msg_send_![encoder, copyFromTexture:raw_texture
+                       sourceSlice:0u64
+                       sourceLevel:mip_level as u64
+                       sourceOrigin:origin
+                       sourceSize:size
+                       toBuffer:staging
+                       destinationOffset:0u64
+                       destinationBytesPerRow:row_size as u64
+                       destinationBytesPerImage:bytes.len() as u64];
+            msg_send_![encoder, endEncoding];
+            msg_send_![command_buffer, commit];
+            msg_send_![command_buffer, waitUntilCompleted];
+            // the rest of the frame, commit_frame presents with it
+            self.command_buffer = Some(msg_send![self.command_queue, commandBuffer]);
+
+            // rows come top down from metal
+            let source: *const u8 = msg_send![staging, contents];
+            for (row, destination) in bytes.chunks_exact_mut(row_size).enumerate() {
+                let source = source.add((height as usize - 1 - row) * row_size);
+                std::ptr::copy_nonoverlapping(source, destination.as_mut_ptr(), row_size);
+            }
+            msg_send_![staging, release];
+        }
     }
     fn texture_generate_mipmaps(&mut self, texture: TextureId) {
         unsafe {
@@ -513,6 +851,28 @@ impl RenderingBackend for MetalContext {
         let texture = self.textures.get(texture);
         texture.params
     }
+    fn texture_format_caps(&self, format: TextureFormat) -> TextureFormatCaps {
+        match format {
+            TextureFormat::RGBA8 | TextureFormat::RGBA16F | TextureFormat::SRGBA8 => {
+                TextureFormatCaps {
+                    renderable: true,
+                    filterable: true,
+                    blendable: true,
+                }
+            }
+            // depth32float is not filterable on iOS GPUs
+            TextureFormat::Depth => TextureFormatCaps {
+                renderable: true,
+                filterable: cfg!(target_os = "macos"),
+                blendable: false,
+            },
+            // no MTLPixelFormat for these yet
+            TextureFormat::RGB8
+            | TextureFormat::Depth32
+            | TextureFormat::Alpha
+            | TextureFormat::SRGB8 => TextureFormatCaps::default(),
+        }
+    }
     unsafe fn texture_raw_id(&self, texture: TextureId) -> RawId {
         let texture = self.textures.get(texture);
         RawId::Metal(texture.texture)
@@ -533,11 +893,11 @@ impl RenderingBackend for MetalContext {
         self.end_render_pass();
     }
 
-    fn new_render_pass_mrt(
+    fn new_render_pass_attachments(
         &mut self,
-        color_img: &[TextureId],
-        resolve_img: Option<&[TextureId]>,
-        depth_img: Option<TextureId>,
+        color_img: &[Attachment],
+        resolve_img: Option<&[Attachment]>,
+        depth_img: Option<Attachment>,
     ) -> RenderPass {
         if resolve_img.is_some() {
             unimplemented!("resolve textures are not yet implemented on metal");
@@ -548,41 +908,70 @@ impl RenderingBackend for MetalContext {
             msg_send_![render_pass_desc, retain];
             assert!(!render_pass_desc.is_null());
             for (i, color_img) in color_img.iter().enumerate() {
-                let color_texture = self.textures.get(*color_img).texture;
+                let color_texture = self.textures.get(color_img.texture).texture;
                 let color_attachment = msg_send_![msg_send_![render_pass_desc, colorAttachments], objectAtIndexedSubscript:i];
                 msg_send_![color_attachment, setTexture: color_texture];
+                msg_send_![color_attachment, setLevel: color_img.mip_level as u64];
+                msg_send_![color_attachment, setSlice: color_img.layer as u64];
                 msg_send_![color_attachment, setLoadAction: MTLLoadAction::Clear];
                 msg_send_![color_attachment, setStoreAction: MTLStoreAction::Store];
             }
             if let Some(depth_img) = depth_img {
-                let depth_texture = self.textures.get(depth_img).texture;
+                let depth_texture = self.textures.get(depth_img.texture).texture;
 
                 let depth_attachment = msg_send_![render_pass_desc, depthAttachment];
                 msg_send_![depth_attachment, setTexture: depth_texture];
+                msg_send_![depth_attachment, setLevel: depth_img.mip_level as u64];
+                msg_send_![depth_attachment, setSlice: depth_img.layer as u64];
                 msg_send_![depth_attachment, setLoadAction: MTLLoadAction::Clear];
                 msg_send_![depth_attachment, setStoreAction: MTLStoreAction::Store];
                 msg_send_![depth_attachment, setClearDepth:1.];
 
                 let stencil_attachment = msg_send_![render_pass_desc, stencilAttachment];
                 msg_send_![stencil_attachment, setTexture: depth_texture];
+                msg_send_![stencil_attachment, setLevel: depth_img.mip_level as u64];
+                msg_send_![stencil_attachment, setSlice: depth_img.layer as u64];
             }
             let pass = RenderPassInternal {
                 render_pass_desc,
-                texture: color_img.to_vec(),
-                _depth_texture: depth_img,
+                texture: color_img.iter().map(|img| img.texture).collect(),
+                depth_texture: depth_img.map(|img| img.texture),
+                mip_level: color_img.first().copied().or(depth_img).unwrap().mip_level,
+                deleted: false,
             };
 
             self.passes.push(pass);
+            self.resources.passes += 1;
 
             RenderPass(self.passes.len() - 1)
         }
     }
 
     fn delete_render_pass(&mut self, render_pass: RenderPass) {
-        let render_pass = &self.passes[render_pass.0];
+        let render_pass = &mut self.passes[render_pass.0];
+        assert!(!render_pass.deleted, "render pass deleted twice");
+        render_pass.deleted = true;
         unsafe {
             msg_send_![render_pass.render_pass_desc, release];
         }
+        self.resources.passes = self.resources.passes.saturating_sub(1);
+
+        // same as on GL: the attachments go with the pass, unless another pass
+        // still renders into them
+        let mut textures = render_pass.texture.clone();
+        textures.extend(render_pass.depth_texture);
+        for (i, &texture) in textures.iter().enumerate() {
+            if textures[..i].contains(&texture) {
+                continue;
+            }
+            let in_use = self.passes.iter().any(|pass| {
+                !pass.deleted
+                    && (pass.texture.contains(&texture) || pass.depth_texture == Some(texture))
+            });
+            if !in_use {
+                self.delete_texture(texture);
+            }
+        }
     }
 
     fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
@@ -630,6 +1019,11 @@ impl RenderingBackend for MetalContext {
             value: 0,
             next_value: 0,
         };
+        self.resources.buffers += 1;
+        self.resources.buffer_memory += size * BUFFERS_IN_ROTATION;
+        if let BufferSource::Slice(_) = data {
+            self.frame_stats.buffer_upload_bytes += size * BUFFERS_IN_ROTATION;
+        }
         self.buffers.push(buffer);
         BufferId(self.buffers.len() - 1)
     }
@@ -650,13 +1044,47 @@ impl RenderingBackend for MetalContext {
             msg_send_![buffer.raw[buffer.next_value], didModifyRange:NSRange::new(0, data.size as u64)];
         }
         buffer.value = buffer.next_value;
+        self.frame_stats.buffer_upload_bytes += data.size;
+    }
+
+    fn buffer_read(&mut self, buffer: BufferId, offset: usize, bytes: &mut [u8]) {
+        let buffer = &self.buffers[buffer.0];
+        assert!(
+            offset + bytes.len() <= buffer.size,
+            "buffer_read outside of the {} bytes buffer",
+            buffer.size
+        );
+        // buffers are only written from the CPU, the last updated one is always up to date
+        unsafe {
+            let source: *const u8 = msg_send![buffer.raw[buffer.value], contents];
+            std::ptr::copy_nonoverlapping(source.add(offset), bytes.as_mut_ptr(), bytes.len());
+        }
+    }
+
+    fn new_feedback_shader(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+        varyings: &[&str],
+    ) -> Result<ShaderId, ShaderError> {
+        if !varyings.is_empty() {
+            return Err(ShaderError::LinkError(
+                "transform feedback is not supported on metal".to_string(),
+            ));
+        }
+        self.new_shader(shader, meta)
     }
 
     fn new_shader(
         &mut self,
         shader: ShaderSource,
-        _meta: ShaderMeta,
+        meta: ShaderMeta,
     ) -> Result<ShaderId, ShaderError> {
+        assert!(
+            meta.uniform_blocks.len() <= MAX_UNIFORM_BLOCKS,
+            "at most {} uniform blocks are supported",
+            MAX_UNIFORM_BLOCKS
+        );
         unsafe {
             let program = match shader {
                 ShaderSource::Msl { program } => program,
@@ -683,8 +1111,15 @@ impl RenderingBackend for MetalContext {
             let shader = ShaderInternal {
                 vertex_function,
                 fragment_function,
+                uniform_block_stages: meta
+                    .uniform_blocks
+                    .iter()
+                    .map(|block| block.stage)
+                    .collect(),
+                images: meta.images,
             };
             self.shaders.push(shader);
+            self.resources.shaders += 1;
             Ok(ShaderId(self.shaders.len() - 1))
         }
     }
@@ -785,6 +1220,8 @@ impl RenderingBackend for MetalContext {
                 sampler_descriptor,
                 params,
             });
+            self.resources.textures += 1;
+            self.resources.texture_memory += params.memory_size();
             TextureId(TextureIdInner::Managed(self.textures.0.len() - 1))
         };
 
@@ -877,6 +1314,10 @@ impl RenderingBackend for MetalContext {
     ) -> Pipeline {
         unsafe {
             let shader_internal = &self.shaders[shader.0];
+            assert!(
+                !shader_internal.vertex_function.is_null(),
+                "new_pipeline with a deleted shader"
+            );
 
             let vertex_descriptor: ObjcId =
                 msg_send![class!(MTLVertexDescriptor), vertexDescriptor];
@@ -913,6 +1354,18 @@ impl RenderingBackend for MetalContext {
                 *offset += a.format.size_bytes() as u64;
             }
             for (i, buffer) in buffer_layout.iter().enumerate() {
+                // metal requires a step rate of 1 for per-vertex layouts
+                let step_rate = match buffer.step_func {
+                    VertexStep::PerVertex => 1,
+                    VertexStep::PerInstance => {
+                        assert!(
+                            buffer.step_rate >= 1,
+                            "invalid step_rate: {}",
+                            buffer.step_rate
+                        );
+                        buffer.step_rate as u64
+                    }
+                };
                 layout(
                     i as u64 + 1,
                     buffer.step_func,
@@ -921,7 +1374,7 @@ impl RenderingBackend for MetalContext {
                     } else {
                         buffer.stride as u64
                     },
-                    buffer.step_rate as u64,
+                    step_rate,
                 );
             }
 
@@ -929,12 +1382,20 @@ impl RenderingBackend for MetalContext {
             msg_send_![descriptor, setVertexFunction:shader_internal.vertex_function];
             msg_send_![descriptor, setFragmentFunction:shader_internal.fragment_function];
             msg_send_![descriptor, setVertexDescriptor: vertex_descriptor];
+            msg_send_![
+                descriptor,
+                setAlphaToCoverageEnabled: params.alpha_to_coverage
+            ];
             let color_attachments = msg_send_![descriptor, colorAttachments];
             for i in 0..2 {
                 let color_attachment = msg_send_![color_attachments, objectAtIndexedSubscript: i];
                 let view_pixel_format: MTLPixelFormat = msg_send![self.view, colorPixelFormat];
                 msg_send_![color_attachment, setPixelFormat: view_pixel_format];
-                if let Some(color_blend) = params.color_blend {
+                let (color_blend, alpha_blend) = match params.attachment_blends[i as usize] {
+                    Some(attachment) => (attachment.color_blend, attachment.alpha_blend),
+                    None => (params.color_blend, params.alpha_blend),
+                };
+                if let Some(color_blend) = color_blend {
                     msg_send_![color_attachment, setBlendingEnabled: true];
 
                     let BlendState {
@@ -946,7 +1407,7 @@ impl RenderingBackend for MetalContext {
                         equation: eq_alpha,
                         sfactor: src_alpha,
                         dfactor: dst_alpha,
-                    } = color_blend;
+                    } = alpha_blend.unwrap_or(color_blend);
                     msg_send_![
                         color_attachment,
                         setRgbBlendOperation: MTLBlendOperation::from(eq_rgb)
@@ -1036,8 +1497,8 @@ impl RenderingBackend for MetalContext {
                 depth_stencil_state,
                 //layout: buffer_layout.to_vec(),
                 //attributes: vertex_layout,
-                _shader: shader,
-                //params,
+                shader,
+                params,
             };
 
             self.pipelines.push(pipeline);
@@ -1053,17 +1514,52 @@ impl RenderingBackend for MetalContext {
         );
         let render_encoder = self.render_encoder.unwrap();
 
+        if self.current_pipeline != Some(*pipeline) {
+            self.frame_stats.pipeline_switches += 1;
+        }
         unsafe {
             self.current_pipeline = Some(*pipeline);
             let pipeline = &self.pipelines[pipeline.0];
 
             msg_send_![render_encoder, setRenderPipelineState: pipeline.pipeline_state];
             msg_send_![render_encoder, setDepthStencilState:pipeline.depth_stencil_state];
+            let fill_mode = match pipeline.params.polygon_mode {
+                PolygonMode::Fill => MTLTriangleFillMode::Fill,
+                PolygonMode::Line => MTLTriangleFillMode::Lines,
+            };
+            msg_send_![render_encoder, setTriangleFillMode: fill_mode];
+            let depth_clip_mode = if pipeline.params.depth_clamp {
+                MTLDepthClipMode::Clamp
+            } else {
+                MTLDepthClipMode::Clip
+            };
+            msg_send_![render_encoder, setDepthClipMode: depth_clip_mode];
+            if let Some(stencil) = pipeline.params.stencil_test {
+                msg_send_![
+                    render_encoder,
+                    setStencilFrontReferenceValue: stencil.front.test_ref as u32
+                    backReferenceValue: stencil.back.test_ref as u32
+                ];
+            }
             // render_encoder.set_front_facing_winding(pipeline.params.front_face_order.into());
             // render_encoder.set_cull_mode(pipeline.params.cull_face.into());
         }
     }
 
+    fn validate_bindings(
+        &self,
+        pipeline: &Pipeline,
+        bindings: &Bindings,
+    ) -> Result<(), BindingError> {
+        let shader = &self.shaders[self.pipelines[pipeline.0].shader.0];
+        validate_images(
+            &shader.images,
+            &bindings.images,
+            &MetalContext::features(),
+            |texture| self.textures.get(texture).params,
+        )
+    }
+
     fn apply_bindings_from_slice(
         &mut self,
         vertex_buffers: &[BufferId],
@@ -1074,6 +1570,12 @@ impl RenderingBackend for MetalContext {
             self.render_encoder.is_some(),
             "apply_bindings before begin_pass"
         );
+        let pipeline = &self.pipelines[self.current_pipeline.unwrap().0];
+        let images = &self.shaders[pipeline.shader.0].images;
+        validate_images(images, textures, &MetalContext::features(), |texture| {
+            self.textures.get(texture).params
+        })
+        .unwrap_or_else(|err| panic!("{}", err));
 
         unsafe {
             let render_encoder = self.render_encoder.unwrap();
@@ -1095,57 +1597,43 @@ impl RenderingBackend for MetalContext {
                     let Texture {
                         sampler, texture, ..
                     } = self.textures.get(*img);
+                    let sampler = match images.get(n) {
+                        Some(image) if image.image_type == ImageType::Shadow => self.shadow_sampler,
+                        _ => sampler,
+                    };
                     msg_send_![render_encoder, setFragmentSamplerState:sampler
                                atIndex:n
                     ];
                     msg_send_![render_encoder, setFragmentTexture:texture
                                atIndex:n
                     ];
+                    self.frame_stats.texture_binds += 1;
                 }
             }
         }
     }
 
     fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
+        self.upload_uniforms(None, 0, uniform_ptr, size);
+    }
+
+    fn apply_uniform_block_from_bytes(
+        &mut self,
+        block: usize,
+        uniform_ptr: *const u8,
+        size: usize,
+    ) {
         assert!(
             self.current_pipeline.is_some(),
             "apply_uniforms before apply_pipeline"
         );
-        assert!(
-            self.render_encoder.is_some(),
-            "apply_uniforms before begin_pass"
-        );
-
-        let render_encoder = self.render_encoder.unwrap();
-
-        self.current_frame_index = (self.current_frame_index + 1) % NUM_INFLIGHT_FRAMES;
-
-        assert!(size < MAX_UNIFORM_BUFFER_SIZE as usize);
-
-        assert!(self.current_ub_offset < MAX_UNIFORM_BUFFER_SIZE);
-
-        let buffer = self.uniform_buffers[self.current_frame_index];
-        unsafe {
-            let dest: *mut std::ffi::c_void = msg_send![buffer, contents];
-            std::ptr::copy(
-                uniform_ptr as _,
-                dest.add(self.current_ub_offset as usize),
-                size,
-            );
-
-            #[cfg(target_os = "macos")]
-            msg_send_![buffer, didModifyRange:NSRange::new(0, size as u64)];
-
-            msg_send_![render_encoder,
-                       setVertexBuffer:buffer
-                       offset:self.current_ub_offset
-                       atIndex:0];
-            msg_send_![render_encoder,
-                       setFragmentBuffer:buffer
-                       offset:self.current_ub_offset
-                       atIndex:0];
-        }
-        self.current_ub_offset = roundup_ub_buffer(self.current_ub_offset + size as u64);
+        let pipeline = &self.pipelines[self.current_pipeline.unwrap().0];
+        let stage = *self.shaders[pipeline.shader.0]
+            .uniform_block_stages
+            .get(block)
+            .unwrap_or_else(|| panic!("shader has no uniform block {}", block));
+        let index = (UNIFORM_BLOCKS_BUFFER_INDEX + block) as u64;
+        self.upload_uniforms(stage, index, uniform_ptr, size);
     }
 
     fn begin_default_pass(&mut self, action: PassAction) {
@@ -1178,10 +1666,11 @@ impl RenderingBackend for MetalContext {
                         //.or(pass.depth_texture)
                         .unwrap();
 
+                    let params = self.textures.get(texture).params;
                     (
                         pass.render_pass_desc,
-                        self.textures.get(texture).params.width as f64,
-                        self.textures.get(texture).params.height as f64,
+                        (params.width >> pass.mip_level).max(1) as f64,
+                        (params.height >> pass.mip_level).max(1) as f64,
                     )
                 }
             };
@@ -1225,10 +1714,28 @@ impl RenderingBackend for MetalContext {
             //     height: h as u64,
             // });
 
+            let (r, g, b, a) = self.blend_color;
+            msg_send_![
+                render_encoder,
+                setBlendColorRed: r
+                green: g
+                blue: b
+                alpha: a
+            ];
+
             self.render_encoder = Some(render_encoder);
         }
     }
 
+    // features.transform_feedback is always false, the same guard as on GL
+    fn begin_feedback_pass(&mut self, _pipeline: &Pipeline, _outputs: &[BufferId]) {
+        panic!("transform feedback is not supported by the GPU");
+    }
+
+    fn end_feedback_pass(&mut self) {
+        panic!("end_feedback_pass without begin_feedback_pass");
+    }
+
     fn end_render_pass(&mut self) {
         assert!(
             self.render_encoder.is_some(),
@@ -1243,6 +1750,16 @@ impl RenderingBackend for MetalContext {
     }
 
     fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        self.draw_base_instance(base_element, num_elements, num_instances, 0);
+    }
+
+    fn draw_base_instance(
+        &self,
+        base_element: i32,
+        num_elements: i32,
+        num_instances: i32,
+        base_instance: i32,
+    ) {
         assert!(self.render_encoder.is_some(), "draw before begin_pass!");
         let render_encoder = self.render_encoder.unwrap();
         assert!(self.index_buffer.is_some());
@@ -1257,19 +1774,33 @@ impl RenderingBackend for MetalContext {
                        indexBufferOffset:0
                        instanceCount:num_instances as u64
                        baseVertex:0
-                       baseInstance:0
+                       baseInstance:base_instance as u64
             ];
         }
+        self.draw_calls.set(self.draw_calls.get() + 1);
     }
 
-    fn delete_shader(&mut self, _shader: ShaderId) {
-        // TODO: place holder
+    fn delete_shader(&mut self, shader: ShaderId) {
+        // existing pipelines keep their compiled state and still need the
+        // shader's metadata, only the functions are released
+        let shader = &mut self.shaders[shader.0];
+        if shader.vertex_function.is_null() {
+            return;
+        }
+        unsafe {
+            msg_send_![shader.vertex_function, release];
+            msg_send_![shader.fragment_function, release];
+        }
+        shader.vertex_function = nil;
+        shader.fragment_function = nil;
+        self.resources.shaders = self.resources.shaders.saturating_sub(1);
     }
     fn delete_pipeline(&mut self, _pipeline: Pipeline) {
         // TODO: place holder
     }
 
     fn commit_frame(&mut self) {
+        self.draw_debug_overlay();
         unsafe {
             assert!(!self.command_queue.is_null());
             let drawable: ObjcId = msg_send!(self.view, currentDrawable);
@@ -1284,8 +1815,33 @@ impl RenderingBackend for MetalContext {
         self.current_ub_offset = 0;
         self.current_pipeline = None;
         self.command_buffer = None;
+        self.last_frame_stats = FrameStats {
+            draw_calls: self.draw_calls.get(),
+            ..self.frame_stats
+        };
+        self.frame_stats = FrameStats::default();
+        self.draw_calls.set(0);
         if (self.current_frame_index + 1) >= 3 {
             self.current_frame_index = 0;
         }
+
+        // the command buffer is completed, nothing can reference the queued resources anymore
+        let deletion_queue = self.deletion_queue.clone();
+        deletion_queue.drain(self);
+    }
+
+    fn deletion_queue(&self) -> resources::DeletionQueue {
+        self.deletion_queue.clone()
+    }
+
+    fn stats(&self) -> RenderingStats {
+        RenderingStats {
+            frame: FrameStats {
+                draw_calls: self.draw_calls.get(),
+                ..self.frame_stats
+            },
+            last_frame: self.last_frame_stats,
+            resources: self.resources,
+        }
     }
 }
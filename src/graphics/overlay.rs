@@ -0,0 +1,334 @@
+//! On-screen debug overlay, drawn by the rendering backend in `commit_frame`,
+//! on top of everything drawn into the default framebuffer during the frame.
+//!
+//! Shown with `conf::Platform::debug_overlay` or `window::set_debug_overlay`,
+//! toggled with `conf::Platform::debug_overlay_toggle`.
+
+use super::{batch::Batcher, resources, *};
+use crate::event::{EventHandler, KeyCode, KeyMods, MouseButton, TouchPhase};
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub(crate) fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+// frame times kept for the graph and the averages
+const HISTORY: usize = 120;
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+// everything else is drawn as a space
+const CHARSET: &str = " 0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ.:/%-()";
+#[rustfmt::skip]
+const FONT: [[u8; GLYPH_HEIGHT]; 44] = [
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000], // ' '
+    [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110], // 0
+    [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110], // 1
+    [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111], // 2
+    [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110], // 3
+    [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010], // 4
+    [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110], // 5
+    [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110], // 6
+    [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000], // 7
+    [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110], // 8
+    [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100], // 9
+    [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001], // A
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110], // B
+    [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110], // C
+    [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100], // D
+    [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111], // E
+    [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000], // F
+    [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111], // G
+    [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001], // H
+    [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110], // I
+    [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100], // J
+    [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001], // K
+    [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111], // L
+    [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001], // M
+    [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001], // N
+    [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110], // O
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000], // P
+    [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101], // Q
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001], // R
+    [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110], // S
+    [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100], // T
+    [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110], // U
+    [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100], // V
+    [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010], // W
+    [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001], // X
+    [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100], // Y
+    [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111], // Z
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100], // .
+    [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000], // :
+    [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000], // /
+    [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011], // %
+    [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000], // -
+    [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010], // (
+    [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000], // )
+];
+
+const BACKGROUND: [f32; 4] = [0., 0., 0., 0.7];
+const TEXT: [f32; 4] = [1., 1., 1., 1.];
+const GOOD: [f32; 4] = [0.3, 0.9, 0.3, 1.];
+const SLOW: [f32; 4] = [0.9, 0.9, 0.3, 1.];
+const BAD: [f32; 4] = [0.9, 0.3, 0.3, 1.];
+const REFERENCE: [f32; 4] = [1., 1., 1., 0.4];
+
+pub(crate) struct DebugOverlay {
+    batch: Batcher,
+    font: resources::Texture,
+    // seconds
+    frame_times: VecDeque<f64>,
+    last_frame: Option<f64>,
+}
+
+impl DebugOverlay {
+    pub fn new(ctx: &mut dyn RenderingBackend) -> DebugOverlay {
+        // glyphs side by side, white on transparent
+        let width = GLYPH_WIDTH * FONT.len();
+        let mut pixels = vec![0u8; width * GLYPH_HEIGHT * 4];
+        for (glyph, rows) in FONT.iter().enumerate() {
+            for (y, row) in rows.iter().enumerate() {
+                for x in 0..GLYPH_WIDTH {
+                    if row & (1 << (GLYPH_WIDTH - 1 - x)) != 0 {
+                        let offset = (y * width + glyph * GLYPH_WIDTH + x) * 4;
+                        pixels[offset..offset + 4].copy_from_slice(&[255; 4]);
+                    }
+                }
+            }
+        }
+        let font = ctx.new_texture_from_data_and_format(
+            &pixels,
+            TextureParams {
+                width: width as _,
+                height: GLYPH_HEIGHT as _,
+                format: TextureFormat::RGBA8,
+                min_filter: FilterMode::Nearest,
+                mag_filter: FilterMode::Nearest,
+                ..Default::default()
+            },
+        );
+
+        DebugOverlay {
+            batch: Batcher::new(ctx),
+            font: resources::Texture::new(ctx, font),
+            frame_times: VecDeque::with_capacity(HISTORY),
+            last_frame: None,
+        }
+    }
+
+    /// Draw into the default framebuffer, `stats` are the ones of the frame
+    /// being committed, without the overlay itself.
+    pub fn draw(&mut self, ctx: &mut dyn RenderingBackend, stats: &RenderingStats) {
+        let now = crate::date::now();
+        if let Some(last_frame) = self.last_frame {
+            if self.frame_times.len() == HISTORY {
+                self.frame_times.pop_front();
+            }
+            self.frame_times.push_back(now - last_frame);
+        }
+        self.last_frame = Some(now);
+
+        let total: f64 = self.frame_times.iter().sum();
+        let average = if self.frame_times.is_empty() {
+            0.
+        } else {
+            total / self.frame_times.len() as f64
+        };
+        let fps = if average > 0. { 1. / average } else { 0. };
+        let worst = self.frame_times.iter().copied().fold(0., f64::max);
+
+        let info = ctx.info();
+        let backend = match info.backend {
+            Backend::Metal => "METAL".to_string(),
+            Backend::OpenGl => {
+                let (major, minor) = info.gl_context.version;
+                let es = if info.gl_context.gles { " ES" } else { "" };
+                format!("OPENGL{es} {major}.{minor}")
+            }
+        };
+        let megabytes = |bytes: usize| bytes as f64 / (1024. * 1024.);
+        let frame = &stats.frame;
+        let resources = &stats.resources;
+        let lines = [
+            format!(
+                "FPS {fps:.1}  {:.1} MS (MAX {:.1})",
+                average * 1000.,
+                worst * 1000.
+            ),
+            format!(
+                "DRAW CALLS {}  PIPELINES {}",
+                frame.draw_calls, frame.pipeline_switches
+            ),
+            format!(
+                "TEXTURES {}  {:.1} MB",
+                resources.textures,
+                megabytes(resources.texture_memory)
+            ),
+            format!(
+                "BUFFERS {}  {:.1} MB",
+                resources.buffers,
+                megabytes(resources.buffer_memory)
+            ),
+            backend,
+        ];
+
+        // font pixels are scaled to stay readable on high dpi screens
+        let scale = (crate::window::dpi_scale() * 2.).round().max(1.);
+        let margin = 4. * scale;
+        let line_height = (GLYPH_HEIGHT as f32 + 2.) * scale;
+        let graph_height = 30. * scale;
+        let columns = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+        let text_width = columns as f32 * (GLYPH_WIDTH as f32 + 1.) * scale;
+        let width = text_width.max(HISTORY as f32 * scale) + margin * 2.;
+        let height = lines.len() as f32 * line_height + graph_height + margin * 3.;
+
+        let batch = &mut self.batch;
+        batch.set_texture(None);
+        batch.rect(0., 0., width, height, BACKGROUND);
+
+        // frame time graph, 33 ms at the top and a line at 16.7 ms
+        let graph_top = margin + lines.len() as f32 * line_height + margin;
+        let graph_bottom = graph_top + graph_height;
+        let bar_width = (width - margin * 2.) / HISTORY as f32;
+        for (i, &time) in self.frame_times.iter().enumerate() {
+            let color = if time <= 1. / 58. {
+                GOOD
+            } else if time <= 1. / 29. {
+                SLOW
+            } else {
+                BAD
+            };
+            let bar_height = (time as f32 * 30.).min(1.) * graph_height;
+            let x = margin + i as f32 * bar_width;
+            batch.rect(x, graph_bottom - bar_height, bar_width, bar_height, color);
+        }
+        batch.rect(
+            margin,
+            graph_bottom - graph_height / 2.,
+            width - margin * 2.,
+            scale.max(1.),
+            REFERENCE,
+        );
+
+        batch.set_texture(Some(self.font.id()));
+        for (i, line) in lines.iter().enumerate() {
+            let y = margin + i as f32 * line_height;
+            for (column, c) in line.chars().enumerate() {
+                let glyph = CHARSET.find(c.to_ascii_uppercase()).unwrap_or(0);
+                if glyph == 0 {
+                    continue;
+                }
+                let u = glyph as f32 / FONT.len() as f32;
+                batch.rect_uv(
+                    margin + column as f32 * (GLYPH_WIDTH as f32 + 1.) * scale,
+                    y,
+                    GLYPH_WIDTH as f32 * scale,
+                    GLYPH_HEIGHT as f32 * scale,
+                    [u, 0., 1. / FONT.len() as f32, 1.],
+                    TEXT,
+                );
+            }
+        }
+
+        ctx.begin_default_pass(PassAction::Nothing);
+        batch.flush(ctx);
+        ctx.end_render_pass();
+    }
+}
+
+/// Forwards everything to the user's handler, except the key combination
+/// toggling the overlay.
+struct ToggleHandler {
+    inner: Box<dyn EventHandler>,
+    toggle: (KeyCode, KeyMods),
+    // the toggle key was pressed, its key up is not forwarded either
+    pressed: bool,
+}
+
+pub(crate) fn wrap_event_handler(
+    event_handler: Box<dyn EventHandler>,
+    toggle: Option<(KeyCode, KeyMods)>,
+) -> Box<dyn EventHandler> {
+    match toggle {
+        Some(toggle) => Box::new(ToggleHandler {
+            inner: event_handler,
+            toggle,
+            pressed: false,
+        }),
+        None => event_handler,
+    }
+}
+
+impl EventHandler for ToggleHandler {
+    fn update(&mut self) {
+        self.inner.update();
+    }
+    fn draw(&mut self) {
+        self.inner.draw();
+    }
+    fn resize_event(&mut self, width: f32, height: f32) {
+        self.inner.resize_event(width, height);
+    }
+    fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        self.inner.mouse_motion_event(x, y);
+    }
+    fn mouse_wheel_event(&mut self, x: f32, y: f32) {
+        self.inner.mouse_wheel_event(x, y);
+    }
+    fn mouse_button_down_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.inner.mouse_button_down_event(button, x, y);
+    }
+    fn mouse_button_up_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.inner.mouse_button_up_event(button, x, y);
+    }
+    fn char_event(&mut self, character: char, keymods: KeyMods, repeat: bool) {
+        self.inner.char_event(character, keymods, repeat);
+    }
+    fn key_down_event(&mut self, keycode: KeyCode, keymods: KeyMods, repeat: bool) {
+        if (keycode, keymods) == self.toggle {
+            if !repeat {
+                set_enabled(!enabled());
+            }
+            self.pressed = true;
+            return;
+        }
+        self.inner.key_down_event(keycode, keymods, repeat);
+    }
+    fn key_up_event(&mut self, keycode: KeyCode, keymods: KeyMods) {
+        if self.pressed && keycode == self.toggle.0 {
+            self.pressed = false;
+            return;
+        }
+        self.inner.key_up_event(keycode, keymods);
+    }
+    fn touch_event(&mut self, phase: TouchPhase, id: u64, x: f32, y: f32) {
+        self.inner.touch_event(phase, id, x, y);
+    }
+    fn raw_mouse_motion(&mut self, dx: f32, dy: f32) {
+        self.inner.raw_mouse_motion(dx, dy);
+    }
+    fn window_minimized_event(&mut self) {
+        self.inner.window_minimized_event();
+    }
+    fn window_restored_event(&mut self) {
+        self.inner.window_restored_event();
+    }
+    fn quit_requested_event(&mut self) {
+        self.inner.quit_requested_event();
+    }
+    fn files_dropped_event(&mut self) {
+        self.inner.files_dropped_event();
+    }
+    fn context_restored_event(&mut self) {
+        self.inner.context_restored_event();
+    }
+}
@@ -0,0 +1,264 @@
+//! Owned wrappers over `RenderingBackend` handles.
+//!
+//! Raw ids (`TextureId`, `BufferId`, ...) are plain `Copy` handles and have to be
+//! deleted explicitly with a `&mut RenderingBackend` in hand. The types here own
+//! the handle instead: dropping them, from anywhere and on any thread, pushes the
+//! handle into the context's `DeletionQueue`, and the actual deletion happens
+//! in the next `commit_frame`.
+//!
+//! ```ignore
+//! let texture = ctx.new_texture_from_rgba8(4, 4, &pixels);
+//! let texture = Texture::new(&*ctx, texture);
+//! let bindings = Bindings {
+//!     vertex_buffers: vec![vertex_buffer.id()],
+//!     index_buffer: index_buffer.id(),
+//!     images: vec![texture.id()],
+//! };
+//! // later, no ctx required
+//! drop(texture);
+//! ```
+
+use super::*;
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// A resource waiting in a `DeletionQueue`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Deletion {
+    Texture(TextureId),
+    Buffer(BufferId),
+    Shader(ShaderId),
+    Pipeline(Pipeline),
+    RenderPass(RenderPass),
+}
+
+/// Resources to be deleted in the next `commit_frame` of the context
+/// the queue came from, see `RenderingBackend::deletion_queue`.
+///
+/// Cloning the queue is cheap, all the clones share the same storage.
+#[derive(Clone, Debug, Default)]
+pub struct DeletionQueue(Arc<Mutex<Vec<Deletion>>>);
+
+impl DeletionQueue {
+    pub fn push(&self, deletion: Deletion) {
+        // a Vec can't be left in a broken state, and drop should never panic
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(deletion);
+    }
+
+    /// Number of resources waiting for the next `commit_frame`.
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Delete all the queued resources, in the order they were queued.
+    pub fn drain<B: RenderingBackend + ?Sized>(&self, ctx: &mut B) {
+        let deletions = std::mem::take(&mut *self.0.lock().unwrap_or_else(PoisonError::into_inner));
+        for deletion in deletions {
+            match deletion {
+                Deletion::Texture(texture) => ctx.delete_texture(texture),
+                Deletion::Buffer(buffer) => ctx.delete_buffer(buffer),
+                Deletion::Shader(shader) => ctx.delete_shader(shader),
+                Deletion::Pipeline(pipeline) => ctx.delete_pipeline(pipeline),
+                Deletion::RenderPass(pass) => ctx.delete_render_pass(pass),
+            }
+        }
+    }
+}
+
+macro_rules! owned_resource {
+    ($(#[$attr:meta])* $name:ident, $id:ty, $deletion:ident) => {
+        $(#[$attr])*
+        #[derive(Debug)]
+        pub struct $name {
+            id: $id,
+            queue: DeletionQueue,
+        }
+
+        impl $name {
+            /// Take the ownership of `id`, it is going to be deleted
+            /// in the first `commit_frame` after the drop.
+            ///
+            /// `id` should come from `ctx` and should not be deleted by hand.
+            pub fn new(ctx: &dyn RenderingBackend, id: $id) -> $name {
+                $name {
+                    id,
+                    queue: ctx.deletion_queue(),
+                }
+            }
+
+            pub fn id(&self) -> $id {
+                self.id
+            }
+
+            /// Give up the ownership, the returned id is not going to be deleted automatically.
+            pub fn into_raw(self) -> $id {
+                let id = self.id;
+                std::mem::forget(self);
+                id
+            }
+        }
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                self.queue.push(Deletion::$deletion(self.id));
+            }
+        }
+    };
+}
+
+owned_resource!(
+    /// Owned `TextureId`.
+    Texture,
+    TextureId,
+    Texture
+);
+owned_resource!(
+    /// Owned `BufferId`.
+    Buffer,
+    BufferId,
+    Buffer
+);
+owned_resource!(
+    /// Owned `ShaderId`.
+    Shader,
+    ShaderId,
+    Shader
+);
+owned_resource!(
+    /// Owned `Pipeline`.
+    ///
+    /// Pipelines do not own their shader, keep the `Shader` alive as well.
+    PipelineHandle,
+    Pipeline,
+    Pipeline
+);
+
+/// Owned `RenderPass` together with its attachments.
+///
+/// Only the pass is queued on drop, `delete_render_pass` deletes the attachments with it.
+#[derive(Debug)]
+pub struct RenderTarget {
+    pass: RenderPass,
+    color: Vec<TextureId>,
+    depth: Option<TextureId>,
+    queue: DeletionQueue,
+}
+
+impl RenderTarget {
+    /// Create a render pass with one color texture and an optional depth texture,
+    /// both with `TextureAccess::RenderTarget`.
+    pub fn new(
+        ctx: &mut dyn RenderingBackend,
+        color: TextureParams,
+        depth: Option<TextureParams>,
+    ) -> RenderTarget {
+        let color = ctx.new_render_texture(color);
+        let depth = depth.map(|params| ctx.new_render_texture(params));
+        RenderTarget::from_textures(ctx, vec![color], depth)
+    }
+
+    /// Create a render pass over already created textures, with `color.len()`
+    /// color attachments. The textures are owned by the target from now on,
+    /// they are deleted with the pass.
+    pub fn from_textures(
+        ctx: &mut dyn RenderingBackend,
+        color: Vec<TextureId>,
+        depth: Option<TextureId>,
+    ) -> RenderTarget {
+        let pass = ctx.new_render_pass_mrt(&color, None, depth);
+        RenderTarget {
+            pass,
+            color,
+            depth,
+            queue: ctx.deletion_queue(),
+        }
+    }
+
+    pub fn pass(&self) -> RenderPass {
+        self.pass
+    }
+
+    pub fn color_textures(&self) -> &[TextureId] {
+        &self.color
+    }
+
+    /// The first color attachment.
+    pub fn texture(&self) -> TextureId {
+        self.color[0]
+    }
+
+    pub fn depth_texture(&self) -> Option<TextureId> {
+        self.depth
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        self.queue.push(Deletion::RenderPass(self.pass));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(queue: &DeletionQueue) -> Vec<Deletion> {
+        queue.0.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn queue_is_shared() {
+        let queue = DeletionQueue::default();
+        let clone = queue.clone();
+        assert!(queue.is_empty());
+        clone.push(Deletion::Buffer(BufferId(1)));
+        queue.push(Deletion::Shader(ShaderId(2)));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(
+            queued(&clone),
+            [Deletion::Buffer(BufferId(1)), Deletion::Shader(ShaderId(2))]
+        );
+    }
+
+    #[test]
+    fn owned_resources_drop() {
+        let queue = DeletionQueue::default();
+        let texture = TextureId(TextureIdInner::Managed(3));
+        drop(Texture {
+            id: texture,
+            queue: queue.clone(),
+        });
+        drop(PipelineHandle {
+            id: Pipeline(4),
+            queue: queue.clone(),
+        });
+        let buffer = Buffer {
+            id: BufferId(5),
+            queue: queue.clone(),
+        };
+        assert_eq!(buffer.into_raw(), BufferId(5));
+        assert_eq!(
+            queued(&queue),
+            [Deletion::Texture(texture), Deletion::Pipeline(Pipeline(4))]
+        );
+    }
+
+    #[test]
+    fn render_target_queues_only_the_pass() {
+        let queue = DeletionQueue::default();
+        drop(RenderTarget {
+            pass: RenderPass(6),
+            color: vec![TextureId(TextureIdInner::Managed(7))],
+            depth: Some(TextureId(TextureIdInner::Managed(8))),
+            queue: queue.clone(),
+        });
+        assert_eq!(queued(&queue), [Deletion::RenderPass(RenderPass(6))]);
+    }
+}
@@ -55,6 +55,10 @@ impl<T> ResourceManager<T> {
         // Let it crash if the resource is not found
         self.resources.remove(&id).unwrap()
     }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.resources.values()
+    }
 }
 
 impl<T> Index<usize> for ResourceManager<T> {
@@ -144,6 +148,23 @@ pub mod window {
         Box::new(GlContext::new())
     }
 
+    /// Start a background thread with a GL context sharing objects with the main one,
+    /// to create textures and buffers without stalling the main loop.
+    /// Finished resources are handed to the rendering backend with
+    /// `RenderingBackend::poll_loader`.
+    ///
+    /// Returns None if the platform can't create shared contexts, or if the
+    /// shared context can't be made current on the loader thread.
+    /// For now only EGL and GLX contexts can be shared: Android, OHOS, Wayland
+    /// and X11, Windows, macOS, iOS and WebGL always return None.
+    pub fn new_loader() -> Option<Loader> {
+        let context = {
+            let d = native_display().lock().unwrap();
+            d.shared_gl_context.as_ref()?()?
+        };
+        Loader::new(context)
+    }
+
     /// The current framebuffer size in pixels
     /// NOTE: [High DPI Rendering](../conf/index.html#high-dpi-rendering)
     pub fn screen_size() -> (f32, f32) {
@@ -170,6 +191,16 @@ pub mod window {
         d.blocking_event_loop
     }
 
+    /// Show or hide the debug overlay: FPS, frame time graph, draw calls and GPU memory,
+    /// drawn by the rendering backend on top of the default framebuffer in `commit_frame`.
+    pub fn set_debug_overlay(shown: bool) {
+        crate::graphics::overlay::set_enabled(shown);
+    }
+
+    pub fn debug_overlay() -> bool {
+        crate::graphics::overlay::enabled()
+    }
+
     /// This function simply quits the application without
     /// giving the user a chance to intervene. Usually this might
     /// be called when the user clicks the 'Ok' button in a 'Really Quit?'
@@ -419,6 +450,10 @@ pub fn start<F>(conf: conf::Conf, f: F)
 where
     F: 'static + FnOnce() -> Box<dyn EventHandler>,
 {
+    graphics::overlay::set_enabled(conf.platform.debug_overlay);
+    let debug_overlay_toggle = conf.platform.debug_overlay_toggle;
+    let f = move || graphics::overlay::wrap_event_handler(f(), debug_overlay_toggle);
+
     #[cfg(target_env = "ohos")]
     unsafe {
         native::ohos::run(conf, f);
@@ -23,6 +23,13 @@ pub(crate) struct NativeDisplayData {
     pub clipboard: Box<dyn Clipboard>,
     pub dropped_files: DroppedFiles,
     pub blocking_event_loop: bool,
+    pub context_loss_recovery: bool,
+    // the default framebuffer was created with an sRGB color space
+    pub srgb_framebuffer: bool,
+    // incremented each time GL context is recreated after a context loss
+    pub context_generation: u32,
+    // None if the platform can't create GL contexts sharing objects with the main one
+    pub shared_gl_context: Option<SharedGlContextFactory>,
 
     #[cfg(target_vendor = "apple")]
     pub view: crate::native::apple::frameworks::ObjcId,
@@ -56,6 +63,10 @@ impl NativeDisplayData {
             clipboard,
             dropped_files: Default::default(),
             blocking_event_loop: false,
+            context_loss_recovery: false,
+            srgb_framebuffer: false,
+            context_generation: 0,
+            shared_gl_context: None,
             #[cfg(target_vendor = "apple")]
             gfx_api: crate::conf::AppleGfxApi::OpenGl,
             #[cfg(target_vendor = "apple")]
@@ -78,6 +89,14 @@ pub(crate) enum Request {
     ShowKeyboard(bool),
 }
 
+/// GL context sharing objects with the main one, used by `graphics::Loader`.
+pub(crate) trait SharedGlContext: Send {
+    /// Make the context current on the calling thread.
+    fn make_current(&mut self) -> bool;
+}
+
+pub(crate) type SharedGlContextFactory = Box<dyn Fn() -> Option<Box<dyn SharedGlContext>> + Send>;
+
 pub trait Clipboard: Send + Sync {
     fn get(&mut self) -> Option<String>;
     fn set(&mut self, string: &str);
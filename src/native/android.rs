@@ -1,4 +1,5 @@
 use crate::{
+    conf::GlConfig,
     event::{EventHandler, KeyCode, KeyMods, TouchPhase},
     native::{
         egl::{self, LibEgl},
@@ -131,6 +132,10 @@ struct MainThreadState {
     egl_display: egl::EGLDisplay,
     egl_config: egl::EGLConfig,
     egl_context: egl::EGLContext,
+    // kept to recreate the context the same way after a context loss
+    gl_config: GlConfig,
+    // Conf::srgb_framebuffer, for the surfaces created on resume
+    srgb_framebuffer: bool,
     surface: egl::EGLSurface,
     window: *mut ndk_sys::ANativeWindow,
     event_handler: Box<dyn EventHandler>,
@@ -141,6 +146,15 @@ struct MainThreadState {
 }
 
 impl MainThreadState {
+    fn egl_share(&self) -> egl::EglShare {
+        egl::EglShare {
+            display: self.egl_display,
+            config: self.egl_config,
+            context: self.egl_context,
+            gl_config: self.gl_config,
+        }
+    }
+
     unsafe fn destroy_surface(&mut self) {
         (self.libegl.eglMakeCurrent)(
             self.egl_display,
@@ -161,12 +175,14 @@ impl MainThreadState {
             self.destroy_surface();
         }
 
-        self.surface = (self.libegl.eglCreateWindowSurface)(
+        self.surface = egl::create_window_surface(
+            &mut self.libegl,
             self.egl_display,
             self.egl_config,
             window as _,
-            std::ptr::null_mut(),
-        );
+            self.srgb_framebuffer,
+        )
+        .0;
 
         assert!(!self.surface.is_null());
 
@@ -177,9 +193,39 @@ impl MainThreadState {
             self.egl_context,
         );
 
+        if res == 0 && (self.libegl.eglGetError)() == egl::EGL_CONTEXT_LOST as _ {
+            self.restore_context();
+            return;
+        }
         assert!(res != 0);
     }
 
+    unsafe fn restore_context(&mut self) {
+        (self.libegl.eglDestroyContext)(self.egl_display, self.egl_context);
+        self.egl_context = egl::create_egl_context_with_config(
+            &mut self.libegl,
+            self.egl_display,
+            self.egl_config,
+            std::ptr::null_mut(),
+            &self.gl_config,
+        )
+        .expect("Cant recreate EGL context");
+        let res = (self.libegl.eglMakeCurrent)(
+            self.egl_display,
+            self.surface,
+            self.surface,
+            self.egl_context,
+        );
+        assert!(res != 0);
+
+        {
+            let mut d = crate::native_display().lock().unwrap();
+            d.context_generation += 1;
+            d.shared_gl_context = Some(self.egl_share().factory());
+        }
+        self.event_handler.context_restored_event();
+    }
+
     fn process_message(&mut self, msg: Message) {
         match msg {
             Message::SurfaceCreated { window } => unsafe {
@@ -258,7 +304,11 @@ impl MainThreadState {
             self.event_handler.draw();
 
             unsafe {
-                (self.libegl.eglSwapBuffers)(self.egl_display, self.surface);
+                if (self.libegl.eglSwapBuffers)(self.egl_display, self.surface) == 0
+                    && (self.libegl.eglGetError)() == egl::EGL_CONTEXT_LOST as _
+                {
+                    self.restore_context();
+                }
             }
         }
     }
@@ -424,6 +474,7 @@ where
             std::ptr::null_mut(), /* EGL_DEFAULT_DISPLAY */
             conf.platform.framebuffer_alpha,
             conf.sample_count,
+            &conf.platform.gl_config,
         )
         .expect("Cant create EGL context");
 
@@ -435,11 +486,12 @@ where
             (libegl.eglGetProcAddress)(name.as_ptr() as _)
         });
 
-        let surface = (libegl.eglCreateWindowSurface)(
+        let (surface, srgb_framebuffer) = egl::create_window_surface(
+            &mut libegl,
             egl_display,
             egl_config,
             window as _,
-            std::ptr::null_mut(),
+            conf.srgb_framebuffer,
         );
 
         if (libegl.eglMakeCurrent)(egl_display, surface, surface, egl_context) == 0 {
@@ -451,6 +503,17 @@ where
         crate::set_or_replace_display(NativeDisplayData {
             high_dpi: conf.high_dpi,
             blocking_event_loop: conf.platform.blocking_event_loop,
+            context_loss_recovery: conf.platform.context_loss_recovery,
+            srgb_framebuffer,
+            shared_gl_context: Some(
+                egl::EglShare {
+                    display: egl_display,
+                    config: egl_config,
+                    context: egl_context,
+                    gl_config: conf.platform.gl_config,
+                }
+                .factory(),
+            ),
             ..NativeDisplayData::new(screen_width as _, screen_height as _, tx_fn, clipboard)
         });
 
@@ -460,6 +523,8 @@ where
             egl_display,
             egl_config,
             egl_context,
+            gl_config: conf.platform.gl_config,
+            srgb_framebuffer: conf.srgb_framebuffer,
             surface,
             window,
             event_handler,
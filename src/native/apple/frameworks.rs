@@ -561,11 +561,13 @@ impl MTLClearColor {
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub enum MTLPixelFormat {
     BGRA8Unorm = 80,
+    BGRA8Unorm_sRGB = 81,
     Depth32Float = 252,
     Stencil8 = 253,
     Depth24Unorm_Stencil8 = 255,
     Depth32Float_Stencil8 = 260,
     RGBA8Unorm = 70,
+    RGBA8Unorm_sRGB = 71,
     RGBA16Float = 115,
 }
 
@@ -701,6 +703,20 @@ pub enum MTLBlendOperation {
     Max = 4,
 }
 
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum MTLTriangleFillMode {
+    Fill = 0,
+    Lines = 1,
+}
+
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum MTLDepthClipMode {
+    Clip = 0,
+    Clamp = 1,
+}
+
 #[repr(u64)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum MTLPrimitiveType {
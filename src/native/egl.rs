@@ -17,7 +17,10 @@ pub type EGLNativeWindowType = ::core::ffi::c_ulong;
 pub use core::ptr::null_mut;
 use std::fmt::Display;
 
+use crate::conf::{GlConfig, GlProfile};
+
 pub const EGL_SUCCESS: u32 = 12288;
+pub const EGL_CONTEXT_LOST: u32 = 12302;
 
 pub const EGL_WINDOW_BIT: u32 = 4;
 
@@ -34,6 +37,28 @@ pub const EGL_HEIGHT: u32 = 12374;
 pub const EGL_SURFACE_TYPE: u32 = 12339;
 pub const EGL_NONE: u32 = 12344;
 pub const EGL_CONTEXT_CLIENT_VERSION: u32 = 12440;
+pub const EGL_RENDERABLE_TYPE: u32 = 0x3040;
+pub const EGL_OPENGL_BIT: u32 = 0x0008;
+pub const EGL_OPENGL_ES2_BIT: u32 = 0x0004;
+pub const EGL_OPENGL_ES3_BIT: u32 = 0x0040;
+pub const EGL_OPENGL_API: u32 = 0x30A2;
+pub const EGL_OPENGL_ES_API: u32 = 0x30A0;
+pub const EGL_CONTEXT_MAJOR_VERSION: u32 = 0x3098;
+pub const EGL_CONTEXT_MINOR_VERSION: u32 = 0x30FB;
+pub const EGL_CONTEXT_FLAGS_KHR: u32 = 0x30FC;
+pub const EGL_CONTEXT_OPENGL_DEBUG_BIT_KHR: u32 = 0x0001;
+pub const EGL_CONTEXT_OPENGL_ROBUST_ACCESS_BIT_KHR: u32 = 0x0004;
+pub const EGL_CONTEXT_OPENGL_PROFILE_MASK: u32 = 0x30FD;
+pub const EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT: u32 = 0x0001;
+pub const EGL_CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT: u32 = 0x0002;
+pub const EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY: u32 = 0x31BD;
+pub const EGL_CONTEXT_OPENGL_ROBUST_ACCESS_EXT: u32 = 0x30BF;
+pub const EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_EXT: u32 = 0x3138;
+pub const EGL_LOSE_CONTEXT_ON_RESET: u32 = 0x31BF;
+pub const EGL_CONTEXT_OPENGL_NO_ERROR_KHR: u32 = 0x31B3;
+pub const EGL_EXTENSIONS: u32 = 0x3055;
+pub const EGL_GL_COLORSPACE_KHR: u32 = 0x309D;
+pub const EGL_GL_COLORSPACE_SRGB_KHR: u32 = 0x3089;
 
 pub type NativeDisplayType = EGLNativeDisplayType;
 pub type NativePixmapType = EGLNativePixmapType;
@@ -142,6 +167,7 @@ crate::declare_module! {
         EGLint,
     ) -> EGLBoolean,
     pub fn eglSwapInterval(EGLDisplay, EGLint) -> EGLBoolean,
+    pub fn eglBindAPI(::core::ffi::c_uint) -> EGLBoolean,
     ...
     ...
 }
@@ -167,11 +193,98 @@ impl std::error::Error for EglError {}
 
 pub struct Egl {}
 
+fn is_desktop_gl(gl_config: &GlConfig) -> bool {
+    matches!(gl_config.profile, GlProfile::Core | GlProfile::Compatibility)
+}
+
+/// `eglCreateContext` attributes for `gl_config`, without the optional flags
+/// the driver may refuse when `flags` is false.
+fn context_attributes(gl_config: &GlConfig, flags: bool) -> Vec<EGLint> {
+    let desktop = is_desktop_gl(gl_config);
+    let (major, minor) = match (gl_config.version, gl_config.profile) {
+        (Some(version), _) => version,
+        (None, GlProfile::Core) => (3, 2),
+        (None, GlProfile::Compatibility) => (2, 1),
+        (None, _) => (2, 0),
+    };
+    // only what was asked for, EGL 1.4 without EGL_KHR_create_context
+    // refuses the attributes it does not know
+    let mut attributes = vec![EGL_CONTEXT_MAJOR_VERSION as EGLint, major as EGLint];
+    if minor != 0 {
+        attributes.extend([EGL_CONTEXT_MINOR_VERSION as EGLint, minor as EGLint]);
+    }
+    match gl_config.profile {
+        GlProfile::Core => attributes.extend([
+            EGL_CONTEXT_OPENGL_PROFILE_MASK as EGLint,
+            EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT as EGLint,
+        ]),
+        GlProfile::Compatibility => attributes.extend([
+            EGL_CONTEXT_OPENGL_PROFILE_MASK as EGLint,
+            EGL_CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT as EGLint,
+        ]),
+        _ => {}
+    }
+    if flags {
+        let mut context_flags = 0;
+        if gl_config.debug {
+            context_flags |= EGL_CONTEXT_OPENGL_DEBUG_BIT_KHR;
+        }
+        if gl_config.robustness && desktop {
+            context_flags |= EGL_CONTEXT_OPENGL_ROBUST_ACCESS_BIT_KHR;
+            attributes.extend([
+                EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY as EGLint,
+                EGL_LOSE_CONTEXT_ON_RESET as EGLint,
+            ]);
+        } else if gl_config.robustness {
+            // GLES robustness comes from EGL_EXT_create_context_robustness instead
+            attributes.extend([
+                EGL_CONTEXT_OPENGL_ROBUST_ACCESS_EXT as EGLint,
+                1,
+                EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_EXT as EGLint,
+                EGL_LOSE_CONTEXT_ON_RESET as EGLint,
+            ]);
+        }
+        if context_flags != 0 {
+            attributes.extend([EGL_CONTEXT_FLAGS_KHR as EGLint, context_flags as EGLint]);
+        }
+        if gl_config.no_error && !gl_config.debug && !gl_config.robustness {
+            attributes.extend([EGL_CONTEXT_OPENGL_NO_ERROR_KHR as EGLint, 1]);
+        }
+    }
+    attributes.push(EGL_NONE as EGLint);
+    attributes
+}
+
+/// `eglCreateWindowSurface`, in the sRGB color space if `srgb` is asked for and
+/// `EGL_KHR_gl_colorspace` is available. Returns whether the surface is sRGB.
+pub unsafe fn create_window_surface(
+    egl: &mut LibEgl,
+    display: EGLDisplay,
+    config: EGLConfig,
+    window: EGLNativeWindowType,
+    srgb: bool,
+) -> (EGLSurface, bool) {
+    if srgb && has_extension(egl, display, "EGL_KHR_gl_colorspace") {
+        let attributes = [
+            EGL_GL_COLORSPACE_KHR as EGLint,
+            EGL_GL_COLORSPACE_SRGB_KHR as EGLint,
+            EGL_NONE as EGLint,
+        ];
+        let surface = (egl.eglCreateWindowSurface)(display, config, window, attributes.as_ptr());
+        if !surface.is_null() {
+            return (surface, true);
+        }
+    }
+    let surface = (egl.eglCreateWindowSurface)(display, config, window, std::ptr::null());
+    (surface, false)
+}
+
 pub unsafe fn create_egl_context(
     egl: &mut LibEgl,
     display: *mut std::ffi::c_void,
     alpha: bool,
     sample_count: i32,
+    gl_config: &GlConfig,
 ) -> Result<(EGLContext, EGLConfig, EGLDisplay), EglError> {
     let display = (egl.eglGetDisplay)(display as _);
     if display.is_null() {
@@ -184,18 +297,29 @@ pub unsafe fn create_egl_context(
     }
 
     let alpha_size = if alpha { 8 } else { 0 };
+    let depth_size = gl_config.depth_bits.unwrap_or(16) as u32;
+    let stencil_size = gl_config.stencil_bits.unwrap_or(0) as u32;
+    // EGL_RENDERABLE_TYPE is left at its default unless something newer than GLES2 is asked for
+    let renderable_type = match (gl_config.profile, gl_config.version) {
+        (GlProfile::Core | GlProfile::Compatibility, _) => EGL_OPENGL_BIT,
+        (_, Some((major, _))) if major >= 3 => EGL_OPENGL_ES3_BIT,
+        _ => EGL_OPENGL_ES2_BIT,
+    };
     #[rustfmt::skip]
-    let cfg_attributes = [
+    let mut cfg_attributes = vec![
         EGL_SURFACE_TYPE, EGL_WINDOW_BIT,
         EGL_RED_SIZE, 8,
         EGL_GREEN_SIZE, 8,
         EGL_BLUE_SIZE, 8,
         EGL_ALPHA_SIZE, alpha_size,
-        EGL_DEPTH_SIZE, 16,
-        EGL_STENCIL_SIZE, 0,
+        EGL_DEPTH_SIZE, depth_size,
+        EGL_STENCIL_SIZE, stencil_size,
         EGL_SAMPLES, sample_count as u32,
-        EGL_NONE,
     ];
+    if renderable_type != EGL_OPENGL_ES2_BIT {
+        cfg_attributes.extend([EGL_RENDERABLE_TYPE, renderable_type]);
+    }
+    cfg_attributes.push(EGL_NONE);
     let mut available_cfgs: Vec<EGLConfig> = vec![null_mut(); 32];
     let mut cfg_count = 0;
 
@@ -218,16 +342,19 @@ pub unsafe fn create_egl_context(
         let mut b: i32 = 0;
         let mut a: i32 = 0;
         let mut d: i32 = 0;
+        let mut s: i32 = 0;
         if (egl.eglGetConfigAttrib)(display, *c, EGL_RED_SIZE as _, &mut r) == 1
             && (egl.eglGetConfigAttrib)(display, *c, EGL_GREEN_SIZE as _, &mut g) == 1
             && (egl.eglGetConfigAttrib)(display, *c, EGL_BLUE_SIZE as _, &mut b) == 1
             && (egl.eglGetConfigAttrib)(display, *c, EGL_ALPHA_SIZE as _, &mut a) == 1
             && (egl.eglGetConfigAttrib)(display, *c, EGL_DEPTH_SIZE as _, &mut d) == 1
+            && (egl.eglGetConfigAttrib)(display, *c, EGL_STENCIL_SIZE as _, &mut s) == 1
             && r == 8
             && g == 8
             && b == 8
             && (alpha_size == 0 || a == alpha_size as _)
-            && d == 16
+            && d == depth_size as _
+            && s == stencil_size as _
         {
             exact_cfg_found = true;
             config = *c;
@@ -237,16 +364,133 @@ pub unsafe fn create_egl_context(
     if !exact_cfg_found {
         config = available_cfgs[0];
     }
-    let ctx_attributes = [EGL_CONTEXT_CLIENT_VERSION, 2, EGL_NONE];
-    let context = (egl.eglCreateContext)(
-        display,
-        config,
-        /* EGL_NO_CONTEXT */ null_mut(),
-        ctx_attributes.as_ptr() as _,
-    );
-    if context.is_null() {
+    let context = create_egl_context_with_config(egl, display, config, null_mut(), gl_config)?;
+
+    Ok((context, config, display))
+}
+
+/// Create a new context for an already initialized display and config,
+/// used to replace a lost context or to share objects with `share_context`.
+pub unsafe fn create_egl_context_with_config(
+    egl: &mut LibEgl,
+    display: EGLDisplay,
+    config: EGLConfig,
+    share_context: EGLContext,
+    gl_config: &GlConfig,
+) -> Result<EGLContext, EglError> {
+    // the bound API is per thread, the loader creates its context on its own one
+    let api = if is_desktop_gl(gl_config) {
+        EGL_OPENGL_API
+    } else {
+        EGL_OPENGL_ES_API
+    };
+    if (egl.eglBindAPI)(api) == 0 {
         return Err(EglError::CreateContextFailed);
     }
 
-    Ok((context, config, display))
+    for flags in [true, false] {
+        let ctx_attributes = context_attributes(gl_config, flags);
+        let context =
+            (egl.eglCreateContext)(display, config, share_context, ctx_attributes.as_ptr() as _);
+        if !context.is_null() {
+            return Ok(context);
+        }
+    }
+    Err(EglError::CreateContextFailed)
+}
+
+/// Everything needed to create contexts sharing objects with the main one.
+#[derive(Clone, Copy)]
+pub struct EglShare {
+    pub display: EGLDisplay,
+    pub config: EGLConfig,
+    pub context: EGLContext,
+    pub gl_config: GlConfig,
+}
+unsafe impl Send for EglShare {}
+
+impl EglShare {
+    pub(crate) fn factory(self) -> crate::native::SharedGlContextFactory {
+        Box::new(move || unsafe { self.create() })
+    }
+
+    unsafe fn create(&self) -> Option<Box<dyn crate::native::SharedGlContext>> {
+        let mut egl = LibEgl::try_load().ok()?;
+        let context = create_egl_context_with_config(
+            &mut egl,
+            self.display,
+            self.config,
+            self.context,
+            &self.gl_config,
+        )
+        .ok()?;
+        // without EGL_KHR_surfaceless_context a context can't be made current with
+        // EGL_NO_SURFACE, a 1x1 pbuffer is bound instead
+        let surface = if has_extension(&mut egl, self.display, "EGL_KHR_surfaceless_context") {
+            null_mut()
+        } else {
+            let attributes = [
+                EGL_WIDTH as EGLint,
+                1,
+                EGL_HEIGHT as EGLint,
+                1,
+                EGL_NONE as EGLint,
+            ];
+            let surface =
+                (egl.eglCreatePbufferSurface)(self.display, self.config, attributes.as_ptr());
+            if surface.is_null() {
+                (egl.eglDestroyContext)(self.display, context);
+                return None;
+            }
+            surface
+        };
+        Some(Box::new(SharedEglContext {
+            egl,
+            display: self.display,
+            context,
+            surface,
+        }))
+    }
+}
+
+/// `EGL_EXTENSIONS` of `display` contains `name`.
+unsafe fn has_extension(egl: &mut LibEgl, display: EGLDisplay, name: &str) -> bool {
+    let extensions = (egl.eglQueryString)(display, EGL_EXTENSIONS as _);
+    !extensions.is_null()
+        && std::ffi::CStr::from_ptr(extensions)
+            .to_string_lossy()
+            .split_whitespace()
+            .any(|extension| extension == name)
+}
+
+/// Context used only to create GL objects, never drawn into a framebuffer.
+/// `surface` is EGL_NO_SURFACE with EGL_KHR_surfaceless_context, a 1x1 pbuffer otherwise.
+struct SharedEglContext {
+    egl: LibEgl,
+    display: EGLDisplay,
+    context: EGLContext,
+    surface: EGLSurface,
+}
+unsafe impl Send for SharedEglContext {}
+
+impl crate::native::SharedGlContext for SharedEglContext {
+    fn make_current(&mut self) -> bool {
+        unsafe {
+            (self.egl.eglMakeCurrent)(self.display, self.surface, self.surface, self.context) != 0
+        }
+    }
+}
+
+impl Drop for SharedEglContext {
+    fn drop(&mut self) {
+        unsafe {
+            if (self.egl.eglGetCurrentContext)() == self.context {
+                (self.egl.eglMakeCurrent)(self.display, null_mut(), null_mut(), null_mut());
+            }
+            (self.egl.eglDestroyContext)(self.display, self.context);
+            if !self.surface.is_null() {
+                (self.egl.eglDestroySurface)(self.display, self.surface);
+            }
+        }
+    }
 }
@@ -440,7 +440,12 @@ unsafe fn create_opengl_view(screen_rect: NSRect, _sample_count: i32, high_dpi:
     }
 }
 
-unsafe fn create_metal_view(screen_rect: NSRect, _sample_count: i32, _high_dpi: bool) -> View {
+unsafe fn create_metal_view(
+    screen_rect: NSRect,
+    _sample_count: i32,
+    _high_dpi: bool,
+    srgb: bool,
+) -> View {
     let mtk_view_obj: ObjcId = msg_send![define_glk_or_mtk_view(class!(MTKView)), alloc];
     let mtk_view_obj: ObjcId = msg_send![mtk_view_obj, initWithFrame: screen_rect];
 
@@ -459,6 +464,12 @@ unsafe fn create_metal_view(screen_rect: NSRect, _sample_count: i32, _high_dpi:
     let device = MTLCreateSystemDefaultDevice();
     msg_send_![mtk_view_obj, setDevice: device];
     msg_send_![mtk_view_obj, setUserInteractionEnabled: YES];
+    if srgb {
+        msg_send_![
+            mtk_view_obj,
+            setColorPixelFormat: MTLPixelFormat::BGRA8Unorm_sRGB
+        ];
+    }
 
     View {
         view: mtk_view_obj,
@@ -514,9 +525,12 @@ pub fn define_app_delegate() -> *const Class {
                 AppleGfxApi::OpenGl => {
                     create_opengl_view(screen_rect, conf.sample_count, conf.high_dpi)
                 }
-                AppleGfxApi::Metal => {
-                    create_metal_view(screen_rect, conf.sample_count, conf.high_dpi)
-                }
+                AppleGfxApi::Metal => create_metal_view(
+                    screen_rect,
+                    conf.sample_count,
+                    conf.high_dpi,
+                    conf.srgb_framebuffer,
+                ),
             };
 
             let (textfield_dlg, textfield) = {
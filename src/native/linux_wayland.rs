@@ -1134,6 +1134,7 @@ where
             wdisplay as *mut _,
             conf.platform.framebuffer_alpha,
             conf.sample_count,
+            &conf.platform.gl_config,
         )
         .unwrap();
 
@@ -1153,12 +1154,14 @@ where
             );
         }
 
-        let egl_surface = (libegl.eglCreateWindowSurface)(
+        let (egl_surface, srgb_framebuffer) = egl::create_window_surface(
+            &mut libegl,
             egl_display,
             config,
             display.egl_window as _,
-            std::ptr::null_mut(),
+            conf.srgb_framebuffer,
         );
+        crate::native_display().lock().unwrap().srgb_framebuffer = srgb_framebuffer;
 
         if egl_surface.is_null() {
             // == EGL_NO_SURFACE
@@ -1176,6 +1179,16 @@ where
             let name = std::ffi::CString::new(proc).unwrap();
             (libegl.eglGetProcAddress)(name.as_ptr() as _)
         });
+        let share = egl::EglShare {
+            display: egl_display,
+            config,
+            context,
+            gl_config: conf.platform.gl_config,
+        };
+        crate::native_display()
+            .try_lock()
+            .unwrap()
+            .shared_gl_context = Some(share.factory());
 
         display.decorations =
             decorations::Decorations::new(&mut display, conf.platform.wayland_decorations);
@@ -445,7 +445,8 @@ where
             .libx11
             .create_window(display.root, display.display, visual, depth, conf);
 
-    let (glx_context, glx_window) = glx.create_context(display.display, display.window);
+    let (glx_context, glx_window) =
+        glx.create_context(&mut display.libx11, display.display, display.window);
     glx.swap_interval(
         display.display,
         glx_window,
@@ -473,6 +474,8 @@ where
         high_dpi: conf.high_dpi,
         dpi_scale: display.libx11.update_system_dpi(display.display),
         blocking_event_loop: conf.platform.blocking_event_loop,
+        srgb_framebuffer: glx.srgb_framebuffer,
+        shared_gl_context: Some(glx.share(display.display, glx_context).factory()),
         ..NativeDisplayData::new(w, h, tx, clipboard)
     });
     if conf.fullscreen {
@@ -544,11 +547,17 @@ where
         display.display as *mut _,
         conf.platform.framebuffer_alpha,
         conf.sample_count,
+        &conf.platform.gl_config,
     )
     .unwrap();
 
-    let egl_surface =
-        (egl_lib.eglCreateWindowSurface)(egl_display, config, display.window, std::ptr::null_mut());
+    let (egl_surface, srgb_framebuffer) = egl::create_window_surface(
+        &mut egl_lib,
+        egl_display,
+        config,
+        display.window,
+        conf.srgb_framebuffer,
+    );
 
     if egl_surface.is_null() {
         // == EGL_NO_SURFACE
@@ -583,6 +592,16 @@ where
         high_dpi: conf.high_dpi,
         dpi_scale: display.libx11.update_system_dpi(display.display),
         blocking_event_loop: conf.platform.blocking_event_loop,
+        srgb_framebuffer,
+        shared_gl_context: Some(
+            egl::EglShare {
+                display: egl_display,
+                config,
+                context,
+                gl_config: conf.platform.gl_config,
+            }
+            .factory(),
+        ),
         ..NativeDisplayData::new(w, h, tx, clipboard)
     });
     if conf.fullscreen {
@@ -2,18 +2,25 @@
 
 use super::{libx11::*, X11Error};
 
-use crate::native::module;
+use crate::{
+    conf::{GlConfig, GlProfile},
+    native::module,
+};
 
 pub type GLXContext = *mut ();
 pub type GLXFBConfig = *mut ();
 pub type GLXWindow = XID;
 pub type GLXDrawable = XID;
+pub type GLXPbuffer = XID;
 
 pub const GLX_VENDOR: libc::c_int = 1 as libc::c_int;
 pub const GLX_RENDER_TYPE: libc::c_int = 0x8011 as libc::c_int;
 pub const GLX_RGBA_BIT: libc::c_int = 0x1 as libc::c_int;
 pub const GLX_DRAWABLE_TYPE: libc::c_int = 0x8010 as libc::c_int;
 pub const GLX_WINDOW_BIT: libc::c_int = 0x1 as libc::c_int;
+pub const GLX_PBUFFER_BIT: libc::c_int = 0x4 as libc::c_int;
+pub const GLX_PBUFFER_HEIGHT: libc::c_int = 0x8040 as libc::c_int;
+pub const GLX_PBUFFER_WIDTH: libc::c_int = 0x8041 as libc::c_int;
 pub const GLX_RED_SIZE: libc::c_int = 8 as libc::c_int;
 pub const GLX_GREEN_SIZE: libc::c_int = 9 as libc::c_int;
 pub const GLX_BLUE_SIZE: libc::c_int = 10 as libc::c_int;
@@ -29,6 +36,14 @@ pub const GLX_CONTEXT_PROFILE_MASK_ARB: libc::c_int = 0x9126 as libc::c_int;
 pub const GLX_CONTEXT_CORE_PROFILE_BIT_ARB: libc::c_int = 0x1 as libc::c_int;
 pub const GLX_CONTEXT_FLAGS_ARB: libc::c_int = 0x2094 as libc::c_int;
 pub const GLX_CONTEXT_FORWARD_COMPATIBLE_BIT_ARB: libc::c_int = 0x2 as libc::c_int;
+pub const GLX_CONTEXT_DEBUG_BIT_ARB: libc::c_int = 0x1 as libc::c_int;
+pub const GLX_CONTEXT_ROBUST_ACCESS_BIT_ARB: libc::c_int = 0x4 as libc::c_int;
+pub const GLX_CONTEXT_COMPATIBILITY_PROFILE_BIT_ARB: libc::c_int = 0x2 as libc::c_int;
+pub const GLX_CONTEXT_ES2_PROFILE_BIT_EXT: libc::c_int = 0x4 as libc::c_int;
+pub const GLX_CONTEXT_RESET_NOTIFICATION_STRATEGY_ARB: libc::c_int = 0x8256 as libc::c_int;
+pub const GLX_LOSE_CONTEXT_ON_RESET_ARB: libc::c_int = 0x8252 as libc::c_int;
+pub const GLX_CONTEXT_OPENGL_NO_ERROR_ARB: libc::c_int = 0x31B3 as libc::c_int;
+pub const GLX_FRAMEBUFFER_SRGB_CAPABLE_ARB: libc::c_int = 0x20B2 as libc::c_int;
 
 pub type GLenum = ::core::ffi::c_uint;
 pub type GLboolean = ::core::ffi::c_uchar;
@@ -50,6 +65,12 @@ pub type PFNGLXDESTROYWINDOWPROC =
     Option<unsafe extern "C" fn(_: *mut Display, _: GLXWindow) -> ()>;
 pub type PFNGLXSWAPBUFFERSPROC =
     Option<unsafe extern "C" fn(_: *mut Display, _: GLXDrawable) -> ()>;
+pub type PFNGLXCREATEPBUFFERPROC = Option<
+    unsafe extern "C" fn(_: *mut Display, _: GLXFBConfig, _: *const libc::c_int) -> GLXPbuffer,
+>;
+pub type PFNGLXDESTROYPBUFFERPROC =
+    Option<unsafe extern "C" fn(_: *mut Display, _: GLXPbuffer) -> ()>;
+pub type PFNGLXGETCURRENTCONTEXTPROC = Option<unsafe extern "C" fn() -> GLXContext>;
 
 pub type PFNGLXGETFBCONFIGATTRIBPROC = Option<
     unsafe extern "C" fn(
@@ -122,6 +143,9 @@ pub struct LibGlx {
     pub glxCreateNewContext: PFNGLXCREATENEWCONTEXTPROC,
     pub glxCreateWindow: PFNGLXCREATEWINDOWPROC,
     pub glxDestroyWindow: PFNGLXDESTROYWINDOWPROC,
+    pub glxCreatePbuffer: PFNGLXCREATEPBUFFERPROC,
+    pub glxDestroyPbuffer: PFNGLXDESTROYPBUFFERPROC,
+    pub glxGetCurrentContext: PFNGLXGETCURRENTCONTEXTPROC,
     pub glxGetProcAddress: PFNGLXGETPROCADDRESSPROC,
     pub glxGetProcAddressARB: PFNGLXGETPROCADDRESSPROC,
     pub glxGetVisualFromFBConfig: PFNGLXGETVISUALFROMFBCONFIGPROC,
@@ -144,6 +168,9 @@ impl LibGlx {
                 glxCreateNewContext: module.get_symbol("glXCreateNewContext").ok(),
                 glxCreateWindow: module.get_symbol("glXCreateWindow").ok(),
                 glxDestroyWindow: module.get_symbol("glXDestroyWindow").ok(),
+                glxCreatePbuffer: module.get_symbol("glXCreatePbuffer").ok(),
+                glxDestroyPbuffer: module.get_symbol("glXDestroyPbuffer").ok(),
+                glxGetCurrentContext: module.get_symbol("glXGetCurrentContext").ok(),
                 glxGetProcAddress: module.get_symbol("glXGetProcAddress").ok(),
                 glxGetProcAddressARB: module.get_symbol("glXGetProcAddressARB").ok(),
                 glxGetVisualFromFBConfig: module.get_symbol("glXGetVisualFromFBConfig").ok(),
@@ -175,6 +202,7 @@ pub struct GLFBConfig {
     pub stencil_bits: libc::c_int,
     pub samples: libc::c_int,
     pub doublebuffer: bool,
+    pub srgb: bool,
     pub handle: libc::c_ulong,
 }
 
@@ -189,6 +217,7 @@ impl Default for GLFBConfig {
             stencil_bits: -1,
             samples: -1,
             doublebuffer: false,
+            srgb: false,
             handle: 0,
         }
     }
@@ -203,11 +232,16 @@ pub struct GlxExtensions {
 
 pub struct Glx {
     pub libgl: LibGlx,
+    gl_config: GlConfig,
     multisample: bool,
     extensions: GlxExtensions,
     fbconfig: GLXFBConfig,
+    // attributes the main context was created with, reused for shared ones
+    context_attributes: Vec<libc::c_int>,
     pub visual: *mut Visual,
     pub depth: i32,
+    /// The chosen GLXFBConfig is sRGB capable and `Conf::srgb_framebuffer` asked for it.
+    pub srgb_framebuffer: bool,
 }
 
 impl Glx {
@@ -244,10 +278,9 @@ impl Glx {
         let extensions = std::ffi::CStr::from_ptr(exts).to_str().unwrap().to_owned();
 
         let multisample = extensions.contains("GLX_ARB_multisample");
-        // let glx_ARB_framebuffer_sRGB =
-        //     _sapp_glx_extsupported(b"GLX_ARB_framebuffer_sRGB\x00", exts);
-        // _sapp_glx_EXT_framebuffer_sRGB =
-        //     _sapp_glx_extsupported(b"GLX_EXT_framebuffer_sRGB\x00", exts);
+        let srgb = conf.srgb_framebuffer
+            && (extensions.contains("GLX_ARB_framebuffer_sRGB")
+                || extensions.contains("GLX_EXT_framebuffer_sRGB"));
         // if _sapp_glx_extsupported(b"GLX_ARB_create_context\x00", exts) {
         //     _sapp_glx_CreateContextAttribsARB =
         //         _sapp_glx_getprocaddr(b"glXCreateContextAttribsARB\x00");
@@ -256,18 +289,22 @@ impl Glx {
         // _sapp_glx_ARB_create_context_profile =
         //     _sapp_glx_extsupported(b"GLX_ARB_create_context_profile\x00", exts);
 
-        let fbconfig = choose_fbconfig(
-            &mut libgl,
-            libx11,
-            display,
-            screen,
-            multisample,
-            conf.sample_count,
-        );
+        let fbconfig =
+            choose_fbconfig(&mut libgl, libx11, display, screen, multisample, srgb, conf);
         assert!(
             !fbconfig.is_null(),
             "GLX: Failed to find a suitable GLXFBConfig"
         );
+        let srgb_framebuffer = srgb && {
+            let mut value = 0;
+            (libgl.glxGetFBConfigAttrib.unwrap())(
+                display,
+                fbconfig,
+                GLX_FRAMEBUFFER_SRGB_CAPABLE_ARB,
+                &mut value,
+            );
+            value != 0
+        };
 
         let result = libgl.glxGetVisualFromFBConfig.unwrap()(display, fbconfig);
         assert!(
@@ -305,16 +342,68 @@ impl Glx {
 
         Ok(Glx {
             libgl,
+            gl_config: conf.platform.gl_config,
             multisample,
             visual,
             depth,
             extensions,
             fbconfig,
+            context_attributes: vec![],
+            srgb_framebuffer,
         })
     }
 
+    /// `glXCreateContextAttribsARB` attributes for `Platform::gl_config`, without
+    /// the optional flags the driver may refuse when `flags` is false.
+    fn context_attributes(&self, flags: bool) -> Vec<libc::c_int> {
+        let gl_config = &self.gl_config;
+        let (major, minor) = match (gl_config.version, gl_config.profile) {
+            (Some(version), _) => version,
+            (None, GlProfile::Core) => (3, 2),
+            (None, GlProfile::Es) => (2, 0),
+            (None, _) => (2, 1),
+        };
+        let mut attributes = vec![
+            GLX_CONTEXT_MAJOR_VERSION_ARB,
+            major as _,
+            GLX_CONTEXT_MINOR_VERSION_ARB,
+            minor as _,
+        ];
+        let profile = match gl_config.profile {
+            GlProfile::PlatformDefault => None,
+            GlProfile::Core => Some(GLX_CONTEXT_CORE_PROFILE_BIT_ARB),
+            GlProfile::Compatibility => Some(GLX_CONTEXT_COMPATIBILITY_PROFILE_BIT_ARB),
+            GlProfile::Es => Some(GLX_CONTEXT_ES2_PROFILE_BIT_EXT),
+        };
+        if let Some(profile) = profile {
+            attributes.extend([GLX_CONTEXT_PROFILE_MASK_ARB, profile]);
+        }
+        if flags {
+            let mut context_flags = 0;
+            if gl_config.debug {
+                context_flags |= GLX_CONTEXT_DEBUG_BIT_ARB;
+            }
+            if gl_config.robustness {
+                context_flags |= GLX_CONTEXT_ROBUST_ACCESS_BIT_ARB;
+                attributes.extend([
+                    GLX_CONTEXT_RESET_NOTIFICATION_STRATEGY_ARB,
+                    GLX_LOSE_CONTEXT_ON_RESET_ARB,
+                ]);
+            }
+            if context_flags != 0 {
+                attributes.extend([GLX_CONTEXT_FLAGS_ARB, context_flags]);
+            }
+            if gl_config.no_error && !gl_config.debug && !gl_config.robustness {
+                attributes.extend([GLX_CONTEXT_OPENGL_NO_ERROR_ARB, 1]);
+            }
+        }
+        attributes.extend([0, 0]);
+        attributes
+    }
+
     pub unsafe fn create_context(
         &mut self,
+        libx11: &mut LibX11,
         display: *mut Display,
         window: Window,
     ) -> (GLXContext, GLXWindow) {
@@ -322,26 +411,28 @@ impl Glx {
             panic!("GLX: ARB_create_context and ARB_create_context_profile required");
         }
 
-        // _sapp_x11_grab_error_handler(libx11);
-        let attribs: [libc::c_int; 8] = [
-            GLX_CONTEXT_MAJOR_VERSION_ARB,
-            2,
-            GLX_CONTEXT_MINOR_VERSION_ARB,
-            1,
-            GLX_CONTEXT_FLAGS_ARB,
-            GLX_CONTEXT_CORE_PROFILE_BIT_ARB,
-            0,
-            0,
-        ];
-        let glx_ctx = self.extensions.glxCreateContextAttribsARB.unwrap()(
-            display,
-            self.fbconfig,
-            std::ptr::null_mut(),
-            true as _,
-            attribs.as_ptr(),
-        );
+        // a refused attribute is an X error, which would abort the application
+        // with the default handler
+        libx11.grab_error_handler();
+        let mut glx_ctx = std::ptr::null_mut();
+        for flags in [true, false] {
+            let attribs = self.context_attributes(flags);
+            glx_ctx = self.extensions.glxCreateContextAttribsARB.unwrap()(
+                display,
+                self.fbconfig,
+                std::ptr::null_mut(),
+                true as _,
+                attribs.as_ptr(),
+            );
+            // errors are reported asynchronously
+            (libx11.XSync)(display, false as _);
+            if !glx_ctx.is_null() {
+                self.context_attributes = attribs;
+                break;
+            }
+        }
+        libx11.release_error_handler(display);
         assert!(!glx_ctx.is_null(), "GLX: failed to create GL context");
-        // _sapp_x11_release_error_handler(libx11);
 
         let glx_window =
             self.libgl.glxCreateWindow.unwrap()(display, self.fbconfig, window, std::ptr::null());
@@ -350,6 +441,18 @@ impl Glx {
         (glx_ctx, glx_window)
     }
 
+    /// Everything needed to create contexts sharing objects with `ctx`,
+    /// created by `create_context`.
+    pub fn share(&self, display: *mut Display, ctx: GLXContext) -> GlxShare {
+        GlxShare {
+            display,
+            fbconfig: self.fbconfig,
+            context: ctx,
+            attributes: self.context_attributes.clone(),
+            create_context: self.extensions.glxCreateContextAttribsARB,
+        }
+    }
+
     pub unsafe fn destroy_context(
         &mut self,
         display: *mut Display,
@@ -394,6 +497,93 @@ impl Glx {
     }
 }
 
+/// Everything needed to create contexts sharing objects with the main one.
+pub struct GlxShare {
+    display: *mut Display,
+    fbconfig: GLXFBConfig,
+    context: GLXContext,
+    attributes: Vec<libc::c_int>,
+    create_context: PFNGLXCREATECONTEXTATTRIBSARBPROC,
+}
+// XInitThreads is called before the display is opened
+unsafe impl Send for GlxShare {}
+
+impl GlxShare {
+    pub(crate) fn factory(self) -> crate::native::SharedGlContextFactory {
+        Box::new(move || unsafe { self.create() })
+    }
+
+    unsafe fn create(&self) -> Option<Box<dyn crate::native::SharedGlContext>> {
+        let libgl = LibGlx::try_load().ok()?;
+        let create_pbuffer = libgl.glxCreatePbuffer?;
+        let context = self.create_context?(
+            self.display,
+            self.fbconfig,
+            self.context,
+            true as _,
+            self.attributes.as_ptr(),
+        );
+        if context.is_null() {
+            return None;
+        }
+        // GL 3.0+ contexts can be made current without a drawable, older ones
+        // need a 1x1 pbuffer, when the window's config supports them
+        let mut drawable_type = 0;
+        (libgl.glxGetFBConfigAttrib.unwrap())(
+            self.display,
+            self.fbconfig,
+            GLX_DRAWABLE_TYPE,
+            &mut drawable_type,
+        );
+        let pbuffer = if drawable_type & GLX_PBUFFER_BIT != 0 {
+            let attributes = [GLX_PBUFFER_WIDTH, 1, GLX_PBUFFER_HEIGHT, 1, 0];
+            create_pbuffer(self.display, self.fbconfig, attributes.as_ptr())
+        } else {
+            0
+        };
+        Some(Box::new(SharedGlxContext {
+            libgl,
+            display: self.display,
+            context,
+            pbuffer,
+        }))
+    }
+}
+
+/// Context used only to create GL objects, never drawn into a framebuffer.
+/// `pbuffer` is 0 when the window's config has no pbuffer support.
+struct SharedGlxContext {
+    libgl: LibGlx,
+    display: *mut Display,
+    context: GLXContext,
+    pbuffer: GLXPbuffer,
+}
+unsafe impl Send for SharedGlxContext {}
+
+impl crate::native::SharedGlContext for SharedGlxContext {
+    fn make_current(&mut self) -> bool {
+        unsafe {
+            (self.libgl.glxMakeCurrent.unwrap())(self.display, self.pbuffer, self.context) != 0
+        }
+    }
+}
+
+impl Drop for SharedGlxContext {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(get_current_context) = self.libgl.glxGetCurrentContext {
+                if get_current_context() == self.context {
+                    (self.libgl.glxMakeCurrent.unwrap())(self.display, 0, std::ptr::null_mut());
+                }
+            }
+            (self.libgl.glxDestroyContext.unwrap())(self.display, self.context);
+            if self.pbuffer != 0 {
+                (self.libgl.glxDestroyPbuffer.unwrap())(self.display, self.pbuffer);
+            }
+        }
+    }
+}
+
 // TODO: this code came a long way from sokol_app, better reimplement it!
 unsafe fn choose_fbconfig(
     libgl: &mut LibGlx,
@@ -401,8 +591,11 @@ unsafe fn choose_fbconfig(
     display: *mut Display,
     screen: i32,
     multisample: bool,
-    desired_sample_count: i32,
+    srgb: bool,
+    conf: &crate::conf::Conf,
 ) -> GLXFBConfig {
+    let gl_config = &conf.platform.gl_config;
+    let desired_sample_count = conf.sample_count;
     let mut native_count: libc::c_int = 0;
     let mut usable_count;
     let mut trust_window_bit = true;
@@ -454,6 +647,9 @@ unsafe fn choose_fbconfig(
         if multisample {
             u.samples = glx_attrib(n, GLX_SAMPLES)
         }
+        if srgb {
+            u.srgb = glx_attrib(n, GLX_FRAMEBUFFER_SRGB_CAPABLE_ARB) != 0;
+        }
         u.handle = n as libc::c_ulong;
         usable_configs.push(u);
         usable_count += 1
@@ -467,9 +663,10 @@ unsafe fn choose_fbconfig(
         desired.green_bits = 8;
         desired.blue_bits = 8;
         desired.alpha_bits = 8;
-        desired.depth_bits = 24;
-        desired.stencil_bits = 8;
+        desired.depth_bits = gl_config.depth_bits.map_or(24, |bits| bits as _);
+        desired.stencil_bits = gl_config.stencil_bits.map_or(8, |bits| bits as _);
         desired.doublebuffer = true;
+        desired.srgb = srgb;
         desired.samples = if desired_sample_count > 1 {
             desired_sample_count
         } else {
@@ -524,6 +721,9 @@ pub unsafe extern "C" fn gl_choose_fbconfig(
 
                 missing += 1;
             }
+            if (*desired).srgb && !(*current).srgb {
+                missing += 1;
+            }
 
             // These polynomials make many small channel size differences matter
             //  less than one large channel size difference
@@ -820,14 +820,24 @@ fn get_window_payload(this: &Object) -> &mut MacosDisplay {
     }
 }
 
-unsafe fn create_metal_view(_: &mut MacosDisplay, sample_count: i32, _: bool) -> ObjcId {
+unsafe fn create_metal_view(
+    _: &mut MacosDisplay,
+    sample_count: i32,
+    _: bool,
+    srgb: bool,
+) -> ObjcId {
     let mtl_device_obj = MTLCreateSystemDefaultDevice();
     let view_class = define_metal_view_class();
     let view: ObjcId = msg_send![view_class, alloc];
     let view: ObjcId = msg_send![view, init];
 
     let () = msg_send![view, setDevice: mtl_device_obj];
-    let () = msg_send![view, setColorPixelFormat: MTLPixelFormat::BGRA8Unorm];
+    let color_format = if srgb {
+        MTLPixelFormat::BGRA8Unorm_sRGB
+    } else {
+        MTLPixelFormat::BGRA8Unorm
+    };
+    let () = msg_send![view, setColorPixelFormat: color_format];
     let () = msg_send![
         view,
         setDepthStencilPixelFormat: MTLPixelFormat::Depth32Float_Stencil8
@@ -1130,7 +1140,12 @@ where
 
     let view = match conf.platform.apple_gfx_api {
         AppleGfxApi::OpenGl => create_opengl_view(&mut display, conf.sample_count, conf.high_dpi),
-        AppleGfxApi::Metal => create_metal_view(&mut display, conf.sample_count, conf.high_dpi),
+        AppleGfxApi::Metal => create_metal_view(
+            &mut display,
+            conf.sample_count,
+            conf.high_dpi,
+            conf.srgb_framebuffer,
+        ),
     };
     {
         let mut d = native_display().lock().unwrap();
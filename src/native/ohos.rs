@@ -5,6 +5,7 @@ use ohos_xcomponent_sys::{
 };
 use ohos_hilog_binding::{hilog_error, hilog_fatal, hilog_info};
 use crate::{
+    conf::GlConfig,
     event::{EventHandler, KeyCode, KeyMods, TouchPhase},
     native::{
         egl::{self, LibEgl},
@@ -65,6 +66,10 @@ struct MainThreadState {
     egl_display: egl::EGLDisplay,
     egl_config: egl::EGLConfig,
     egl_context: egl::EGLContext,
+    // kept to recreate the context the same way after a context loss
+    gl_config: GlConfig,
+    // Conf::srgb_framebuffer, for the surfaces created on resume
+    srgb_framebuffer: bool,
     surface: egl::EGLSurface,
     window:  WindowRaw,
     event_handler: Box<dyn EventHandler>,
@@ -75,6 +80,15 @@ struct MainThreadState {
 }
 
 impl MainThreadState {
+    fn egl_share(&self) -> egl::EglShare {
+        egl::EglShare {
+            display: self.egl_display,
+            config: self.egl_config,
+            context: self.egl_context,
+            gl_config: self.gl_config,
+        }
+    }
+
     unsafe fn destroy_surface(&mut self) {
         (self.libegl.eglMakeCurrent)(
             self.egl_display,
@@ -91,12 +105,14 @@ impl MainThreadState {
         if self.surface.is_null() == false {
             self.destroy_surface();
         }
-        self.surface = (self.libegl.eglCreateWindowSurface)(
+        self.surface = egl::create_window_surface(
+            &mut self.libegl,
             self.egl_display,
             self.egl_config,
             window.0 as _,
-            std::ptr::null_mut(),
-        );
+            self.srgb_framebuffer,
+        )
+        .0;
 
         if self.surface.is_null() {
             let error = (self.libegl.eglGetError)();
@@ -111,10 +127,50 @@ impl MainThreadState {
             self.egl_context,
         );
 
+        if res == 0 {
+            let error = (self.libegl.eglGetError)();
+            if error == egl::EGL_CONTEXT_LOST as _ {
+                self.restore_context();
+            } else {
+                hilog_fatal!(format!("Failed to make EGL context current, EGL error: {}", error));
+            }
+        }
+    }
+
+    unsafe fn restore_context(&mut self) {
+        hilog_info!("EGL context lost, creating a new one");
+        (self.libegl.eglDestroyContext)(self.egl_display, self.egl_context);
+        self.egl_context = match egl::create_egl_context_with_config(
+            &mut self.libegl,
+            self.egl_display,
+            self.egl_config,
+            std::ptr::null_mut(),
+            &self.gl_config,
+        ) {
+            Ok(context) => context,
+            Err(err) => {
+                hilog_fatal!(format!("Failed to recreate EGL context: {}", err));
+                return;
+            }
+        };
+        let res = (self.libegl.eglMakeCurrent)(
+            self.egl_display,
+            self.surface,
+            self.surface,
+            self.egl_context,
+        );
         if res == 0 {
             let error = (self.libegl.eglGetError)();
             hilog_fatal!(format!("Failed to make EGL context current, EGL error: {}", error));
+            return;
         }
+
+        {
+            let mut d = crate::native_display().lock().unwrap();
+            d.context_generation += 1;
+            d.shared_gl_context = Some(self.egl_share().factory());
+        }
+        self.event_handler.context_restored_event();
     }
 
     fn process_message(&mut self, msg: Message) {
@@ -191,8 +247,11 @@ impl MainThreadState {
             self.update_requested = false;
             self.event_handler.draw();
             unsafe {
-                (self.libegl.eglSwapBuffers)(self.egl_display, self.surface);
-                
+                if (self.libegl.eglSwapBuffers)(self.egl_display, self.surface) == 0
+                    && (self.libegl.eglGetError)() == egl::EGL_CONTEXT_LOST as _
+                {
+                    self.restore_context();
+                }
             }
         }
     }
@@ -305,6 +364,7 @@ where
             std::ptr::null_mut(), /* EGL_DEFAULT_DISPLAY */
             true, // force set rgba 8888 for ohos
             conf.sample_count,
+            &conf.platform.gl_config,
         )
         .expect("Cant create EGL context");
 
@@ -316,11 +376,12 @@ where
             (libegl.eglGetProcAddress)(name.as_ptr() as _)
         });
 
-        let surface = (libegl.eglCreateWindowSurface)(
+        let (surface, srgb_framebuffer) = egl::create_window_surface(
+            &mut libegl,
             egl_display,
             egl_config,
             window.0 as _,
-            std::ptr::null_mut(),
+            conf.srgb_framebuffer,
         );
 
         if (libegl.eglMakeCurrent)(egl_display, surface, surface, egl_context) == 0 {
@@ -332,6 +393,17 @@ where
         crate::set_or_replace_display(NativeDisplayData {
             high_dpi: conf.high_dpi,
             blocking_event_loop: conf.platform.blocking_event_loop,
+            context_loss_recovery: conf.platform.context_loss_recovery,
+            srgb_framebuffer,
+            shared_gl_context: Some(
+                egl::EglShare {
+                    display: egl_display,
+                    config: egl_config,
+                    context: egl_context,
+                    gl_config: conf.platform.gl_config,
+                }
+                .factory(),
+            ),
             ..NativeDisplayData::new(screen_width as _, screen_height as _, tx_fn, clipboard)
         });
 
@@ -341,6 +413,8 @@ where
             egl_display,
             egl_config,
             egl_context,
+            gl_config: conf.platform.gl_config,
+            srgb_framebuffer: conf.srgb_framebuffer,
             surface,
             window: WindowRaw(std::ptr::null_mut()), // Will be set when we create the surface
             event_handler,
@@ -86,6 +86,7 @@ where
     let clipboard = Box::new(Clipboard);
     crate::set_display(NativeDisplayData {
         blocking_event_loop: conf.platform.blocking_event_loop,
+        context_loss_recovery: conf.platform.context_loss_recovery,
         dpi_scale,
         ..NativeDisplayData::new(w, h, tx, clipboard)
     });
@@ -340,6 +341,14 @@ pub extern "C" fn focus(has_focus: bool) {
     });
 }
 
+#[no_mangle]
+pub extern "C" fn context_restored() {
+    crate::native_display().lock().unwrap().context_generation += 1;
+    tl_event_handler(|event_handler| {
+        event_handler.context_restored_event();
+    });
+}
+
 #[no_mangle]
 pub extern "C" fn on_files_dropped_start() {
     let mut d = crate::native_display().lock().unwrap();
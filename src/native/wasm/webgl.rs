@@ -32,6 +32,8 @@ pub type GLdouble = f64;
 pub type GLclampd = f64;
 
 pub const GL_INT_2_10_10_10_REV: u32 = 0x8D9F;
+pub const GL_UNSIGNED_INT_2_10_10_10_REV: u32 = 0x8368;
+pub const GL_HALF_FLOAT: u32 = 0x140B;
 pub const GL_PROGRAM_POINT_SIZE: u32 = 0x8642;
 pub const GL_STENCIL_ATTACHMENT: u32 = 0x8D20;
 pub const GL_DEPTH_ATTACHMENT: u32 = 0x8D00;
@@ -50,6 +52,8 @@ pub const GL_TEXTURE_CUBE_MAP_POSITIVE_Z: u32 = 0x8519;
 pub const GL_TEXTURE_CUBE_MAP: u32 = 0x8513;
 pub const GL_FUNC_SUBTRACT: u32 = 0x800A;
 pub const GL_FUNC_REVERSE_SUBTRACT: u32 = 0x800B;
+pub const GL_MIN: u32 = 0x8007;
+pub const GL_MAX: u32 = 0x8008;
 pub const GL_CONSTANT_COLOR: u32 = 0x8001;
 pub const GL_DECR_WRAP: u32 = 0x8508;
 pub const GL_LINEAR_MIPMAP_LINEAR: u32 = 0x2703;
@@ -60,6 +64,11 @@ pub const GL_TEXTURE_CUBE_MAP_NEGATIVE_Y: u32 = 0x8518;
 pub const GL_LINK_STATUS: u32 = 0x8B82;
 pub const GL_TEXTURE_CUBE_MAP_POSITIVE_Y: u32 = 0x8517;
 pub const GL_SAMPLE_ALPHA_TO_COVERAGE: u32 = 0x809E;
+pub const GL_FRONT_AND_BACK: u32 = 0x0408;
+pub const GL_FILL: u32 = 0x1B02;
+pub const GL_LINE: u32 = 0x1B01;
+pub const GL_DEPTH_CLAMP: u32 = 0x864F;
+pub const GL_PRIMITIVE_RESTART_FIXED_INDEX: u32 = 0x8D69;
 pub const GL_RGBA16F: u32 = 0x881A;
 pub const GL_CONSTANT_ALPHA: u32 = 0x8003;
 pub const GL_READ_FRAMEBUFFER: u32 = 0x8CA8;
@@ -77,6 +86,9 @@ pub const GL_ONE: u32 = 1;
 pub const GL_NEAREST_MIPMAP_LINEAR: u32 = 0x2702;
 pub const GL_RGB10_A2: u32 = 0x8059;
 pub const GL_RGBA8: u32 = 0x8058;
+pub const GL_SRGB8: u32 = 0x8C41;
+pub const GL_SRGB8_ALPHA8: u32 = 0x8C43;
+pub const GL_FRAMEBUFFER_SRGB: u32 = 0x8DB9;
 pub const GL_COLOR_ATTACHMENT1: u32 = 0x8CE1;
 pub const GL_RGBA4: u32 = 0x8056;
 pub const GL_RGB8: u32 = 0x8051;
@@ -239,9 +251,40 @@ pub const GL_MAX_CUBE_MAP_TEXTURE_SIZE: u32 = 0x851C;
 pub const GL_MAX_3D_TEXTURE_SIZE: u32 = 0x8073;
 pub const GL_MAX_ARRAY_TEXTURE_LAYERS: u32 = 0x88FF;
 pub const GL_MAX_VERTEX_ATTRIBS: u32 = 0x8869;
+pub const GL_MAX_TEXTURE_IMAGE_UNITS: u32 = 0x8872;
+pub const GL_MAX_SAMPLES: u32 = 0x8D57;
+pub const GL_MAX_DRAW_BUFFERS: u32 = 0x8824;
+pub const GL_MAX_VERTEX_UNIFORM_VECTORS: u32 = 0x8DFB;
+pub const GL_MAX_FRAGMENT_UNIFORM_VECTORS: u32 = 0x8DFD;
+pub const GL_CONTEXT_FLAGS: u32 = 0x821E;
+pub const GL_CONTEXT_FLAG_DEBUG_BIT: u32 = 0x2;
+pub const GL_CONTEXT_FLAG_ROBUST_ACCESS_BIT: u32 = 0x4;
+pub const GL_CONTEXT_FLAG_NO_ERROR_BIT: u32 = 0x8;
+pub const GL_CONTEXT_PROFILE_MASK: u32 = 0x9126;
+pub const GL_CONTEXT_CORE_PROFILE_BIT: u32 = 0x1;
+pub const GL_DEPTH_BITS: u32 = 0x0D56;
+pub const GL_STENCIL_BITS: u32 = 0x0D57;
+pub const GL_FRAMEBUFFER_ATTACHMENT_DEPTH_SIZE: u32 = 0x2216;
+pub const GL_FRAMEBUFFER_ATTACHMENT_STENCIL_SIZE: u32 = 0x2217;
+pub const GL_MAX_VERTEX_UNIFORM_COMPONENTS: u32 = 0x8B4A;
+pub const GL_MAX_FRAGMENT_UNIFORM_COMPONENTS: u32 = 0x8B49;
 pub const GL_CLAMP_TO_BORDER: u32 = 0x812D;
 pub const GL_TEXTURE_BORDER_COLOR: u32 = 0x1004;
 pub const GL_UNPACK_ALIGNMENT: u32 = 3317;
+pub const GL_UNPACK_ROW_LENGTH: u32 = 0x0CF2;
+pub const GL_BGRA: u32 = 0x80E1;
+pub const GL_PACK_ALIGNMENT: u32 = 3333;
+pub const GL_MAP_READ_BIT: u32 = 0x0001;
+pub const GL_COPY_READ_BUFFER: u32 = 0x8F36;
+pub const GL_TRANSFORM_FEEDBACK_BUFFER: u32 = 0x8C8E;
+pub const GL_TRANSFORM_FEEDBACK: u32 = 0x8E22;
+pub const GL_PIXEL_PACK_BUFFER: u32 = 0x88EB;
+pub const GL_PIXEL_UNPACK_BUFFER: u32 = 0x88EC;
+pub const GL_SEPARATE_ATTRIBS: u32 = 0x8C8D;
+pub const GL_RASTERIZER_DISCARD: u32 = 0x8C89;
+pub const GL_TEXTURE_COMPARE_MODE: u32 = 0x884C;
+pub const GL_TEXTURE_COMPARE_FUNC: u32 = 0x884D;
+pub const GL_COMPARE_REF_TO_TEXTURE: u32 = 0x884E;
 pub const GL_TEXTURE_SWIZZLE_R: u32 = 36418;
 pub const GL_TEXTURE_SWIZZLE_G: u32 = 36419;
 pub const GL_TEXTURE_SWIZZLE_B: u32 = 36420;
@@ -816,6 +859,12 @@ extern "C" {
         size: GLsizeiptr,
     );
     pub fn glBindBufferBase(target: GLenum, index: GLuint, buffer: GLuint);
+    pub fn glGetBufferSubData(
+        target: GLenum,
+        offset: GLintptr,
+        size: GLsizeiptr,
+        data: *mut ::core::ffi::c_void,
+    );
     pub fn glTransformFeedbackVaryings(
         program: GLuint,
         count: GLsizei,
@@ -986,6 +1035,25 @@ extern "C" {
         bufSize: GLsizei,
         params: *mut GLint,
     );
+    pub fn glEnablei(target: GLenum, index: GLuint);
+    pub fn glDisablei(target: GLenum, index: GLuint);
+    pub fn glBlendFuncSeparatei(
+        buf: GLuint,
+        srcRGB: GLenum,
+        dstRGB: GLenum,
+        srcAlpha: GLenum,
+        dstAlpha: GLenum,
+    );
+    pub fn glBlendEquationSeparatei(buf: GLuint, modeRGB: GLenum, modeAlpha: GLenum);
+    pub fn glPolygonMode(face: GLenum, mode: GLenum);
+    pub fn glDrawElementsInstancedBaseInstance(
+        mode: GLenum,
+        count: GLsizei,
+        type_: GLenum,
+        indices: *const ::core::ffi::c_void,
+        instancecount: GLsizei,
+        baseinstance: GLuint,
+    );
 }
 
 pub unsafe fn is_gl2() -> bool {
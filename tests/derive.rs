@@ -0,0 +1,59 @@
+//! `#[derive(Uniforms, Vertex)]` on real structs, `cargo test --features derive`.
+#![cfg(feature = "derive")]
+
+use miniquad::*;
+
+#[derive(Uniforms)]
+#[uniforms(std140)]
+#[repr(C)]
+struct Globals {
+    mvp: [[f32; 4]; 4],
+    #[uniform(name = "lightColors")]
+    light_colors: [[f32; 4]; 8],
+    offset: [f32; 2],
+    #[uniform(Int1)]
+    mode: u32,
+    time: f32,
+}
+
+#[derive(Vertex)]
+#[repr(C)]
+struct QuadVertex {
+    pos: [f32; 2],
+    uv: [f32; 2],
+    #[vertex(Byte4Norm, name = "in_color")]
+    color: [u8; 4],
+}
+
+#[test]
+fn uniforms() {
+    let descs = Globals::uniform_descs();
+    let names: Vec<&str> = descs.iter().map(|desc| &desc.name[..]).collect();
+    assert_eq!(names, ["mvp", "lightColors", "offset", "mode", "time"]);
+    assert!(matches!(descs[0].uniform_type, UniformType::Mat4));
+    assert!(matches!(descs[1].uniform_type, UniformType::Float4));
+    assert!(matches!(descs[2].uniform_type, UniformType::Float2));
+    assert!(matches!(descs[3].uniform_type, UniformType::Int1));
+    assert!(matches!(descs[4].uniform_type, UniformType::Float1));
+    let counts: Vec<usize> = descs.iter().map(|desc| desc.array_count).collect();
+    assert_eq!(counts, [1, 8, 1, 1, 1]);
+    assert_eq!(Globals::uniform_layout().uniforms.len(), 5);
+}
+
+#[test]
+fn vertex() {
+    let attributes = QuadVertex::attributes(1);
+    let attributes: Vec<(&str, VertexFormat, usize)> = attributes
+        .iter()
+        .map(|attribute| (attribute.name, attribute.format, attribute.buffer_index))
+        .collect();
+    assert_eq!(
+        attributes,
+        [
+            ("pos", VertexFormat::Float2, 1),
+            ("uv", VertexFormat::Float2, 1),
+            ("in_color", VertexFormat::Byte4Norm, 1),
+        ]
+    );
+    assert_eq!(QuadVertex::buffer_layout().stride, 20);
+}